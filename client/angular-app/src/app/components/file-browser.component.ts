@@ -19,7 +19,10 @@ import { ActiveFilterState, FilterBarComponent, FilterPreset } from './filter-ba
 import { TabBarComponent } from './tab-bar.component';
 
 // Archive types (from archive.service.ts)
-import { ArchiveService, ArchiveEntry, ArchiveListing, isArchive, archiveFormat } from '../services/archive.service';
+import {
+  ArchiveService, ArchiveEntry, ArchiveListing, ArchiveCommandError, PASSWORD_ERROR_CODES,
+  isArchive, archiveFormat,
+} from '../services/archive.service';
 
 // ============================================================================
 // Archive Navigation Types
@@ -33,6 +36,8 @@ interface ArchiveStackFrame {
   innerPath: string;
   /** Listing returned from the server. */
   listing: ArchiveListing;
+  /** Password entered for this archive, reused for deeper levels. */
+  password?: string;
 }
 
 // ============================================================================
@@ -311,6 +316,15 @@ export class FileBrowserComponent implements OnInit, OnDestroy {
     return pane.archiveStack.length > 0;
   }
 
+  /**
+   * True inside an archive nested in another one. The server only edits
+   * the outer archive, so adding and deleting are unavailable there.
+   */
+  isInNestedArchive(pane: BrowserPane): boolean {
+    const frame = pane.archiveStack[pane.archiveStack.length - 1];
+    return !!frame && frame.innerPath.includes('!/');
+  }
+
   /**
    * Navigate into an archive (or a subdirectory inside an archive).
    * Pushes a new frame onto the stack and renders archive entries as FileInfo.
@@ -319,10 +333,13 @@ export class FileBrowserComponent implements OnInit, OnDestroy {
     pane.loading = true;
     pane.error   = null;
     try {
-      const listing = await this.archiveService.listArchive(archivePath, innerPath);
+      const parent = pane.archiveStack[pane.archiveStack.length - 1];
+      const known  = parent?.archivePath === archivePath ? parent.password : undefined;
+      const { result: listing, password } = await this.withArchivePassword(
+        archivePath, known, pw => this.archiveService.listArchive(archivePath, innerPath, pw));
       if (!listing) throw new Error('Empty response from server');
 
-      const frame: ArchiveStackFrame = { archivePath, innerPath, listing };
+      const frame: ArchiveStackFrame = { archivePath, innerPath, listing, password };
       pane.archiveStack.push(frame);
       pane.currentArchiveListing = listing;
 
@@ -380,7 +397,10 @@ export class FileBrowserComponent implements OnInit, OnDestroy {
 
     pane.loading = true;
     try {
-      await this.archiveService.extractArchive(frame.archivePath, destination, innerPaths);
+      const { password } = await this.withArchivePassword(
+        frame.archivePath, frame.password,
+        pw => this.archiveService.extractArchive(frame.archivePath, destination, innerPaths, pw));
+      this.rememberArchivePassword(pane, frame.archivePath, password);
       alert(`Extracted successfully to ${destination}`);
     } catch (error: any) {
       alert(`Extraction failed: ${error.message}`);
@@ -389,6 +409,39 @@ export class FileBrowserComponent implements OnInit, OnDestroy {
     }
   }
 
+  /**
+   * Run an archive command, prompting for a password and retrying while the
+   * server reports the archive as encrypted. Returns the password that worked.
+   */
+  private async withArchivePassword<T>(
+    archivePath: string,
+    password: string | undefined,
+    run: (password?: string) => Promise<T>,
+  ): Promise<{ result: T; password?: string }> {
+    for (;;) {
+      try {
+        return { result: await run(password), password };
+      } catch (error: any) {
+        if (!(error instanceof ArchiveCommandError) || !PASSWORD_ERROR_CODES.includes(error.code)) {
+          throw error;
+        }
+        const name  = archivePath.split(/[\\/]/).pop() ?? archivePath;
+        const retry = error.code === 'WRONG_PASSWORD' ? 'Wrong password. ' : '';
+        const entered = prompt(`${retry}Password for ${name}:`);
+        if (entered === null) throw error;
+        password = entered;
+      }
+    }
+  }
+
+  /** Store a password that worked on every stack frame of that archive. */
+  private rememberArchivePassword(pane: BrowserPane, archivePath: string, password?: string): void {
+    if (password === undefined) return;
+    for (const frame of pane.archiveStack) {
+      if (frame.archivePath === archivePath) frame.password = password;
+    }
+  }
+
   // ────────────────────────────────────────────────────────────────────────────
   // Converts ArchiveListing entries → FileInfo[] (adds '..' entry at the top)
   // ────────────────────────────────────────────────────────────────────────────
@@ -417,16 +470,32 @@ export class FileBrowserComponent implements OnInit, OnDestroy {
       name:        entry.name,
       // Store the inner path so selection / extraction works correctly
       path:        entry.innerPath,
-      type:        entry.type === 'DIRECTORY' ? FileType.DIRECTORY : FileType.FILE,
+      type:        entry.type === 'DIRECTORY' ? FileType.DIRECTORY
+                 : entry.type === 'SYMLINK'   ? FileType.SYMLINK
+                 : FileType.FILE,
       size:        entry.size,
       modified:    entry.modified,
-      permissions: entry.type === 'DIRECTORY' ? 'drwxr-xr-x' : '-rw-r--r--',
+      permissions: this.archiveEntryPermissions(entry),
       isHidden:    entry.name.startsWith('.'),
       created:     entry.modified,
       accessed:    entry.modified,
     };
   }
 
+  /** `ls -l` style permissions from the entry's stored Unix mode. */
+  private archiveEntryPermissions(entry: ArchiveEntry): string {
+    const kind = entry.type === 'DIRECTORY' ? 'd' : entry.type === 'SYMLINK' ? 'l' : '-';
+    if (entry.mode === undefined) {
+      return entry.type === 'DIRECTORY' ? 'drwxr-xr-x' : `${kind}rw-r--r--`;
+    }
+    const bits = 'rwxrwxrwx';
+    let perms = kind;
+    for (let i = 0; i < 9; i++) {
+      perms += entry.mode & (0o400 >> i) ? bits[i] : '-';
+    }
+    return perms;
+  }
+
   // ============================================================================
   // Unified Navigation Entry Point (used by template click handler)
   // ============================================================================
@@ -443,8 +512,11 @@ export class FileBrowserComponent implements OnInit, OnDestroy {
       const frame = pane.archiveStack[pane.archiveStack.length - 1];
       if (entry.type === FileType.DIRECTORY) {
         await this.loadArchive(pane, frame.archivePath, entry.path);
+      } else if (isArchive(entry.name)) {
+        // Archive inside the archive: browse it through a nested path
+        await this.loadArchive(pane, frame.archivePath, `${entry.path}!/`);
       }
-      // Files inside archives are read-only view — no action for now
+      // Other files inside archives are read-only view — no action for now
       return;
     }
 
@@ -487,7 +559,8 @@ export class FileBrowserComponent implements OnInit, OnDestroy {
         return { label: archiveName, frameIndex: i };
       }
       // Deeper frame: show the last segment of the inner path
-      const segment = frame.innerPath.split('/').pop() ?? frame.innerPath;
+      // A nested archive root ('lib/inner.jar!/') is labelled with the archive's name
+      const segment = frame.innerPath.replace(/!\/$/, '').split('/').pop() ?? frame.innerPath;
       return { label: segment, frameIndex: i };
     });
   }
@@ -647,8 +720,23 @@ export class FileBrowserComponent implements OnInit, OnDestroy {
   }
 
   async deleteSelected(pane: BrowserPane): Promise<void> {
-    if (this.isInArchive(pane)) { alert('Cannot delete files inside an archive.'); return; }
     if (pane.selectedFiles.size === 0) return;
+    if (this.isInNestedArchive(pane)) { alert('Cannot delete from an archive nested in another archive.'); return; }
+    if (this.isInArchive(pane)) {
+      if (!confirm(`Delete ${pane.selectedFiles.size} item(s) from the archive?`)) return;
+      const frame = pane.archiveStack[pane.archiveStack.length - 1];
+      pane.loading = true;
+      try {
+        const job = await this.archiveService.archiveDelete(frame.archivePath, Array.from(pane.selectedFiles));
+        await this.archiveService.waitForJob(job);
+      } catch (error: any) {
+        alert(`Failed to delete from archive: ${error.message}`);
+      } finally {
+        pane.loading = false;
+      }
+      await this.refresh(pane);
+      return;
+    }
     if (!confirm(`Delete ${pane.selectedFiles.size} item(s)?`)) return;
     for (const path of Array.from(pane.selectedFiles)) {
       try {
@@ -679,6 +767,10 @@ export class FileBrowserComponent implements OnInit, OnDestroy {
       return;
     }
     if (sourcePane.selectedFiles.size === 0) { alert('No files selected'); return; }
+    if (this.isInArchive(targetPane)) {
+      await this.addToArchive(targetPane, Array.from(sourcePane.selectedFiles));
+      return;
+    }
     for (const sourcePath of Array.from(sourcePane.selectedFiles)) {
       const fileName = sourcePath.split('/').pop() || 'file';
       const destPath = `${targetPane.currentPath}/${fileName}`.replace('//', '/');
@@ -707,6 +799,52 @@ export class FileBrowserComponent implements OnInit, OnDestroy {
     await this.refresh(targetPane);
   }
 
+  /** Add filesystem paths to the archive open in `pane`, at its current inner path. */
+  async addToArchive(pane: BrowserPane, sources: string[]): Promise<void> {
+    if (this.isInNestedArchive(pane)) { alert('Cannot add to an archive nested in another archive.'); return; }
+    const frame = pane.archiveStack[pane.archiveStack.length - 1];
+    pane.loading = true;
+    try {
+      const job = await this.archiveService.archiveAdd(frame.archivePath, frame.innerPath, sources);
+      await this.archiveService.waitForJob(job);
+    } catch (error: any) {
+      alert(`Failed to add to archive: ${error.message}`);
+    } finally {
+      pane.loading = false;
+    }
+    await this.refresh(pane);
+  }
+
+  // ============================================================================
+  // Drag & Drop
+  // ============================================================================
+
+  private dragSourcePane: BrowserPane | null = null;
+
+  onDragStart(pane: BrowserPane, entry: FileInfo, event: DragEvent): void {
+    if (entry.name === '..') { event.preventDefault(); return; }
+    if (!pane.selectedFiles.has(entry.path)) {
+      pane.selectedFiles.clear();
+      pane.selectedFiles.add(entry.path);
+    }
+    this.dragSourcePane = pane;
+    event.dataTransfer?.setData('text/plain', Array.from(pane.selectedFiles).join('\n'));
+  }
+
+  onDragOver(pane: BrowserPane, event: DragEvent): void {
+    if (this.isInNestedArchive(pane)) return;
+    if (this.dragSourcePane && this.dragSourcePane !== pane) event.preventDefault();
+  }
+
+  /** Dropping onto the other pane copies; onto an archive pane this becomes ARCHIVE_ADD. */
+  async onDrop(pane: BrowserPane, event: DragEvent): Promise<void> {
+    event.preventDefault();
+    const source = this.dragSourcePane;
+    this.dragSourcePane = null;
+    if (!source || source === pane || this.isInNestedArchive(pane)) return;
+    await this.copyToOtherPane(source, pane);
+  }
+
   // ============================================================================
   // Display Helpers
   // ============================================================================
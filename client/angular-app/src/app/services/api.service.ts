@@ -19,6 +19,12 @@ import {
   CopyFileCommand,
   GetFileInfoCommand,
   SearchFilesCommand,
+  SearchContentCommand,
+  ContentSearchOptions,
+  IndexStatusCommand,
+  ReindexCommand,
+  QueryIndexCommand,
+  CompletePathCommand,
   // Response data shapes
   OSInfoResponse,
   DrivesList,
@@ -28,7 +34,12 @@ import {
   FileInfo,
   OperationResult,
   SearchResult,
+  ContentMatches,
+  ContentSearchResult,
+  IndexStatus,
+  PathCompletions,
 } from '@shared/protocol';
+import type { JobInfo } from './archive.service';
 
 @Injectable({
   providedIn: 'root',
@@ -40,6 +51,11 @@ export class ApiService {
   private ws: WebSocket | null = null;
   private readonly wsMessages$ = new Subject<Response>();
   private commandCounter = 0;
+  /** Running streamed commands, by command id. */
+  private readonly streams = new Map<
+    string,
+    { onChunk: (chunk: unknown) => void; done: (response: Response) => void }
+  >();
 
   // -------------------------------------------------------------------------
   // WebSocket
@@ -59,7 +75,16 @@ export class ApiService {
       try {
         const message = JSON.parse(event.data as string) as WebSocketMessage;
         if (message.type === MessageType.RESPONSE) {
-          this.wsMessages$.next(message.payload as Response);
+          const response = message.payload as Response & { command_id?: string };
+          const id = response.command_id ?? response.commandId;
+          const stream = this.streams.get(id);
+          if (stream) {
+            this.streams.delete(id);
+            stream.done(response);
+          }
+          this.wsMessages$.next(response);
+        } else if (message.type === MessageType.CONTENT_MATCHES && message.command_id) {
+          this.streams.get(message.command_id)?.onChunk(message.payload);
         }
       } catch (error) {
         console.error('Failed to parse WebSocket message:', error);
@@ -71,6 +96,15 @@ export class ApiService {
     this.ws.onclose = () => {
       console.log('WebSocket disconnected');
       this.ws = null;
+      for (const [id, stream] of this.streams) {
+        stream.done({
+          commandId: id,
+          status: ResponseStatus.ERROR,
+          timestamp: Date.now(),
+          error: { code: 'DISCONNECTED', message: 'WebSocket disconnected' },
+        });
+      }
+      this.streams.clear();
     };
 
     return this.wsMessages$.asObservable();
@@ -96,6 +130,38 @@ export class ApiService {
     this.ws.send(JSON.stringify(message));
   }
 
+  /** Connect if needed and resolve once the WebSocket is open. */
+  private async openWebSocket(): Promise<void> {
+    this.connectWebSocket();
+    const ws = this.ws!;
+    if (ws.readyState === WebSocket.OPEN) {
+      return;
+    }
+    await new Promise<void>((resolve, reject) => {
+      ws.addEventListener('open', () => resolve(), { once: true });
+      ws.addEventListener('close', () => reject(new Error('WebSocket connection failed')), { once: true });
+    });
+  }
+
+  /**
+   * Send a command over the WebSocket, passing each streamed chunk to
+   * `onChunk` until its response arrives.
+   */
+  private async sendStreamingCommand<T, C>(
+    command: Command,
+    onChunk: (chunk: C) => void,
+  ): Promise<Response<T>> {
+    await this.openWebSocket();
+    const done = new Promise<Response<T>>((resolve) => {
+      this.streams.set(command.id, {
+        onChunk: (chunk) => onChunk(chunk as C),
+        done: (response) => resolve(response as Response<T>),
+      });
+    });
+    this.sendWebSocketCommand(command);
+    return done;
+  }
+
   // -------------------------------------------------------------------------
   // HTTP
   // -------------------------------------------------------------------------
@@ -115,6 +181,25 @@ export class ApiService {
     return res.json() as Promise<Response<T>>;
   }
 
+  /**
+   * URL of a server GET route, with the defined parameters as its query.
+   * Array values repeat their parameter.
+   */
+  routeUrl(
+    route: string,
+    params: Record<string, string | number | boolean | string[] | undefined> = {},
+  ): string {
+    const url = new URL(route, this.serverUrl);
+    for (const [key, value] of Object.entries(params)) {
+      for (const item of Array.isArray(value) ? value : [value]) {
+        if (item !== undefined) {
+          url.searchParams.append(key, String(item));
+        }
+      }
+    }
+    return url.toString();
+  }
+
   /** Throw if the response carries an error, otherwise return the data payload. */
   private unwrap<T>(response: Response<T>): T {
     if (response.status === ResponseStatus.ERROR) {
@@ -261,22 +346,100 @@ export class ApiService {
     return this.unwrap(response);
   }
 
-  /** Search for files matching a pattern within a directory. */
+  /**
+   * Search for files matching a query within a directory, e.g.
+   * `*.log size>10M modified<7d` (see `SearchFilesCommand.pattern`).
+   */
   async searchFiles(
     path: string,
     pattern: string,
     recursive = true,
+    includeArchives = false,
+    fuzzy = false,
+    recentPaths: string[] = [],
   ): Promise<SearchResult> {
     const command: SearchFilesCommand = {
       ...this.baseCommand(CommandType.SEARCH_FILES),
       path,
       pattern,
       recursive,
+      include_archives: includeArchives,
+      fuzzy,
+      recent_paths: recentPaths,
     };
     const response = await this.sendHttpCommand<SearchResult>(command);
     return this.unwrap(response);
   }
 
+  /**
+   * Search file contents below `path` for `query`, handing each file's
+   * matches to `onMatches` as the server finds them. Resolves with the
+   * totals once the search is done.
+   */
+  async searchContent(
+    path: string,
+    query: string,
+    onMatches: (matches: ContentMatches) => void,
+    options: ContentSearchOptions = {},
+  ): Promise<ContentSearchResult> {
+    const command: SearchContentCommand = {
+      ...this.baseCommand(CommandType.SEARCH_CONTENT),
+      path,
+      query,
+      options,
+    };
+    const response = await this.sendStreamingCommand<ContentSearchResult, ContentMatches>(
+      command,
+      onMatches,
+    );
+    return this.unwrap(response);
+  }
+
+  /** Roots, sizes and scan state of the server's search index. */
+  async indexStatus(): Promise<IndexStatus> {
+    const command: IndexStatusCommand = this.baseCommand(CommandType.INDEX_STATUS);
+    const response = await this.sendHttpCommand<IndexStatus>(command);
+    return this.unwrap(response);
+  }
+
+  /**
+   * Rescan the search index, optionally replacing its roots and exclude
+   * globs. Resolves with the background job; poll it with GET_JOB_STATUS.
+   */
+  async reindex(roots?: string[], exclude?: string[]): Promise<JobInfo> {
+    const command: ReindexCommand = {
+      ...this.baseCommand(CommandType.REINDEX),
+      roots,
+      exclude,
+    };
+    const response = await this.sendHttpCommand<JobInfo>(command);
+    return this.unwrap(response);
+  }
+
+  /** Look up a SEARCH_FILES query in the search index, optionally below `path`. */
+  async queryIndex(query: string, path?: string, limit?: number): Promise<SearchResult> {
+    const command: QueryIndexCommand = {
+      ...this.baseCommand(CommandType.QUERY_INDEX),
+      query,
+      path,
+      limit,
+    };
+    const response = await this.sendHttpCommand<SearchResult>(command);
+    return this.unwrap(response);
+  }
+
+  /** Ranked completions for a partially typed path, for the address bar. */
+  async completePath(path: string, cwd?: string, limit?: number): Promise<PathCompletions> {
+    const command: CompletePathCommand = {
+      ...this.baseCommand(CommandType.COMPLETE_PATH),
+      path,
+      cwd,
+      limit,
+    };
+    const response = await this.sendHttpCommand<PathCompletions>(command);
+    return this.unwrap(response);
+  }
+
   /** Ping the server health endpoint. */
   async checkHealth(): Promise<boolean> {
     try {
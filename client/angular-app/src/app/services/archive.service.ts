@@ -12,16 +12,25 @@ import {
 // Archive Response Types (matching Rust protocol)
 // ============================================================================
 
-export type ArchiveEntryType = 'FILE' | 'DIRECTORY';
+export type ArchiveEntryType = 'FILE' | 'DIRECTORY' | 'SYMLINK';
 
 export interface ArchiveEntry {
   name: string;
   innerPath: string;
   type: ArchiveEntryType;
-  size: number;
+  size: number;       // Directories: total of all files below
   compressedSize: number;
   modified: number;   // Unix seconds (0 = unknown)
-  compression: string;
+  compression: string; // Method name, e.g. "Deflate", "LZMA2:24"
+  compressionLevel?: string; // e.g. "Maximum", when the format records it
+  encrypted: boolean; // Data needs a password to read
+  comment?: string;
+  mode?: number;      // Unix mode bits including the file type
+  owner?: string;     // User name, or numeric uid when only that is stored
+  group?: string;     // Group name, or numeric gid
+  symlinkTarget?: string;
+  crc32?: number;
+  hostOs?: string;    // e.g. "Unix", "MS-DOS", "NTFS"
 }
 
 export interface ArchiveListing {
@@ -30,6 +39,18 @@ export interface ArchiveListing {
   format: string;      // Archive format as string
   entries: ArchiveEntry[];
   totalSize: number;
+  metadata?: ArchiveMetadata;
+}
+
+/** Archive-level details: comments, disc image labels and package fields. */
+export interface ArchiveMetadata {
+  volumeLabel?: string;
+  comment?: string;    // zip, 7z, RAR and gzip archive comments
+  created?: number;    // Unix timestamp
+  /** Encoding of zip entry names stored without the UTF-8 flag, e.g. 'cp437', 'ibm866'. */
+  nameEncoding?: string;
+  /** Package fields of a .deb or .rpm: name, version, depends, ... */
+  package?: Record<string, string>;
 }
 
 export interface ReadArchiveFileResponse {
@@ -45,6 +66,69 @@ export interface ExtractArchiveResponse {
   affectedPaths: string[];
 }
 
+export interface CreateArchiveOptions {
+  /** Format-specific level; the server picks the format default when omitted */
+  compression_level?: number;
+  /** Stored names are relative to this directory (default: each source's parent) */
+  base_path?: string;
+  /** Glob patterns of entries to skip, e.g. '*.log' */
+  exclude?: string[];
+  /** Store symlinks as links instead of their targets (ignored for 7z) */
+  store_symlinks?: boolean;
+  /** Encrypt with this password (7z only) */
+  password?: string;
+  /** Write volumes of this many bytes (<destination>.001, .002, ...) */
+  split_size?: number;
+}
+
+/** Error codes the server uses for encrypted archives. */
+export const PASSWORD_ERROR_CODES = ['PASSWORD_REQUIRED', 'WRONG_PASSWORD'];
+
+/** Error thrown by ArchiveService, carrying the server's error code. */
+export class ArchiveCommandError extends Error {
+  constructor(message: string, public readonly code: string) {
+    super(message);
+  }
+}
+
+export type CreatableArchiveFormat =
+  | 'zip' | '7z' | 'tar' | 'tar.gz' | 'tar.bz2' | 'tar.xz' | 'tar.zst';
+
+/** Result of a TEST_ARCHIVE job. */
+export interface ArchiveTestReport {
+  archivePath: string;
+  format: string;
+  ok: boolean;
+  passed: number;
+  failed: number;
+  entries: EntryTestResult[];
+  error?: string;      // Failure not tied to one entry (e.g. damaged stream)
+}
+
+export interface EntryTestResult {
+  innerPath: string;
+  ok: boolean;
+  size: number;
+  error?: string;
+}
+
+export type JobState = 'RUNNING' | 'COMPLETED' | 'FAILED' | 'CANCELLED';
+
+export interface JobInfo {
+  jobId: string;
+  kind: string;
+  state: JobState;
+  totalItems: number;
+  processedItems: number;
+  totalBytes: number;
+  processedBytes: number;
+  currentItem?: string;
+  started: number;
+  finished?: number;
+  error?: string;
+  result?: unknown;
+}
+
 // ============================================================================
 // Archive Format Detection (matching Rust ArchiveFormat)
 // ============================================================================
@@ -54,13 +138,21 @@ export type ArchiveFormat =
   | 'zip' | 'tar' | 'tar.gz' | 'tar.bz2' | 'tar.xz' | 'tar.zst'
   | 'gz' | 'bz2' | 'xz' | 'zst'
   // Additional formats
-  | '7z' | 'rar' | 'cab' | 'arj' | 'lzh' | 'ace';
+  | '7z' | 'rar' | 'cab' | 'iso' | 'arj' | 'lzh' | 'ace'
+  // Packages
+  | 'deb' | 'rpm' | 'ar' | 'cpio';
+
+// Containers that are plain ZIP files under another name
+const ZIP_EXTENSIONS = [
+  '.zip', '.jar', '.war', '.ear', '.apk', '.aar', '.ipa', '.xpi', '.crx',
+  '.whl', '.nupkg', '.vsix', '.epub',
+  '.docx', '.xlsx', '.pptx', '.odt', '.ods', '.odp',
+];
 
 // Complete list of archive extensions (updated with all supported formats)
 const ARCHIVE_EXTENSIONS = [
-  // ZIP and Office documents (all ZIP-based)
-  '.zip', '.jar', '.war', '.ear', '.apk',
-  '.docx', '.xlsx', '.pptx', '.odt', '.ods', '.odp',
+  // ZIP and ZIP-based containers (packages, Office documents, e-books)
+  ...ZIP_EXTENSIONS,
   
   // TAR variants
   '.tar', '.tar.gz', '.tgz', '.tar.bz2', '.tbz2', '.tbz',
@@ -73,14 +165,22 @@ const ARCHIVE_EXTENSIONS = [
   '.7z',           // 7-Zip
   '.rar',          // RAR Archive
   '.cab',          // CAB Archive
+  '.iso',          // ISO 9660 / UDF disc image
   '.arj',          // ARJ Archive
   '.lzh', '.lha',  // LZH Archive
   '.ace',          // ACE Archive
+
+  // Packages and Unix archives
+  '.deb', '.udeb', // Debian package
+  '.rpm',          // RPM package
+  '.a', '.ar',     // ar archive (static libraries)
+  '.cpio',         // cpio archive
 ];
 
 /**
- * Check if a file path has a supported archive extension
- * Matches the Rust ArchiveFormat::detect function
+ * Check if a file path has a supported archive extension.
+ * The server sniffs signature bytes as well, so this is only a hint; the
+ * detected format is returned in ArchiveListing.format.
  */
 export function isArchive(path: string): boolean {
   const lower = path.toLowerCase();
@@ -120,10 +220,7 @@ export function getArchiveFormat(path: string): ArchiveFormat | 'unknown' {
   }
   
   // ZIP and ZIP-based formats (Office docs, JAR, etc.)
-  if (lower.endsWith('.zip') || lower.endsWith('.jar') || lower.endsWith('.war') ||
-      lower.endsWith('.ear') || lower.endsWith('.apk') || lower.endsWith('.docx') ||
-      lower.endsWith('.xlsx') || lower.endsWith('.pptx') || lower.endsWith('.odt') ||
-      lower.endsWith('.ods') || lower.endsWith('.odp')) {
+  if (ZIP_EXTENSIONS.some(ext => lower.endsWith(ext))) {
     return 'zip';
   }
   
@@ -151,6 +248,9 @@ export function getArchiveFormat(path: string): ArchiveFormat | 'unknown' {
   if (lower.endsWith('.cab')) {
     return 'cab';
   }
+  if (lower.endsWith('.iso')) {
+    return 'iso';
+  }
   if (lower.endsWith('.arj')) {
     return 'arj';
   }
@@ -161,6 +261,20 @@ export function getArchiveFormat(path: string): ArchiveFormat | 'unknown' {
     return 'ace';
   }
   
+  // Packages
+  if (lower.endsWith('.deb') || lower.endsWith('.udeb')) {
+    return 'deb';
+  }
+  if (lower.endsWith('.rpm')) {
+    return 'rpm';
+  }
+  if (lower.endsWith('.a') || lower.endsWith('.ar')) {
+    return 'ar';
+  }
+  if (lower.endsWith('.cpio')) {
+    return 'cpio';
+  }
+  
   return 'unknown';
 }
 
@@ -185,9 +299,16 @@ export function getFormatDescription(format: ArchiveFormat | string): string {
     '7z': '7-Zip Archive',
     'rar': 'RAR Archive',
     'cab': 'CAB Archive',
+    'iso': 'ISO Disc Image',
     'arj': 'ARJ Archive',
     'lzh': 'LZH Archive',
     'ace': 'ACE Archive',
+    
+    // Packages
+    'deb': 'Debian Package',
+    'rpm': 'RPM Package',
+    'ar': 'ar Archive',
+    'cpio': 'cpio Archive',
   };
   return descriptions[format] || `Unknown (${format})`;
 }
@@ -210,9 +331,14 @@ export function getFormatExtension(format: ArchiveFormat): string {
     '7z': '.7z',
     'rar': '.rar',
     'cab': '.cab',
+    'iso': '.iso',
     'arj': '.arj',
     'lzh': '.lzh',
     'ace': '.ace',
+    'deb': '.deb',
+    'rpm': '.rpm',
+    'ar': '.a',
+    'cpio': '.cpio',
   };
   return extensions[format] || '.unknown';
 }
@@ -248,7 +374,7 @@ export class ArchiveService {
     const response = await this.api['sendHttpCommand'](command as Command) as Response<T>;
     
     if (!this.isSuccessResponse(response)) {
-      throw new Error(response.error.message);
+      throw new ArchiveCommandError(response.error.message, response.error.code);
     }
     
     return response.data;
@@ -259,11 +385,23 @@ export class ArchiveService {
    * 
    * @param archivePath  Absolute path to the archive file on disk.
    * @param innerPath    Inner directory to list (empty string = root).
+   * @param password     Password for encrypted archives.
+   * @param recursive    Return every entry below innerPath as a flat list.
+   * @param nameEncoding Encoding of legacy zip entry names; detected when omitted.
    */
-  async listArchive(archivePath: string, innerPath: string = ''): Promise<ArchiveListing> {
+  async listArchive(
+    archivePath: string,
+    innerPath: string = '',
+    password?: string,
+    recursive = false,
+    nameEncoding?: string,
+  ): Promise<ArchiveListing> {
     const command = this.baseCommand('LIST_ARCHIVE', {
       archive_path: archivePath,
       inner_path: innerPath,
+      password,
+      recursive,
+      name_encoding: nameEncoding,
     });
     
     return this.sendCommand<ArchiveListing>(command);
@@ -276,42 +414,260 @@ export class ArchiveService {
    * @param archivePath  Absolute path to the archive file on disk.
    * @param innerPath    Path of the file inside the archive.
    * @param encoding     'utf8' | 'base64' (defaults to 'utf8')
+   * @param password     Password for encrypted entries.
+   * @param nameEncoding Encoding of legacy zip entry names, as for listArchive().
    */
   async readArchiveFile(
     archivePath: string, 
     innerPath: string, 
-    encoding: 'utf8' | 'base64' = 'utf8'
+    encoding: 'utf8' | 'base64' = 'utf8',
+    password?: string,
+    nameEncoding?: string,
   ): Promise<ReadArchiveFileResponse> {
     const command = this.baseCommand('READ_ARCHIVE_FILE', {
       archive_path: archivePath,
       inner_path: innerPath,
       encoding,
+      password,
+      name_encoding: nameEncoding,
     });
     
     return this.sendCommand<ReadArchiveFileResponse>(command);
   }
 
+  /**
+   * URL streaming one file from inside an archive, for media elements,
+   * previews and downloads. Stored zip entries support Range requests.
+   *
+   * @param archivePath  Absolute path to the archive file on disk.
+   * @param innerPath    Path of the file inside the archive.
+   * @param download     Ask the browser to save it rather than display it.
+   * @param password     Password for encrypted entries.
+   * @param nameEncoding Encoding of legacy zip entry names, as for listArchive().
+   */
+  archiveEntryUrl(
+    archivePath: string,
+    innerPath: string,
+    download = false,
+    password?: string,
+    nameEncoding?: string,
+  ): string {
+    return this.api.routeUrl('/api/archive/entry', {
+      archive_path: archivePath,
+      inner_path: innerPath,
+      download: download || undefined,
+      password,
+      name_encoding: nameEncoding,
+    });
+  }
+
+  /**
+   * URL streaming an archive of files and folders, built while it downloads
+   * ("download as zip"). Usable as a plain link; nothing is written on the server.
+   *
+   * @param paths   Files and directories to include.
+   * @param format  Any creatable format except 7z.
+   * @param name    Download name without extension; defaults to the single
+   *                path's name, or "download" for a selection.
+   * @param options Compression level, exclude globs and symlink handling.
+   */
+  archiveDownloadUrl(
+    paths: string[],
+    format: Exclude<CreatableArchiveFormat, '7z'> = 'zip',
+    name?: string,
+    options: Pick<CreateArchiveOptions, 'compression_level' | 'exclude' | 'store_symlinks'> = {},
+  ): string {
+    return this.api.routeUrl('/api/archive/download', {
+      path: paths,
+      format,
+      name,
+      level: options.compression_level,
+      exclude: options.exclude,
+      store_symlinks: options.store_symlinks || undefined,
+    });
+  }
+
   /**
    * Extract entries from an archive to a destination directory.
    * 
    * @param archivePath  Absolute path to the archive file on disk.
    * @param destination  Destination directory on the filesystem.
    * @param innerPaths   Specific inner paths to extract (empty = all).
+   * @param password     Password for encrypted entries.
+   * @param nameEncoding Encoding of legacy zip entry names, as for listArchive().
    */
   async extractArchive(
     archivePath: string,
     destination: string,
     innerPaths: string[] = [],
+    password?: string,
+    nameEncoding?: string,
   ): Promise<ExtractArchiveResponse> {
     const command = this.baseCommand('EXTRACT_ARCHIVE', {
       archive_path: archivePath,
       destination,
       inner_paths: innerPaths,
+      password,
+      name_encoding: nameEncoding,
     });
     
     return this.sendCommand<ExtractArchiveResponse>(command);
   }
 
+  /**
+   * Create a new archive from files and folders.
+   * Runs as a server-side job; poll it with getJobStatus().
+   *
+   * @param sources      Files and directories to add.
+   * @param destination  Path of the archive to create.
+   * @param format       Archive format to write.
+   * @param options      Compression level, base path, exclusions, symlinks.
+   */
+  async createArchive(
+    sources: string[],
+    destination: string,
+    format: CreatableArchiveFormat,
+    options: CreateArchiveOptions = {},
+  ): Promise<JobInfo> {
+    const command = this.baseCommand('CREATE_ARCHIVE', {
+      sources,
+      destination,
+      format,
+      options,
+    });
+
+    return this.sendCommand<JobInfo>(command);
+  }
+
+  /**
+   * Add files and folders from disk to an existing zip or tar archive.
+   * Runs as a server-side job.
+   *
+   * @param archivePath  Archive to modify.
+   * @param innerPath    Directory inside the archive to add to ('' = root).
+   * @param sources      Files and directories on disk.
+   */
+  async archiveAdd(
+    archivePath: string,
+    innerPath: string,
+    sources: string[],
+    options: CreateArchiveOptions = {},
+  ): Promise<JobInfo> {
+    const command = this.baseCommand('ARCHIVE_ADD', {
+      archive_path: archivePath,
+      inner_path: innerPath,
+      sources,
+      options,
+    });
+
+    return this.sendCommand<JobInfo>(command);
+  }
+
+  /**
+   * Remove entries (folders recursively) from a zip or tar archive.
+   * Runs as a server-side job.
+   */
+  async archiveDelete(archivePath: string, innerPaths: string[]): Promise<JobInfo> {
+    const command = this.baseCommand('ARCHIVE_DELETE', {
+      archive_path: archivePath,
+      inner_paths: innerPaths,
+    });
+
+    return this.sendCommand<JobInfo>(command);
+  }
+
+  /**
+   * Rename or move an entry inside a zip or tar archive.
+   * Runs as a server-side job.
+   */
+  async archiveRename(
+    archivePath: string,
+    innerPath: string,
+    newInnerPath: string,
+  ): Promise<JobInfo> {
+    const command = this.baseCommand('ARCHIVE_RENAME', {
+      archive_path: archivePath,
+      inner_path: innerPath,
+      new_inner_path: newInnerPath,
+    });
+
+    return this.sendCommand<JobInfo>(command);
+  }
+
+  /**
+   * Verify every entry's checksums without extracting anything.
+   * Runs as a server-side job whose result is an ArchiveTestReport.
+   */
+  async testArchive(archivePath: string, password?: string): Promise<JobInfo> {
+    const command = this.baseCommand('TEST_ARCHIVE', {
+      archive_path: archivePath,
+      password,
+    });
+
+    return this.sendCommand<JobInfo>(command);
+  }
+
+  /**
+   * Convert an archive to another format (e.g. 7z → tar.zst) without
+   * extracting it. Paths, mtimes and modes are kept. Runs as a server-side job.
+   *
+   * @param archivePath  Archive to convert.
+   * @param destination  Path of the archive to create.
+   * @param format       Format to write.
+   * @param options      Level, exclusions (matched against inner paths),
+   *                     password and split size of the new archive.
+   * @param password     Password of the source archive.
+   */
+  async repackArchive(
+    archivePath: string,
+    destination: string,
+    format: CreatableArchiveFormat,
+    options: CreateArchiveOptions = {},
+    password?: string,
+  ): Promise<JobInfo> {
+    const command = this.baseCommand('REPACK_ARCHIVE', {
+      archive_path: archivePath,
+      destination,
+      format,
+      password,
+      options,
+    });
+
+    return this.sendCommand<JobInfo>(command);
+  }
+
+  /**
+   * Poll a job until it finishes. Resolves with the final snapshot and
+   * rejects when the job failed or was cancelled.
+   */
+  async waitForJob(job: JobInfo, intervalMs = 300): Promise<JobInfo> {
+    let current = job;
+    while (current.state === 'RUNNING') {
+      await new Promise(resolve => setTimeout(resolve, intervalMs));
+      current = await this.getJobStatus(current.jobId);
+    }
+    if (current.state !== 'COMPLETED') {
+      throw new Error(current.error || `Job ${current.state.toLowerCase()}`);
+    }
+    return current;
+  }
+
+  /**
+   * Get progress and, once finished, the result of a background job.
+   */
+  async getJobStatus(jobId: string): Promise<JobInfo> {
+    const command = this.baseCommand('GET_JOB_STATUS', { job_id: jobId });
+    return this.sendCommand<JobInfo>(command);
+  }
+
+  /**
+   * Ask the server to cancel a running job.
+   */
+  async cancelJob(jobId: string): Promise<JobInfo> {
+    const command = this.baseCommand('CANCEL_JOB', { job_id: jobId });
+    return this.sendCommand<JobInfo>(command);
+  }
+
   /**
    * Get compression ratio as a percentage string
    */
@@ -356,7 +712,8 @@ export class ArchiveService {
     const supportedFormats: ArchiveFormat[] = [
       'zip', 'tar', 'tar.gz', 'tar.bz2', 'tar.xz', 'tar.zst',
       'gz', 'bz2', 'xz', 'zst',
-      '7z', 'rar', 'cab', 'arj', 'lzh', 'ace'
+      '7z', 'rar', 'cab', 'iso', 'arj', 'lzh', 'ace',
+      'deb', 'rpm', 'ar', 'cpio'
     ];
     return supportedFormats.includes(format as ArchiveFormat);
   }
@@ -368,7 +725,8 @@ export class ArchiveService {
     return [
       'zip', 'tar', 'tar.gz', 'tar.bz2', 'tar.xz', 'tar.zst',
       'gz', 'bz2', 'xz', 'zst',
-      '7z', 'rar', 'cab', 'arj', 'lzh', 'ace'
+      '7z', 'rar', 'cab', 'iso', 'arj', 'lzh', 'ace',
+      'deb', 'rpm', 'ar', 'cpio'
     ];
   }
 
@@ -393,6 +751,10 @@ export class ArchiveService {
       'arj': '📦',
       'lzh': '📦',
       'ace': '📦',
+      'deb': '📦',
+      'rpm': '📦',
+      'ar': '📦',
+      'cpio': '📦',
     };
     return icons[format] || '📄';
   }
@@ -86,9 +86,9 @@ export class PathHistoryService {
     const historyMatches = this.getHistoryMatches(query);
     suggestions.push(...historyMatches);
 
-    // 2. Directory-based suggestions (autocomplete current directory)
-    if (query.startsWith('/') || query.startsWith('.')) {
-      const dirSuggestions = await this.getDirectorySuggestions(query);
+    // 2. Directory-based suggestions (server-side path completion)
+    if (/^[/.~$]/.test(query)) {
+      const dirSuggestions = await this.getDirectorySuggestions(query, currentPath);
       suggestions.push(...dirSuggestions);
     }
 
@@ -149,32 +149,16 @@ export class PathHistoryService {
   /**
    * Get directory-based suggestions
    */
-  private async getDirectorySuggestions(query: string): Promise<PathSuggestion[]> {
+  private async getDirectorySuggestions(query: string, currentPath: string): Promise<PathSuggestion[]> {
     try {
-      // Extract the directory part
-      const lastSlash = query.lastIndexOf('/');
-      const dirPath = query.substring(0, lastSlash + 1) || '/';
-      const prefix = query.substring(lastSlash + 1);
-
-      // List directory
-      const listing = await this.apiService.listDirectory(dirPath, false);
-      
-      const suggestions: PathSuggestion[] = [];
-      
-      for (const entry of listing.entries) {
-        if (entry.name.toLowerCase().startsWith(prefix.toLowerCase())) {
-          const fullPath = `${dirPath}${entry.name}`.replace('//', '/');
-          suggestions.push({
-            path: fullPath,
-            displayName: entry.name,
-            matchType: 'directory',
-            score: 90,
-            exists: true,
-          });
-        }
-      }
-
-      return suggestions;
+      const result = await this.apiService.completePath(query, currentPath);
+      return result.completions.map((completion): PathSuggestion => ({
+        path: completion.path,
+        displayName: completion.name,
+        matchType: 'directory',
+        score: 90 + completion.score,
+        exists: true,
+      }));
     } catch (error) {
       return [];
     }
@@ -1,32 +1,281 @@
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeMap, HashSet};
 use std::fs::File;
-use std::io::Read;
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::Path;
 use std::process::Command;
 
-use crate::protocol::{ArchiveEntry, ArchiveEntryType, ArchiveListing};
+use crate::jobs::{JobHandle, ProgressWriter};
+use attributes::ZipRecord;
+use names::NameEncoding;
+use volumes::Source;
+use crate::protocol::{
+    ArchiveEntry, ArchiveEntryType, ArchiveListing, ArchiveMetadata, ArchiveTestReport,
+    EntryTestResult,
+};
+
+mod attributes;
+mod cache;
+mod create;
+mod detect;
+mod edit;
+mod iso;
+mod names;
+mod package;
+mod repack;
+mod stream;
+mod volumes;
+
+pub(crate) use create::build_globset;
+pub use create::create_archive;
+pub use edit::{add_to_archive, delete_from_archive, rename_in_archive};
+pub use repack::repack_archive;
+pub use stream::stream_archive;
 
 // =======================
 // Archive Format Detection
 // =======================
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ArchiveFormat {
     Zip,
     SevenZip,
+    Tar,
+    TarGz,
+    TarBz2,
+    TarXz,
+    TarZst,
+    /// A single gzip/bzip2/xz/zstd-compressed file, not a tarball
+    Gz,
+    Bz2,
+    Xz,
+    Zst,
+    Cab,
+    Rar,
+    Iso,
+    Deb,
+    Rpm,
+    Ar,
+    /// cpio, plain or inside a gzip/bzip2/xz/zstd stream
+    Cpio,
 }
 
 impl ArchiveFormat {
+    /// Identify the archive at `path` by its signature bytes, falling back
+    /// to the extension when the file has no recognizable signature.
     pub fn detect(path: &str) -> Option<Self> {
-        let lower = path.to_lowercase();
-        if lower.ends_with(".zip") {
-            Some(Self::Zip)
-        } else if lower.ends_with(".7z") {
-            Some(Self::SevenZip)
-        } else {
-            None
+        detect::detect(path)
+    }
+
+    /// Parse a format name as used in the protocol (`"zip"`, `"tar.gz"`, ...).
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "zip" => Some(Self::Zip),
+            "7z" => Some(Self::SevenZip),
+            "tar" => Some(Self::Tar),
+            "tar.gz" | "tgz" => Some(Self::TarGz),
+            "tar.bz2" | "tbz2" => Some(Self::TarBz2),
+            "tar.xz" | "txz" => Some(Self::TarXz),
+            "tar.zst" | "tzst" => Some(Self::TarZst),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Zip => "zip",
+            Self::SevenZip => "7z",
+            Self::Tar => "tar",
+            Self::TarGz => "tar.gz",
+            Self::TarBz2 => "tar.bz2",
+            Self::TarXz => "tar.xz",
+            Self::TarZst => "tar.zst",
+            Self::Gz => "gz",
+            Self::Bz2 => "bz2",
+            Self::Xz => "xz",
+            Self::Zst => "zst",
+            Self::Cab => "cab",
+            Self::Rar => "rar",
+            Self::Iso => "iso",
+            Self::Deb => "deb",
+            Self::Rpm => "rpm",
+            Self::Ar => "ar",
+            Self::Cpio => "cpio",
+        }
+    }
+
+    /// Name of the stream compression wrapped around a tar or single file,
+    /// as shown in listings.
+    fn stream_compression(&self) -> &'static str {
+        match self {
+            Self::TarGz | Self::Gz => "Gzip",
+            Self::TarBz2 | Self::Bz2 => "BZip2",
+            Self::TarXz | Self::Xz => "Xz",
+            Self::TarZst | Self::Zst => "Zstd",
+            _ => "Stored",
+        }
+    }
+
+    /// Wrap `writer` in the compressor for this format's outer stream.
+    /// `level` uses each compressor's own scale; `None` picks its default.
+    fn compress<W: Write>(&self, writer: W, level: Option<u32>) -> Result<StreamEncoder<W>> {
+        Ok(match self {
+            Self::TarGz | Self::Gz => StreamEncoder::Gz(flate2::write::GzEncoder::new(
+                writer,
+                flate2::Compression::new(level.unwrap_or(6).min(9)),
+            )),
+            Self::TarBz2 | Self::Bz2 => StreamEncoder::Bz2(bzip2::write::BzEncoder::new(
+                writer,
+                bzip2::Compression::new(level.unwrap_or(9).clamp(1, 9)),
+            )),
+            Self::TarXz | Self::Xz => {
+                StreamEncoder::Xz(xz2::write::XzEncoder::new(writer, level.unwrap_or(6).min(9)))
+            }
+            Self::TarZst | Self::Zst => StreamEncoder::Zst(zstd::stream::write::Encoder::new(
+                writer,
+                level.unwrap_or(3).clamp(1, 22) as i32,
+            )?),
+            _ => StreamEncoder::Plain(writer),
+        })
+    }
+
+    /// Wrap `reader` in the decompressor for this format's outer stream.
+    fn decompress<'a, R: Read + 'a>(&self, reader: R) -> Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            Self::TarGz | Self::Gz => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+            Self::TarBz2 | Self::Bz2 => Box::new(bzip2::read::MultiBzDecoder::new(reader)),
+            Self::TarXz | Self::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+            Self::TarZst | Self::Zst => Box::new(zstd::stream::read::Decoder::new(reader)?),
+            _ => Box::new(reader),
+        })
+    }
+}
+
+/// Compressor for the outer stream of a tar-family archive.
+enum StreamEncoder<W: Write> {
+    Plain(W),
+    Gz(flate2::write::GzEncoder<W>),
+    Bz2(bzip2::write::BzEncoder<W>),
+    Xz(xz2::write::XzEncoder<W>),
+    Zst(zstd::stream::write::Encoder<'static, W>),
+}
+
+impl<W: Write> StreamEncoder<W> {
+    /// Flush the compressor trailer and return the underlying writer.
+    fn finish(self) -> std::io::Result<W> {
+        match self {
+            Self::Plain(w) => Ok(w),
+            Self::Gz(e) => e.finish(),
+            Self::Bz2(e) => e.finish(),
+            Self::Xz(e) => e.finish(),
+            Self::Zst(e) => e.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for StreamEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Plain(w) => w.write(buf),
+            Self::Gz(e) => e.write(buf),
+            Self::Bz2(e) => e.write(buf),
+            Self::Xz(e) => e.write(buf),
+            Self::Zst(e) => e.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Plain(w) => w.flush(),
+            Self::Gz(e) => e.flush(),
+            Self::Bz2(e) => e.flush(),
+            Self::Xz(e) => e.flush(),
+            Self::Zst(e) => e.flush(),
+        }
+    }
+}
+
+// =======================
+// Options and Errors
+// =======================
+
+/// Per-request options understood by every backend.
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveOptions {
+    /// Password for encrypted archives (zip ZipCrypto/AES, 7z AES-256).
+    pub password: Option<String>,
+    /// List everything below the inner path instead of direct children only.
+    pub recursive: bool,
+    /// Encoding of zip entry names stored without the UTF-8 flag; guessed
+    /// per archive when unset.
+    pub name_encoding: Option<String>,
+}
+
+/// Archive failures the client can act on, surfaced as their own error codes.
+#[derive(Debug)]
+pub enum ArchiveError {
+    PasswordRequired,
+    WrongPassword,
+    /// A volume of a split archive, by file name
+    MissingVolume(String),
+    /// An inner path the archive has no entry for
+    EntryNotFound(String),
+    /// An inner path naming a directory or link where a file was expected
+    NotAFile(String),
+}
+
+impl ArchiveError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::PasswordRequired => "PASSWORD_REQUIRED",
+            Self::WrongPassword => "WRONG_PASSWORD",
+            Self::MissingVolume(_) => "MISSING_VOLUME",
+            Self::EntryNotFound(_) => "ENTRY_NOT_FOUND",
+            Self::NotAFile(_) => "NOT_A_FILE",
+        }
+    }
+
+    /// The error for a rejected password, depending on whether one was given.
+    fn for_password(password: Option<&str>) -> Self {
+        match password {
+            Some(_) => Self::WrongPassword,
+            None => Self::PasswordRequired,
+        }
+    }
+}
+
+impl std::fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PasswordRequired => write!(f, "Archive is encrypted; a password is required"),
+            Self::WrongPassword => write!(f, "Wrong password for encrypted archive"),
+            Self::MissingVolume(name) => write!(f, "Split archive is incomplete: volume '{}' is missing", name),
+            Self::EntryNotFound(inner) => write!(f, "Entry '{}' not found in archive", inner),
+            Self::NotAFile(inner) => write!(f, "'{}' is not a file", inner),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+/// Where entry `inner` lands under `dest`, or `None` when the name is
+/// absolute or climbs out of `dest` with `..`.
+fn enclosed_path(dest: &str, inner: &str) -> Option<std::path::PathBuf> {
+    let mut out = std::path::PathBuf::from(dest);
+    for part in normalize_inner(inner).split('/') {
+        match part {
+            "" | "." => {}
+            ".." => return None,
+            _ if part.contains(':') => return None,
+            _ => out.push(part),
         }
     }
+    Some(out)
+}
+
+/// Inner archive path with forward slashes and no leading/trailing slash.
+fn normalize_inner(path: &str) -> String {
+    path.replace('\\', "/").trim_matches('/').to_string()
 }
 
 // =======================
@@ -34,9 +283,126 @@ impl ArchiveFormat {
 // =======================
 
 trait ArchiveBackend {
-    fn read_file(path: &str, inner: &str) -> Result<Vec<u8>>;
-    fn extract(path: &str, dest: &str, files: &[String]) -> Result<Vec<String>>;
-    fn list(path: &str, inner: &str) -> Result<ArchiveListing>;
+    /// Stream one file's contents into `out`, returning the bytes written.
+    fn copy_file(path: &str, inner: &str, opts: &ArchiveOptions, out: &mut dyn Write) -> Result<u64>;
+    fn read_file(path: &str, inner: &str, opts: &ArchiveOptions) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        Self::copy_file(path, inner, opts, &mut buf)?;
+        Ok(buf)
+    }
+    fn extract(path: &str, dest: &str, files: &[String], opts: &ArchiveOptions) -> Result<Vec<String>>;
+    /// Every record stored in the archive, with full inner paths. Directory
+    /// records may be missing; `ArchiveIndex` fills them in.
+    fn entries(path: &str, opts: &ArchiveOptions) -> Result<Vec<ArchiveEntry>>;
+    /// Read every file entry to the end, letting the format verify its
+    /// checksums on the way. Streaming formats override this with a single
+    /// pass over the archive.
+    fn test(path: &str, opts: &ArchiveOptions, job: &JobHandle) -> Result<TestOutcome> {
+        let files: Vec<ArchiveEntry> = Self::entries(path, opts)?
+            .into_iter()
+            .filter(|e| matches!(e.entry_type, ArchiveEntryType::File))
+            .collect();
+        job.set_totals(files.len() as u64, files.iter().map(|e| e.size).sum());
+
+        let mut outcome = TestOutcome::default();
+        for entry in files {
+            job.check_cancelled()?;
+            job.begin_item(&entry.inner_path);
+            let result = Self::copy_file(path, &entry.inner_path, opts, &mut test_sink(job));
+            outcome.record(normalize_inner(&entry.inner_path), entry.size, result)?;
+            job.finish_item();
+        }
+        job.check_cancelled()?;
+        Ok(outcome)
+    }
+
+    /// Hand every record to `visit` in archive order, with a reader over
+    /// its data (empty for directories and links). Each file is read through
+    /// `copy_file` on a helper thread and piped across, so nothing touches
+    /// the disk; streaming formats override this with a single pass.
+    fn for_each_entry(path: &str, opts: &ArchiveOptions, job: &JobHandle, visit: &mut EntryVisitor) -> Result<()> {
+        let records = Self::entries(path, opts)?;
+        report_totals(job, &records);
+        for record in &records {
+            job.check_cancelled()?;
+            if !matches!(record.entry_type, ArchiveEntryType::File) {
+                visit(record, &mut std::io::empty())?;
+                continue;
+            }
+            let (mut reader, mut writer) = std::io::pipe()?;
+            std::thread::scope(|scope| {
+                let inner = &record.inner_path;
+                let copy = scope.spawn(move || Self::copy_file(path, inner, opts, &mut writer));
+                let visited = visit(record, &mut reader);
+                // Unblock the copy if `visit` stopped reading early.
+                drop(reader);
+                let copied = copy.join().map_err(|_| anyhow!("Reading '{}' panicked", inner))?;
+                match copied {
+                    // A failed read explains a short entry; a broken pipe
+                    // only means `visit` gave up first.
+                    Err(e) if visited.is_ok() || !is_broken_pipe(&e) => Err(e),
+                    _ => visited,
+                }
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Receives each record of an archive with a reader over its data.
+type EntryVisitor<'a> = dyn FnMut(&ArchiveEntry, &mut dyn Read) -> Result<()> + 'a;
+
+/// Set a job's totals from an archive's records.
+fn report_totals(job: &JobHandle, records: &[ArchiveEntry]) {
+    let files = records
+        .iter()
+        .filter(|e| matches!(e.entry_type, ArchiveEntryType::File));
+    job.set_totals(records.len() as u64, files.map(|e| e.size).sum());
+}
+
+fn is_broken_pipe(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<std::io::Error>()
+        .is_some_and(|io| io.kind() == std::io::ErrorKind::BrokenPipe)
+}
+
+/// Per-entry results of an integrity test, plus any failure of the archive
+/// as a whole.
+#[derive(Default)]
+struct TestOutcome {
+    entries: Vec<EntryTestResult>,
+    error: Option<String>,
+}
+
+impl TestOutcome {
+    /// Record the result of reading one entry. Password errors abort the
+    /// whole test so the client can ask for one and retry.
+    fn record(&mut self, inner_path: String, size: u64, result: Result<u64>) -> Result<()> {
+        let error = match result {
+            Ok(_) => None,
+            Err(e) if e.downcast_ref::<ArchiveError>().is_some() => return Err(e),
+            Err(e) => Some(e.to_string()),
+        };
+        self.entries.push(EntryTestResult {
+            inner_path,
+            ok: error.is_none(),
+            size,
+            error,
+        });
+        Ok(())
+    }
+}
+
+/// Where tested data goes: nowhere, with progress reported to the job.
+fn test_sink(job: &JobHandle) -> ProgressWriter<'_, std::io::Sink> {
+    ProgressWriter::new(std::io::sink(), job)
+}
+
+/// Read what is left of a stream after its last entry, so compressors
+/// verify their trailers (gzip CRC, xz check). Returns the failure, if any.
+fn drain_trailer<R: Read>(mut reader: R) -> Option<String> {
+    std::io::copy(&mut reader, &mut std::io::sink())
+        .err()
+        .map(|e| format!("Archive stream is damaged: {}", e))
 }
 
 // =======================
@@ -46,43 +412,160 @@ trait ArchiveBackend {
 struct ZipBackend;
 
 impl ZipBackend {
+    /// Open the entry named `target`, as decoded by `entry_names`.
     fn open_entry<'a>(
-        zip: &'a mut zip::ZipArchive<File>,
+        zip: &'a mut zip::ZipArchive<Source>,
+        path: &str,
         target: &str,
+        opts: &ArchiveOptions,
     ) -> Result<zip::read::ZipFile<'a>> {
-        let name = if zip.file_names().any(|n| n == target) {
-            target.to_string()
-        } else {
-            format!("{}/", target)
+        let dir = format!("{}/", target);
+        let (names, _) = Self::entry_names(zip, path, opts)?;
+        let index = names
+            .iter()
+            .map(|name| name.replace('\\', "/"))
+            .position(|name| name == target || name == dir)
+            .ok_or_else(|| anyhow!("Entry '{}' not found", target))?;
+        Self::open_index(zip, index, opts.password.as_deref())
+    }
+
+    /// Entry names by index. Names flagged as UTF-8 or carrying a Unicode
+    /// Path extra field are used as stored; the rest are decoded with
+    /// `opts.name_encoding`, or with an encoding guessed from all of them.
+    /// That legacy encoding is returned when any name needed it.
+    fn entry_names(
+        zip: &mut zip::ZipArchive<Source>,
+        path: &str,
+        opts: &ArchiveOptions,
+    ) -> Result<(Vec<String>, Option<NameEncoding>)> {
+        let forced = opts.name_encoding.as_deref().map(NameEncoding::from_label).transpose()?;
+        let mut raw = Source::open(path)?;
+        let mut names = Vec::with_capacity(zip.len());
+        let mut legacy = Vec::new();
+
+        for i in 0..zip.len() {
+            let entry = zip.by_index_raw(i)?;
+            let name_raw = entry.name_raw();
+            if name_raw.is_ascii() {
+                names.push(entry.name().to_string());
+                continue;
+            }
+            let record = ZipRecord::read(&mut raw, entry.central_header_start())?;
+            if record.utf8_name() {
+                names.push(entry.name().to_string());
+            } else if let Some(name) = attributes::zip_unicode_path(entry.extra_data(), name_raw) {
+                names.push(name);
+            } else {
+                legacy.push((i, name_raw.to_vec()));
+                names.push(String::new());
+            }
+        }
+
+        if legacy.is_empty() {
+            return Ok((names, None));
+        }
+        let encoding =
+            forced.unwrap_or_else(|| NameEncoding::detect(legacy.iter().map(|(_, name)| name.as_slice())));
+        for (i, name) in legacy {
+            names[i] = encoding.decode(&name);
+        }
+        Ok((names, Some(encoding)))
+    }
+
+    /// Open entry `index` for reading, decrypting it when it is encrypted.
+    fn open_index<'a>(
+        zip: &'a mut zip::ZipArchive<Source>,
+        index: usize,
+        password: Option<&str>,
+    ) -> Result<zip::read::ZipFile<'a>> {
+        let result = match password {
+            Some(pw) => zip.by_index_decrypt(index, pw.as_bytes()),
+            None => zip.by_index(index).map(Ok),
+        };
+        match result {
+            Ok(Ok(file)) => Ok(file),
+            Ok(Err(zip::result::InvalidPassword)) => Err(ArchiveError::WrongPassword.into()),
+            Err(zip::result::ZipError::UnsupportedArchive(msg))
+                if msg == zip::result::ZipError::PASSWORD_REQUIRED =>
+            {
+                Err(ArchiveError::PasswordRequired.into())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Offset and length of an entry's bytes in the archive file, when it
+    /// is stored without compression or encryption.
+    fn stored_span(path: &str, inner: &str, opts: &ArchiveOptions) -> Result<Option<(u64, u64)>> {
+        let mut zip = zip::ZipArchive::new(Source::open(path)?)?;
+        let (names, _) = Self::entry_names(&mut zip, path, opts)?;
+        let target = normalize_inner(inner);
+        let Some(index) = names.iter().position(|name| normalize_inner(name) == target) else {
+            return Ok(None);
         };
-        let file = zip
-            .by_name(&name)
-            .with_context(|| format!("Entry '{}' not found", target))?;
-        Ok(file)
+        let entry = zip.by_index_raw(index)?;
+        let record = ZipRecord::read(&mut Source::open(path)?, entry.central_header_start())?;
+        let stored = entry.compression() == zip::CompressionMethod::Stored && !record.encrypted();
+        Ok(stored.then(|| (entry.data_start(), entry.compressed_size())))
+    }
+
+    /// The archive comment, and the encoding of names stored without the
+    /// UTF-8 flag, when there are any.
+    fn metadata(path: &str, opts: &ArchiveOptions) -> Result<Option<ArchiveMetadata>> {
+        let mut zip = zip::ZipArchive::new(Source::open(path)?)?;
+        let comment = String::from_utf8_lossy(zip.comment()).trim_end().to_string();
+        let (_, encoding) = Self::entry_names(&mut zip, path, opts)?;
+        if comment.is_empty() && encoding.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(ArchiveMetadata {
+            comment: Some(comment).filter(|c| !c.is_empty()),
+            name_encoding: encoding.map(|e| e.label()),
+            ..Default::default()
+        }))
     }
 }
 
 impl ArchiveBackend for ZipBackend {
-    fn read_file(path: &str, inner: &str) -> Result<Vec<u8>> {
-        let file = File::open(path)?;
+    fn copy_file(path: &str, inner: &str, opts: &ArchiveOptions, out: &mut dyn Write) -> Result<u64> {
+        let file = Source::open(path)?;
         let mut zip = zip::ZipArchive::new(file)?;
         let target = inner.replace('\\', "/");
-        let mut entry = Self::open_entry(&mut zip, &target)?;
-        let mut buf = Vec::new();
-        entry.read_to_end(&mut buf)?;
-        Ok(buf)
+        let mut entry = Self::open_entry(&mut zip, path, &target, opts)?;
+        Ok(std::io::copy(&mut entry, out)?)
     }
 
-    fn extract(path: &str, dest: &str, files: &[String]) -> Result<Vec<String>> {
-        let file = File::open(path)?;
+    fn extract(path: &str, dest: &str, files: &[String], opts: &ArchiveOptions) -> Result<Vec<String>> {
+        let file = Source::open(path)?;
         let mut zip = zip::ZipArchive::new(file)?;
+        let mut raw = Source::open(path)?;
+        let (names, _) = Self::entry_names(&mut zip, path, opts)?;
+        let wanted: Vec<String> = files.iter().map(|f| normalize_inner(f)).collect();
         let mut extracted = Vec::new();
 
-        for name in files {
-            let mut entry = Self::open_entry(&mut zip, name)?;
-            let out_path = Path::new(dest).join(name);
+        for (i, stored_name) in names.iter().enumerate() {
+            let name = normalize_inner(stored_name);
+            let mode = {
+                let entry = zip.by_index_raw(i)?;
+                ZipRecord::read(&mut raw, entry.central_header_start())?.unix_mode()
+            };
+            let selected = wanted.is_empty()
+                || wanted
+                    .iter()
+                    .any(|w| name == *w || name.starts_with(&format!("{}/", w)));
+            // Symbolic links are skipped rather than written out as files
+            // holding their target.
+            if !selected || name.is_empty() || mode.is_some_and(attributes::is_symlink) {
+                continue;
+            }
+
+            // Entries with absolute paths or `..` components are skipped.
+            let Some(out_path) = enclosed_path(dest, stored_name) else {
+                continue;
+            };
+            let mut entry = Self::open_index(&mut zip, i, opts.password.as_deref())?;
 
-            if entry.name().ends_with('/') {
+            if entry.is_dir() {
                 std::fs::create_dir_all(&out_path)?;
             } else {
                 if let Some(parent) = out_path.parent() {
@@ -90,52 +573,32 @@ impl ArchiveBackend for ZipBackend {
                 }
                 let mut outfile = File::create(&out_path)?;
                 std::io::copy(&mut entry, &mut outfile)?;
+                // Unix permissions; directories keep the default so
+                // read-only ones can still be filled.
+                #[cfg(unix)]
+                if let Some(mode) = mode {
+                    use std::os::unix::fs::PermissionsExt;
+                    outfile.set_permissions(std::fs::Permissions::from_mode(mode & 0o777))?;
+                }
             }
-            extracted.push(name.clone());
+            extracted.push(name);
+        }
+        if extracted.is_empty() && !wanted.is_empty() {
+            return Err(anyhow!("No matching entries in archive"));
         }
         Ok(extracted)
     }
 
-    fn list(path: &str, inner: &str) -> Result<ArchiveListing> {
-        let file = File::open(path)?;
-        let mut zip = zip::ZipArchive::new(file)?;
-        let prefix = inner.replace('\\', "/");
-        let prefix_norm = if prefix.is_empty() || prefix.ends_with('/') {
-            prefix.clone()
-        } else {
-            format!("{}/", prefix)
-        };
-
-        let mut entries = Vec::new();
-
-        for i in 0..zip.len() {
-            let entry = zip.by_index(i)?;
-            let raw_name = entry.name();
-            let name = raw_name.replace('\\', "/");
-
-            // Filter by inner path
-            if !prefix.is_empty() && !name.starts_with(&prefix_norm) {
-                continue;
-            }
-
-            // Determine inner path relative to listing root
-            let rel_path = if prefix.is_empty() {
-                name.clone()
-            } else {
-                name[prefix_norm.len()..].to_string()
-            };
-
-            // Skip if not direct child (for directory listing we want only immediate children)
-            if rel_path.contains('/') {
-                continue;
-            }
-
-            let is_dir = entry.is_dir();
-            let size = entry.size();
-            let compressed = entry.compressed_size();
+    fn entries(path: &str, opts: &ArchiveOptions) -> Result<Vec<ArchiveEntry>> {
+        let mut zip = zip::ZipArchive::new(Source::open(path)?)?;
+        let mut raw = Source::open(path)?;
+        let (names, _) = Self::entry_names(&mut zip, path, opts)?;
+        let mut entries = Vec::with_capacity(zip.len());
 
+        for (i, name) in names.into_iter().enumerate() {
+            let entry = zip.by_index_raw(i)?;
+            let record = ZipRecord::read(&mut raw, entry.central_header_start())?;
             let dt = entry.last_modified();
-
             let modified =
                 chrono::NaiveDate::from_ymd_opt(dt.year() as i32, dt.month() as u32, dt.day() as u32)
                     .and_then(|date| {
@@ -143,35 +606,88 @@ impl ArchiveBackend for ZipBackend {
                     })
                     .map(|dt| dt.and_utc().timestamp())
                     .unwrap_or(0);
+            let mode = record.unix_mode();
+            let is_link = mode.is_some_and(attributes::is_symlink);
+            let (owner, group) = attributes::zip_owner(entry.extra_data());
 
-            let compression = format!("{:?}", entry.compression());
-
-            let archive_entry = ArchiveEntry {
-                name: rel_path.clone(),
-                inner_path: rel_path,
-                entry_type: if is_dir {
+            let mut archive_entry = ArchiveEntry {
+                inner_path: name,
+                entry_type: if entry.is_dir() {
                     ArchiveEntryType::Directory
+                } else if is_link {
+                    ArchiveEntryType::Symlink
                 } else {
                     ArchiveEntryType::File
                 },
-                size,
-                compressed_size: compressed,
+                size: entry.size(),
+                compressed_size: entry.compressed_size(),
                 modified,
-                compression,
+                compression: record.compression(entry.extra_data()),
+                compression_level: record.compression_level(entry.extra_data()),
+                encrypted: record.encrypted(),
+                comment: Some(entry.comment().to_string()).filter(|c| !c.is_empty()),
+                mode,
+                owner,
+                group,
+                crc32: (!entry.is_dir()).then(|| entry.crc32()),
+                host_os: Some(attributes::zip_host_os(record.host)),
+                ..Default::default()
             };
+            drop(entry);
 
+            // Unix zippers store a link's target as its data.
+            if is_link && !archive_entry.encrypted && archive_entry.size <= attributes::MAX_LINK_TARGET {
+                let mut target = String::new();
+                if zip.by_index(i)?.read_to_string(&mut target).is_ok() {
+                    archive_entry.symlink_target = Some(target);
+                }
+            }
             entries.push(archive_entry);
         }
+        Ok(entries)
+    }
+
+    fn test(path: &str, opts: &ArchiveOptions, job: &JobHandle) -> Result<TestOutcome> {
+        let mut zip = zip::ZipArchive::new(Source::open(path)?)?;
+        let (names, _) = Self::entry_names(&mut zip, path, opts)?;
+        let mut files = Vec::new();
+        for (i, name) in names.iter().enumerate() {
+            let entry = zip.by_index_raw(i)?;
+            if !entry.is_dir() {
+                files.push((i, normalize_inner(name), entry.size()));
+            }
+        }
+        job.set_totals(files.len() as u64, files.iter().map(|f| f.2).sum());
 
-        let total_size = entries.iter().map(|e| e.size).sum();
+        let mut outcome = TestOutcome::default();
+        for (index, name, size) in files {
+            job.check_cancelled()?;
+            job.begin_item(&name);
+            // The zip reader checks the CRC once the entry is read to the end.
+            let result = Self::open_index(&mut zip, index, opts.password.as_deref())
+                .and_then(|mut file| Ok(std::io::copy(&mut file, &mut test_sink(job))?));
+            outcome.record(name, size, result)?;
+            job.finish_item();
+        }
+        job.check_cancelled()?;
+        Ok(outcome)
+    }
 
-        Ok(ArchiveListing {
-            archive_path: path.to_string(),
-            inner_path: prefix,
-            format: "zip".to_string(),
-            entries,
-            total_size,
-        })
+    fn for_each_entry(path: &str, opts: &ArchiveOptions, job: &JobHandle, visit: &mut EntryVisitor) -> Result<()> {
+        // `entries` yields one record per index, in order.
+        let records = Self::entries(path, opts)?;
+        report_totals(job, &records);
+        let mut zip = zip::ZipArchive::new(Source::open(path)?)?;
+        for (index, record) in records.iter().enumerate() {
+            job.check_cancelled()?;
+            if matches!(record.entry_type, ArchiveEntryType::File) {
+                let mut file = Self::open_index(&mut zip, index, opts.password.as_deref())?;
+                visit(record, &mut file)?;
+            } else {
+                visit(record, &mut std::io::empty())?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -181,58 +697,117 @@ impl ArchiveBackend for ZipBackend {
 
 struct SevenZipBackend;
 
-impl ArchiveBackend for SevenZipBackend {
-    fn read_file(path: &str, inner: &str) -> Result<Vec<u8>> {
-        let output = Command::new("7z")
-            .args(["x", "-so", path, inner])
-            .output()?;
+/// How often a running `7z` process is checked for cancellation.
+const CANCEL_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
 
-        if !output.status.success() {
-            return Err(anyhow!(
-                "7z failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+impl SevenZipBackend {
+    fn spawn_error(e: std::io::Error) -> anyhow::Error {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            anyhow!("The 7z tool is required for 7z and RAR archives but was not found in PATH")
+        } else {
+            e.into()
         }
-        Ok(output.stdout)
     }
 
-    fn extract(path: &str, dest: &str, files: &[String]) -> Result<Vec<String>> {
-        let mut args = vec![
-            "x".to_string(),
-            "-y".to_string(),
-            format!("-o{}", dest),
-        ];
-        args.push(path.to_string());
-        args.extend(files.iter().cloned());
-
-        let output = Command::new("7z").args(&args).output()?;
-
-        if !output.status.success() {
-            return Err(anyhow!(
-                "7z extraction failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+    /// Start `7z`. A password goes to it on stdin, where other users cannot
+    /// read it as they can the argument list; without one an empty `-p` is
+    /// passed so 7z never stops to prompt on encrypted archives.
+    fn spawn(command: &mut Command, password: Option<&str>) -> Result<std::process::Child> {
+        let password = password.filter(|p| !p.is_empty());
+        match password {
+            Some(_) => {
+                command.stdin(std::process::Stdio::piped());
+                // Without a controlling terminal, 7z reads the password
+                // prompt's answer from stdin rather than from /dev/tty.
+                #[cfg(unix)]
+                {
+                    use std::os::unix::process::CommandExt;
+                    // SAFETY: `setsid` is async-signal-safe and touches no
+                    // memory of the parent.
+                    unsafe {
+                        command.pre_exec(|| {
+                            libc::setsid();
+                            Ok(())
+                        });
+                    }
+                }
+            }
+            None => {
+                command.arg("-p").stdin(std::process::Stdio::null());
+            }
         }
-        Ok(files.to_vec())
+        let mut child = command.spawn().map_err(Self::spawn_error)?;
+        if let (Some(password), Some(mut stdin)) = (password, child.stdin.take()) {
+            let line = format!("{}\n", password);
+            // 7z only reads it when the archive is encrypted; a closed pipe
+            // is fine.
+            std::thread::spawn(move || {
+                let _ = stdin.write_all(line.as_bytes());
+            });
+        }
+        Ok(child)
     }
 
-    fn list(path: &str, inner: &str) -> Result<ArchiveListing> {
-        // Use detailed listing to get metadata
-        let output = Command::new("7z")
-            .args(["l", "-slt", path])
-            .output()?;
+    /// Run the `7z` tool and collect its output.
+    fn run(args: &[String], password: Option<&str>, action: &str) -> Result<Vec<u8>> {
+        let mut command = Command::new("7z");
+        command
+            .args(args)
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+        let output = Self::spawn(&mut command, password)?.wait_with_output()?;
 
         if !output.status.success() {
-            return Err(anyhow!(
-                "7z list failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if stderr.to_lowercase().contains("wrong password") {
+                return Err(ArchiveError::for_password(password).into());
+            }
+            return Err(anyhow!("7z {} failed: {}", action, stderr));
         }
+        Ok(output.stdout)
+    }
+
+    /// Like `Command::output`, but the process is killed as soon as `job`
+    /// is cancelled.
+    fn output_cancellable(
+        command: &mut Command,
+        password: Option<&str>,
+        job: &JobHandle,
+    ) -> Result<std::process::Output> {
+        command
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+        let mut child = Self::spawn(command, password)?;
+        // Both pipes are drained meanwhile so 7z never blocks on a full one.
+        let stdout = drain(child.stdout.take());
+        let stderr = drain(child.stderr.take());
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if job.is_cancelled() {
+                let _ = child.kill();
+                let _ = child.wait();
+                job.check_cancelled()?;
+            }
+            std::thread::sleep(CANCEL_POLL_INTERVAL);
+        };
+        Ok(std::process::Output {
+            status,
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+        })
+    }
 
-        let text = String::from_utf8_lossy(&output.stdout);
-        let prefix = inner.replace('\\', "/");
+    /// Detailed listing (`7z l -slt`): the entries, and the archive comment
+    /// from the header block that precedes them.
+    fn list(path: &str, opts: &ArchiveOptions) -> Result<(Vec<ArchiveEntry>, Option<String>)> {
+        let args = ["l".to_string(), "-slt".to_string(), path.to_string()];
+        let stdout = Self::run(&args, opts.password.as_deref(), "list")?;
 
+        let text = String::from_utf8_lossy(&stdout);
         let mut entries = Vec::new();
+        let mut comment = None;
         let mut current = None;
         let mut in_entry = false;
 
@@ -240,9 +815,7 @@ impl ArchiveBackend for SevenZipBackend {
             let line = line.trim();
             if line.is_empty() {
                 if let Some(entry_data) = current.take() {
-                    if let Some(entry) = parse_7z_entry(entry_data, &prefix) {
-                        entries.push(entry);
-                    }
+                    entries.extend(parse_7z_entry(entry_data));
                 }
                 in_entry = false;
                 continue;
@@ -258,119 +831,2121 @@ impl ArchiveBackend for SevenZipBackend {
                 if let Some(ref mut data) = current {
                     data.push(line.to_string());
                 }
+            } else if let Some(value) = line.strip_prefix("Comment = ") {
+                comment = Some(value.to_string()).filter(|c| !c.is_empty());
             }
         }
 
         // Handle last entry
         if let Some(entry_data) = current.take() {
-            if let Some(entry) = parse_7z_entry(entry_data, &prefix) {
-                entries.push(entry);
-            }
+            entries.extend(parse_7z_entry(entry_data));
         }
-
-        // Filter to direct children of inner path
-        let filtered: Vec<_> = entries
-            .into_iter()
-            .filter(|e| {
-                if prefix.is_empty() {
-                    !e.inner_path.contains('/')
-                } else {
-                    e.inner_path.starts_with(&format!("{}/", prefix))
-                        && !e.inner_path[prefix.len() + 1..].contains('/')
-                }
-            })
-            .collect();
-
-        let total_size = filtered.iter().map(|e| e.size).sum();
-
-        Ok(ArchiveListing {
-            archive_path: path.to_string(),
-            inner_path: prefix,
-            format: "7z".to_string(),
-            entries: filtered,
-            total_size,
-        })
+        Ok((entries, comment))
     }
-}
 
-fn parse_7z_entry(lines: Vec<String>, prefix: &str) -> Option<ArchiveEntry> {
-    let mut path = None;
-    let mut size = 0;
-    let mut compressed = 0;
-    let mut modified = 0;
-    let mut is_dir = false;
+    fn metadata(path: &str, opts: &ArchiveOptions) -> Result<Option<ArchiveMetadata>> {
+        Ok(Self::list(path, opts)?.1.map(|comment| ArchiveMetadata {
+            comment: Some(comment),
+            ..Default::default()
+        }))
+    }
 
-    for line in lines {
-        if let Some((key, value)) = line.split_once(" = ") {
-            match key {
-                "Path" => path = Some(value.to_string()),
-                "Size" => size = value.parse().unwrap_or(0),
-                "Packed Size" => compressed = value.parse().unwrap_or(0),
-                "Modified" => {
-                    if let Ok(dt) = chrono::DateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S") {
-                        modified = dt.timestamp();
+    /// Decode a 7z archive in-process, one pass per solid block: entries
+    /// with data come in block order, the empty ones after them. Returns
+    /// `false` without visiting anything when the decoder cannot read the
+    /// archive (a split set, a method it lacks), so the `7z` tool can
+    /// take over.
+    fn for_each_decoded(
+        path: &str,
+        records: &[ArchiveEntry],
+        job: &JobHandle,
+        visit: &mut EntryVisitor,
+    ) -> Result<bool> {
+        let Ok(mut reader) = sevenz_rust::SevenZReader::open(path, sevenz_rust::Password::empty()) else {
+            return Ok(false);
+        };
+        let by_name: std::collections::HashMap<String, &ArchiveEntry> =
+            records.iter().map(|r| (test_key(&r.inner_path), r)).collect();
+        let mut visited = false;
+        let mut failed = None;
+        let decoded = reader.for_each_entries(|entry, data| {
+            visited = true;
+            let result = (|| -> Result<()> {
+                job.check_cancelled()?;
+                if let Some(record) = by_name.get(&test_key(entry.name())) {
+                    if matches!(record.entry_type, ArchiveEntryType::File) {
+                        visit(record, data)?;
+                    } else {
+                        visit(record, &mut std::io::empty())?;
                     }
                 }
-                "Folder" => is_dir = value == "+",
-                _ => {}
+                // The block is one stream: whatever `visit` left unread
+                // has to go before the next entry starts.
+                std::io::copy(data, &mut std::io::sink())?;
+                Ok(())
+            })();
+            match result {
+                Ok(()) => Ok(true),
+                Err(e) => {
+                    failed = Some(e);
+                    Ok(false)
+                }
             }
+        });
+        if let Some(e) = failed {
+            return Err(e);
+        }
+        match decoded {
+            Ok(()) => Ok(true),
+            Err(_) if !visited => Ok(false),
+            Err(e) => Err(anyhow!("7z read failed: {}", e)),
         }
     }
 
-    let full_path = path?;
-    let name = full_path.split('/').last().unwrap_or(&full_path).to_string();
-    let inner_path = full_path.replace('\\', "/");
+    /// Visit every entry from a single `7z x -so` run, which writes the data
+    /// of all files back to back in archive order; the listing's sizes
+    /// split it up again.
+    fn for_each_streamed(
+        path: &str,
+        records: &[ArchiveEntry],
+        opts: &ArchiveOptions,
+        job: &JobHandle,
+        visit: &mut EntryVisitor,
+    ) -> Result<()> {
+        let password = opts.password.as_deref();
+        let mut command = Command::new("7z");
+        command
+            .args(["x", "-so", path])
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+        let mut child = Self::spawn(&mut command, password)?;
+        let stderr = drain(child.stderr.take());
+        let stdout = child.stdout.take().ok_or_else(|| anyhow!("7z produced no output"))?;
+        let mut stdout = EofTracker { inner: stdout, ended: false };
 
-    // Skip if not under prefix
-    if !prefix.is_empty() && !inner_path.starts_with(&format!("{}/", prefix)) && inner_path != *prefix {
-        return None;
+        let visited = (|| -> Result<()> {
+            for record in records {
+                job.check_cancelled()?;
+                let size = match record.entry_type {
+                    ArchiveEntryType::Directory => 0,
+                    _ => record.size,
+                };
+                let mut data = (&mut stdout).take(size);
+                if matches!(record.entry_type, ArchiveEntryType::File) {
+                    visit(record, &mut data)?;
+                } else {
+                    visit(record, &mut std::io::empty())?;
+                }
+                // Keep the stream in step when `visit` stopped early.
+                std::io::copy(&mut data, &mut std::io::sink())?;
+            }
+            Ok(())
+        })();
+        // A stream that ran dry means 7z failed, and its own error says
+        // more than the short entry; otherwise `visit` gave up and 7z is
+        // stopped.
+        let ended = stdout.ended;
+        if visited.is_err() && !ended {
+            let _ = child.kill();
+        }
+        drop(stdout);
+        let status = child.wait()?;
+        if !status.success() && (visited.is_ok() || ended) {
+            let stderr = stderr.join().unwrap_or_default();
+            let stderr = String::from_utf8_lossy(&stderr);
+            if stderr.to_lowercase().contains("wrong password") {
+                return Err(ArchiveError::for_password(password).into());
+            }
+            return Err(anyhow!("7z read failed: {}", stderr));
+        }
+        visited?;
+        Ok(())
     }
-
-    Some(ArchiveEntry {
-        name,
-        inner_path,
-        entry_type: if is_dir {
-            ArchiveEntryType::Directory
-        } else {
-            ArchiveEntryType::File
-        },
-        size,
-        compressed_size: compressed,
-        modified,
-        compression: "7z".to_string(),
-    })
 }
 
-// =======================
-// Public API
-// =======================
-
-pub fn read_archive_file(path: &str, inner: &str) -> Result<Vec<u8>> {
-    let fmt = ArchiveFormat::detect(path)
-        .ok_or_else(|| anyhow!("Unsupported archive format"))?;
+impl ArchiveBackend for SevenZipBackend {
+    fn copy_file(path: &str, inner: &str, opts: &ArchiveOptions, out: &mut dyn Write) -> Result<u64> {
+        let password = opts.password.as_deref();
+        let mut command = Command::new("7z");
+        command
+            .args(["x", "-so", path, inner])
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+        let mut child = Self::spawn(&mut command, password)?;
 
-    match fmt {
-        ArchiveFormat::Zip => ZipBackend::read_file(path, inner),
-        ArchiveFormat::SevenZip => SevenZipBackend::read_file(path, inner),
+        let copied = match child.stdout.take() {
+            Some(mut stdout) => std::io::copy(&mut stdout, out),
+            None => Ok(0),
+        };
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if stderr.to_lowercase().contains("wrong password") {
+                return Err(ArchiveError::for_password(password).into());
+            }
+            return Err(anyhow!("7z read failed: {}", stderr));
+        }
+        Ok(copied?)
     }
-}
 
-pub fn extract_archive(path: &str, dest: &str, files: &[String]) -> Result<Vec<String>> {
-    let fmt = ArchiveFormat::detect(path)
-        .ok_or_else(|| anyhow!("Unsupported archive format"))?;
+    fn extract(path: &str, dest: &str, files: &[String], opts: &ArchiveOptions) -> Result<Vec<String>> {
+        let mut args = vec![
+            "x".to_string(),
+            "-y".to_string(),
+            format!("-o{}", dest),
+        ];
+        args.push(path.to_string());
+        args.extend(files.iter().cloned());
+
+        Self::run(&args, opts.password.as_deref(), "extraction")?;
+        Ok(files.to_vec())
+    }
 
-    match fmt {
-        ArchiveFormat::Zip => ZipBackend::extract(path, dest, files),
-        ArchiveFormat::SevenZip => SevenZipBackend::extract(path, dest, files),
+    fn entries(path: &str, opts: &ArchiveOptions) -> Result<Vec<ArchiveEntry>> {
+        Ok(Self::list(path, opts)?.0)
     }
-}
 
-pub fn list_archive(path: &str, inner: &str) -> Result<ArchiveListing> {
-    let fmt = ArchiveFormat::detect(path)
-        .ok_or_else(|| anyhow!("Unsupported archive format"))?;
+    /// `7z t` checks every CRC in one pass; failures are reported as
+    /// `ERROR: <reason> : <path>` lines.
+    fn test(path: &str, opts: &ArchiveOptions, job: &JobHandle) -> Result<TestOutcome> {
+        let password = opts.password.as_deref();
+        let files: Vec<ArchiveEntry> = Self::entries(path, opts)?
+            .into_iter()
+            .filter(|e| matches!(e.entry_type, ArchiveEntryType::File))
+            .collect();
+        job.set_totals(files.len() as u64, files.iter().map(|e| e.size).sum());
+        job.begin_item(path);
+
+        let mut command = Command::new("7z");
+        command.args(["t", path]);
+        let output = Self::output_cancellable(&mut command, password, job)?;
+        let text = format!(
+            "{}\n{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        if text.to_lowercase().contains("wrong password") {
+            return Err(ArchiveError::for_password(password).into());
+        }
 
-    match fmt {
-        ArchiveFormat::Zip => ZipBackend::list(path, inner),
-        ArchiveFormat::SevenZip => SevenZipBackend::list(path, inner),
-    }
-}
\ No newline at end of file
+        let mut failures = std::collections::HashMap::new();
+        let mut general = None;
+        for line in text.lines() {
+            let Some(message) = line.trim().strip_prefix("ERROR: ") else {
+                continue;
+            };
+            match message.split_once(" : ") {
+                Some((reason, name)) => {
+                    failures.insert(test_key(name), reason.to_string());
+                }
+                None => {
+                    general.get_or_insert_with(|| message.to_string());
+                }
+            }
+        }
+
+        let mut outcome = TestOutcome::default();
+        if !output.status.success() && failures.is_empty() {
+            outcome.error = Some(general.unwrap_or_else(|| "7z reported errors".to_string()));
+        }
+        for entry in files {
+            let result = match failures.remove(&test_key(&entry.inner_path)) {
+                Some(reason) => Err(anyhow!(reason)),
+                None => Ok(entry.size),
+            };
+            job.add_bytes(entry.size);
+            outcome.record(entry.inner_path, entry.size, result)?;
+            job.finish_item();
+        }
+        Ok(outcome)
+    }
+
+    /// One pass over the whole archive instead of a `7z x` run per entry,
+    /// which would decode a solid block again for every file in it.
+    /// Encrypted archives, and those the decoder cannot read, are streamed
+    /// through the `7z` tool instead.
+    fn for_each_entry(path: &str, opts: &ArchiveOptions, job: &JobHandle, visit: &mut EntryVisitor) -> Result<()> {
+        let records = Self::entries(path, opts)?;
+        report_totals(job, &records);
+        if !records.iter().any(|r| r.encrypted) && Self::for_each_decoded(path, &records, job, visit)? {
+            return Ok(());
+        }
+        Self::for_each_streamed(path, &records, opts, job, visit)
+    }
+}
+
+/// An entry name as both `7z t` and `7z l` may spell it: `./dir\file`
+/// and `dir/file` are the same entry.
+fn test_key(name: &str) -> String {
+    normalize_inner(name)
+        .split('/')
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Read a child's pipe to the end on its own thread.
+fn drain<R: Read + Send + 'static>(pipe: Option<R>) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+        bytes
+    })
+}
+
+/// Notes whether a reader ran dry, which tells a stream that was cut
+/// short from a consumer that stopped reading early.
+struct EofTracker<R> {
+    inner: R,
+    ended: bool,
+}
+
+impl<R: Read> Read for EofTracker<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n == 0 && !buf.is_empty() {
+            self.ended = true;
+        }
+        Ok(n)
+    }
+}
+
+fn parse_7z_entry(lines: Vec<String>) -> Option<ArchiveEntry> {
+    let mut entry = ArchiveEntry::default();
+    let mut path = None;
+    let mut is_dir = false;
+    let mut method = None;
+
+    for line in lines {
+        if let Some((key, value)) = line.split_once(" = ") {
+            match key {
+                "Path" => path = Some(value.to_string()),
+                "Size" => entry.size = value.parse().unwrap_or(0),
+                "Packed Size" => entry.compressed_size = value.parse().unwrap_or(0),
+                "Modified" => {
+                    if let Ok(dt) = chrono::DateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S") {
+                        entry.modified = dt.timestamp();
+                    }
+                }
+                "Folder" => is_dir = value == "+",
+                "Encrypted" => entry.encrypted = value == "+",
+                "Method" if !value.is_empty() => method = Some(value.to_string()),
+                "CRC" => entry.crc32 = u32::from_str_radix(value, 16).ok(),
+                // Windows attribute letters, followed by the Unix mode when
+                // one is stored: `A_ -rw-r--r--`.
+                "Attributes" => {
+                    entry.mode = value.split_whitespace().last().and_then(attributes::parse_symbolic_mode)
+                }
+                "Host OS" if !value.is_empty() => entry.host_os = Some(value.to_string()),
+                "Comment" if !value.is_empty() => entry.comment = Some(value.to_string()),
+                "User" if !value.is_empty() => entry.owner = Some(value.to_string()),
+                "Group" if !value.is_empty() => entry.group = Some(value.to_string()),
+                "Symbolic Link" if !value.is_empty() => entry.symlink_target = Some(value.to_string()),
+                _ => {}
+            }
+        }
+    }
+
+    entry.inner_path = path?;
+    entry.entry_type = if is_dir {
+        ArchiveEntryType::Directory
+    } else if entry.symlink_target.is_some() || entry.mode.is_some_and(attributes::is_symlink) {
+        ArchiveEntryType::Symlink
+    } else {
+        ArchiveEntryType::File
+    };
+    entry.compression = method.unwrap_or_else(|| "7z".to_string());
+    Some(entry)
+}
+
+// =======================
+// RAR BACKEND
+// =======================
+
+/// RAR4 and RAR5 archives, read through the `7z` tool like 7z archives.
+/// Creating RAR archives is not supported.
+struct RarBackend;
+
+impl RarBackend {
+    /// 7z has to be pointed at the first volume of a multi-volume set:
+    /// `name.part1.rar` for new-style sets, `name.rar` for `.r00` sets.
+    fn first_volume(path: &str) -> String {
+        // ASCII lowercasing keeps byte offsets valid for slicing `path`.
+        let lower = path.to_ascii_lowercase();
+        let candidate = if let Some(stem) = lower.strip_suffix(".rar") {
+            stem.rfind(".part").and_then(|i| {
+                let digits = &stem[i + 5..];
+                if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                    return None;
+                }
+                let first = format!("{:0width$}", 1, width = digits.len());
+                Some(format!("{}{}{}", &path[..i + 5], first, &path[stem.len()..]))
+            })
+        } else {
+            detect::is_old_rar_volume(&lower).then(|| format!("{}rar", &path[..path.len() - 3]))
+        };
+        match candidate {
+            Some(first) if Path::new(&first).exists() => first,
+            _ => path.to_string(),
+        }
+    }
+}
+
+impl ArchiveBackend for RarBackend {
+    fn copy_file(path: &str, inner: &str, opts: &ArchiveOptions, out: &mut dyn Write) -> Result<u64> {
+        SevenZipBackend::copy_file(&Self::first_volume(path), inner, opts, out)
+    }
+
+    fn extract(path: &str, dest: &str, files: &[String], opts: &ArchiveOptions) -> Result<Vec<String>> {
+        SevenZipBackend::extract(&Self::first_volume(path), dest, files, opts)
+    }
+
+    fn entries(path: &str, opts: &ArchiveOptions) -> Result<Vec<ArchiveEntry>> {
+        SevenZipBackend::entries(&Self::first_volume(path), opts)
+    }
+
+    fn test(path: &str, opts: &ArchiveOptions, job: &JobHandle) -> Result<TestOutcome> {
+        SevenZipBackend::test(&Self::first_volume(path), opts, job)
+    }
+
+    /// A single `7z x -so` stream, like encrypted 7z archives.
+    fn for_each_entry(path: &str, opts: &ArchiveOptions, job: &JobHandle, visit: &mut EntryVisitor) -> Result<()> {
+        let path = Self::first_volume(path);
+        let records = SevenZipBackend::entries(&path, opts)?;
+        report_totals(job, &records);
+        SevenZipBackend::for_each_streamed(&path, &records, opts, job, visit)
+    }
+}
+
+// =======================
+// TAR BACKEND
+// =======================
+
+/// Plain and compressed tarballs. Tar has no index, so every call streams
+/// the archive from the start.
+struct TarBackend;
+
+impl TarBackend {
+    fn open(path: &str) -> Result<tar::Archive<Box<dyn Read>>> {
+        let format = ArchiveFormat::detect(path).unwrap_or(ArchiveFormat::Tar);
+        let file = Source::open(path)?;
+        Ok(tar::Archive::new(format.decompress(file)?))
+    }
+
+    /// Normalised inner path of a tar entry: forward slashes, no `./` prefix
+    /// and no trailing slash.
+    fn entry_path<R: Read>(entry: &tar::Entry<R>) -> String {
+        let raw = String::from_utf8_lossy(&entry.path_bytes()).replace('\\', "/");
+        raw.trim_start_matches("./").trim_end_matches('/').to_string()
+    }
+
+    /// The operations below work on any tar stream, including the data
+    /// member of a Debian package.
+    fn copy_from<R: Read>(archive: &mut tar::Archive<R>, inner: &str, out: &mut dyn Write) -> Result<u64> {
+        let target = normalize_inner(inner);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if Self::entry_path(&entry) == target {
+                return Ok(std::io::copy(&mut entry, out)?);
+            }
+        }
+        Err(anyhow!("Entry '{}' not found", inner))
+    }
+
+    fn extract_from<R: Read>(archive: &mut tar::Archive<R>, dest: &str, files: &[String]) -> Result<Vec<String>> {
+        let wanted: Vec<String> = files
+            .iter()
+            .map(|f| normalize_inner(f))
+            .collect();
+        let mut extracted = Vec::new();
+
+        std::fs::create_dir_all(dest)?;
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let name = Self::entry_path(&entry);
+            let selected = wanted.is_empty()
+                || wanted
+                    .iter()
+                    .any(|w| name == *w || name.starts_with(&format!("{}/", w)));
+            if !selected || name.is_empty() {
+                continue;
+            }
+            // `unpack_in` refuses entries that would escape `dest`.
+            if entry.unpack_in(dest)? {
+                extracted.push(name);
+            }
+        }
+        Ok(extracted)
+    }
+
+    fn entries_from<R: Read>(archive: &mut tar::Archive<R>, compression: &str) -> Result<Vec<ArchiveEntry>> {
+        let mut entries = Vec::new();
+        for entry in archive.entries()? {
+            entries.extend(Self::record(&entry?, compression)?);
+        }
+        Ok(entries)
+    }
+
+    /// Listing record for one tar entry; `None` for pax extension headers.
+    fn record<R: Read>(entry: &tar::Entry<R>, compression: &str) -> Result<Option<ArchiveEntry>> {
+        let header = entry.header();
+        let entry_type = header.entry_type();
+        if entry_type.is_pax_global_extensions() || entry_type.is_pax_local_extensions() {
+            return Ok(None);
+        }
+        let is_dir = entry_type.is_dir();
+        let is_link = entry_type.is_symlink();
+        // Tar keeps the file type in the type flag, not the mode.
+        let file_type = if is_dir {
+            0o040000
+        } else if is_link {
+            attributes::S_IFLNK
+        } else {
+            0o100000
+        };
+        let name_or_id = |name: Option<&str>, id: u64| {
+            name.filter(|n| !n.is_empty()).map_or_else(|| id.to_string(), str::to_string)
+        };
+
+        Ok(Some(ArchiveEntry {
+            inner_path: Self::entry_path(entry),
+            entry_type: if is_dir {
+                ArchiveEntryType::Directory
+            } else if is_link {
+                ArchiveEntryType::Symlink
+            } else {
+                ArchiveEntryType::File
+            },
+            size: if is_dir { 0 } else { header.size().unwrap_or(0) },
+            compressed_size: 0,
+            modified: header.mtime().unwrap_or(0) as i64,
+            compression: compression.to_string(),
+            mode: header.mode().ok().map(|mode| file_type | (mode & 0o7777)),
+            owner: header.uid().ok().map(|uid| name_or_id(header.username().ok().flatten(), uid)),
+            group: header.gid().ok().map(|gid| name_or_id(header.groupname().ok().flatten(), gid)),
+            symlink_target: if is_link {
+                entry.link_name()?.map(|target| target.to_string_lossy().into_owned())
+            } else {
+                None
+            },
+            ..Default::default()
+        }))
+    }
+
+    fn for_each_from<R: Read>(
+        archive: &mut tar::Archive<R>,
+        compression: &str,
+        job: &JobHandle,
+        visit: &mut EntryVisitor,
+    ) -> Result<()> {
+        for entry in archive.entries()? {
+            job.check_cancelled()?;
+            let mut entry = entry?;
+            if let Some(record) = Self::record(&entry, compression)? {
+                visit(&record, &mut entry)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Tar has no checksums over file data; reading every entry checks the
+    /// header checksums and the compressor's own checks.
+    fn test_from<R: Read>(mut archive: tar::Archive<R>, job: &JobHandle) -> Result<TestOutcome> {
+        let mut outcome = TestOutcome::default();
+        for entry in archive.entries()? {
+            job.check_cancelled()?;
+            let mut entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    outcome.error = Some(e.to_string());
+                    break;
+                }
+            };
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let name = Self::entry_path(&entry);
+            let size = entry.size();
+            job.begin_item(&name);
+            let result = std::io::copy(&mut entry, &mut test_sink(job)).map_err(Into::into);
+            outcome.record(name, size, result)?;
+            job.finish_item();
+        }
+        job.check_cancelled()?;
+        if outcome.error.is_none() {
+            outcome.error = drain_trailer(archive.into_inner());
+        }
+        Ok(outcome)
+    }
+}
+
+impl ArchiveBackend for TarBackend {
+    fn copy_file(path: &str, inner: &str, _opts: &ArchiveOptions, out: &mut dyn Write) -> Result<u64> {
+        Self::copy_from(&mut Self::open(path)?, inner, out)
+    }
+
+    fn extract(path: &str, dest: &str, files: &[String], _opts: &ArchiveOptions) -> Result<Vec<String>> {
+        Self::extract_from(&mut Self::open(path)?, dest, files)
+    }
+
+    fn entries(path: &str, _opts: &ArchiveOptions) -> Result<Vec<ArchiveEntry>> {
+        let format = ArchiveFormat::detect(path).unwrap_or(ArchiveFormat::Tar);
+        Self::entries_from(&mut Self::open(path)?, format.stream_compression())
+    }
+
+    fn test(path: &str, _opts: &ArchiveOptions, job: &JobHandle) -> Result<TestOutcome> {
+        Self::test_from(Self::open(path)?, job)
+    }
+
+    fn for_each_entry(path: &str, _opts: &ArchiveOptions, job: &JobHandle, visit: &mut EntryVisitor) -> Result<()> {
+        let format = ArchiveFormat::detect(path).unwrap_or(ArchiveFormat::Tar);
+        Self::for_each_from(&mut Self::open(path)?, format.stream_compression(), job, visit)
+    }
+}
+
+// =======================
+// CAB BACKEND
+// =======================
+
+/// Microsoft Cabinet files (driver packages, Windows update payloads).
+/// Files are grouped into folders that share one compression stream.
+struct CabBackend;
+
+impl CabBackend {
+    fn open(path: &str) -> Result<cab::Cabinet<Source>> {
+        Ok(cab::Cabinet::new(Source::open(path)?)?)
+    }
+
+    /// Method name and level. LZX has no level; its window size is given
+    /// the way 7z writes dictionary sizes (`LZX:21` for 2^21 bytes).
+    fn compression_name(ctype: cab::CompressionType) -> (String, Option<String>) {
+        match ctype {
+            cab::CompressionType::None => ("Stored".to_string(), None),
+            cab::CompressionType::MsZip => ("MSZIP".to_string(), None),
+            cab::CompressionType::Quantum(level, _) => ("Quantum".to_string(), Some(level.to_string())),
+            cab::CompressionType::Lzx(window) => (format!("LZX:{}", window), None),
+        }
+    }
+
+    /// CAB names use backslashes; map normalized names back to stored ones.
+    fn stored_names(cabinet: &cab::Cabinet<Source>) -> Vec<(String, String)> {
+        cabinet
+            .folder_entries()
+            .flat_map(|folder| folder.file_entries())
+            .map(|file| (normalize_inner(file.name()), file.name().to_string()))
+            .collect()
+    }
+}
+
+impl ArchiveBackend for CabBackend {
+    fn copy_file(path: &str, inner: &str, _opts: &ArchiveOptions, out: &mut dyn Write) -> Result<u64> {
+        let target = normalize_inner(inner);
+        let mut cabinet = Self::open(path)?;
+        let (_, stored) = Self::stored_names(&cabinet)
+            .into_iter()
+            .find(|(name, _)| *name == target)
+            .ok_or_else(|| anyhow!("Entry '{}' not found", inner))?;
+        let mut reader = cabinet.read_file(&stored)?;
+        Ok(std::io::copy(&mut reader, out)?)
+    }
+
+    fn extract(path: &str, dest: &str, files: &[String], _opts: &ArchiveOptions) -> Result<Vec<String>> {
+        let wanted: Vec<String> = files.iter().map(|f| normalize_inner(f)).collect();
+        let mut cabinet = Self::open(path)?;
+        let mut extracted = Vec::new();
+
+        for (name, stored) in Self::stored_names(&cabinet) {
+            let selected = wanted.is_empty()
+                || wanted
+                    .iter()
+                    .any(|w| name == *w || name.starts_with(&format!("{}/", w)));
+            if !selected {
+                continue;
+            }
+            let Some(out_path) = enclosed_path(dest, &name) else {
+                continue;
+            };
+            if let Some(parent) = out_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut reader = cabinet.read_file(&stored)?;
+            std::io::copy(&mut reader, &mut File::create(&out_path)?)?;
+            extracted.push(name);
+        }
+        if extracted.is_empty() && !wanted.is_empty() {
+            return Err(anyhow!("No matching entries in archive"));
+        }
+        Ok(extracted)
+    }
+
+    fn entries(path: &str, _opts: &ArchiveOptions) -> Result<Vec<ArchiveEntry>> {
+        let cabinet = Self::open(path)?;
+        let mut entries = Vec::new();
+
+        for folder in cabinet.folder_entries() {
+            let (compression, level) = Self::compression_name(folder.compression_type());
+            for file in folder.file_entries() {
+                entries.push(ArchiveEntry {
+                    inner_path: file.name().to_string(),
+                    entry_type: ArchiveEntryType::File,
+                    size: file.uncompressed_size() as u64,
+                    // Files share their folder's compressed stream.
+                    compressed_size: 0,
+                    // CAB timestamps carry no zone; read them as UTC like zip.
+                    modified: file
+                        .datetime()
+                        .map(|dt| dt.assume_utc().unix_timestamp())
+                        .unwrap_or(0),
+                    compression: compression.clone(),
+                    compression_level: level.clone(),
+                    ..Default::default()
+                });
+            }
+        }
+        Ok(entries)
+    }
+}
+
+// =======================
+// ISO BACKEND
+// =======================
+
+/// ISO 9660 disc images, with Rock Ridge and Joliet names. Images that only
+/// carry UDF structures are read through the `7z` tool instead, split ones
+/// from their first volume.
+struct IsoBackend;
+
+impl IsoBackend {
+    fn open(path: &str) -> Result<Option<iso::IsoImage>> {
+        iso::IsoImage::open(path)
+    }
+
+    fn find(image: &iso::IsoImage, inner: &str) -> Result<usize> {
+        let target = normalize_inner(inner);
+        image
+            .entries
+            .iter()
+            .position(|e| e.path == target && !e.is_dir)
+            .ok_or_else(|| anyhow!("Entry '{}' not found", inner))
+    }
+
+    fn metadata(path: &str) -> Result<Option<ArchiveMetadata>> {
+        Ok(Self::open(path)?.map(|image| ArchiveMetadata {
+            volume_label: image.volume_label,
+            created: image.created,
+            ..Default::default()
+        }))
+    }
+}
+
+impl ArchiveBackend for IsoBackend {
+    fn copy_file(path: &str, inner: &str, opts: &ArchiveOptions, out: &mut dyn Write) -> Result<u64> {
+        let Some(mut image) = Self::open(path)? else {
+            return SevenZipBackend::copy_file(&volumes::first_volume(path), inner, opts, out);
+        };
+        let index = Self::find(&image, inner)?;
+        image.copy_entry(index, out)
+    }
+
+    fn extract(path: &str, dest: &str, files: &[String], opts: &ArchiveOptions) -> Result<Vec<String>> {
+        let Some(mut image) = Self::open(path)? else {
+            return SevenZipBackend::extract(&volumes::first_volume(path), dest, files, opts);
+        };
+        let wanted: Vec<String> = files.iter().map(|f| normalize_inner(f)).collect();
+        let mut extracted = Vec::new();
+
+        for index in 0..image.entries.len() {
+            let entry = &image.entries[index];
+            let (name, is_dir, mode) = (entry.path.clone(), entry.is_dir, entry.mode);
+            let selected = wanted.is_empty()
+                || wanted
+                    .iter()
+                    .any(|w| name == *w || name.starts_with(&format!("{}/", w)));
+            // Symlinks are not recreated; their targets may point anywhere.
+            if !selected || entry.symlink.is_some() {
+                continue;
+            }
+            let Some(out_path) = enclosed_path(dest, &name) else {
+                continue;
+            };
+            if is_dir {
+                std::fs::create_dir_all(&out_path)?;
+            } else {
+                if let Some(parent) = out_path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                image.copy_entry(index, &mut File::create(&out_path)?)?;
+                // Rock Ridge permissions; directories keep the default so
+                // read-only ones can still be filled.
+                #[cfg(unix)]
+                if let Some(mode) = mode {
+                    use std::os::unix::fs::PermissionsExt;
+                    std::fs::set_permissions(&out_path, std::fs::Permissions::from_mode(mode))?;
+                }
+            }
+            extracted.push(name);
+        }
+        if extracted.is_empty() && !wanted.is_empty() {
+            return Err(anyhow!("No matching entries in archive"));
+        }
+        Ok(extracted)
+    }
+
+    fn entries(path: &str, opts: &ArchiveOptions) -> Result<Vec<ArchiveEntry>> {
+        let Some(image) = Self::open(path)? else {
+            return SevenZipBackend::entries(&volumes::first_volume(path), opts);
+        };
+        Ok(image
+            .entries
+            .into_iter()
+            .map(|e| ArchiveEntry {
+                inner_path: e.path,
+                entry_type: if e.is_dir {
+                    ArchiveEntryType::Directory
+                } else if e.symlink.is_some() {
+                    ArchiveEntryType::Symlink
+                } else {
+                    ArchiveEntryType::File
+                },
+                size: e.size,
+                compressed_size: e.size,
+                modified: e.modified,
+                compression: "Stored".to_string(),
+                // Rock Ridge modes are kept as permission bits only.
+                mode: e.mode.map(|mode| {
+                    let file_type = if e.is_dir {
+                        0o040000
+                    } else if e.symlink.is_some() {
+                        attributes::S_IFLNK
+                    } else {
+                        0o100000
+                    };
+                    file_type | mode
+                }),
+                symlink_target: e.symlink,
+                ..Default::default()
+            })
+            .collect())
+    }
+}
+
+// =======================
+// AR BACKEND
+// =======================
+
+/// Unix `ar` archives, mostly static libraries. Members are a flat list.
+struct ArBackend;
+
+impl ArBackend {
+    fn open(path: &str) -> Result<(Source, Vec<package::ArMember>)> {
+        let mut file = Source::open(path)?;
+        let members = package::read_ar(&mut file)?;
+        Ok((file, members))
+    }
+
+    fn copy_member(file: &mut Source, member: &package::ArMember, out: &mut dyn Write) -> Result<u64> {
+        file.seek(SeekFrom::Start(member.offset))?;
+        Ok(std::io::copy(&mut file.take(member.size), out)?)
+    }
+}
+
+impl ArchiveBackend for ArBackend {
+    fn copy_file(path: &str, inner: &str, _opts: &ArchiveOptions, out: &mut dyn Write) -> Result<u64> {
+        let target = normalize_inner(inner);
+        let (mut file, members) = Self::open(path)?;
+        let member = members
+            .iter()
+            .find(|m| m.name == target)
+            .ok_or_else(|| anyhow!("Entry '{}' not found", inner))?;
+        Self::copy_member(&mut file, member, out)
+    }
+
+    fn extract(path: &str, dest: &str, files: &[String], _opts: &ArchiveOptions) -> Result<Vec<String>> {
+        let wanted: Vec<String> = files.iter().map(|f| normalize_inner(f)).collect();
+        let (mut file, members) = Self::open(path)?;
+        let mut extracted = Vec::new();
+
+        std::fs::create_dir_all(dest)?;
+        for member in &members {
+            if !wanted.is_empty() && !wanted.contains(&member.name) {
+                continue;
+            }
+            let Some(out_path) = enclosed_path(dest, &member.name) else {
+                continue;
+            };
+            Self::copy_member(&mut file, member, &mut File::create(&out_path)?)?;
+            extracted.push(member.name.clone());
+        }
+        if extracted.is_empty() && !wanted.is_empty() {
+            return Err(anyhow!("No matching entries in archive"));
+        }
+        Ok(extracted)
+    }
+
+    fn entries(path: &str, _opts: &ArchiveOptions) -> Result<Vec<ArchiveEntry>> {
+        let (_, members) = Self::open(path)?;
+        Ok(members
+            .into_iter()
+            .map(|m| ArchiveEntry {
+                inner_path: m.name,
+                entry_type: ArchiveEntryType::File,
+                size: m.size,
+                compressed_size: m.size,
+                modified: m.modified,
+                compression: "Stored".to_string(),
+                mode: m.mode,
+                owner: m.uid.map(|uid| uid.to_string()),
+                group: m.gid.map(|gid| gid.to_string()),
+                ..Default::default()
+            })
+            .collect())
+    }
+}
+
+// =======================
+// DEBIAN PACKAGE BACKEND
+// =======================
+
+/// Debian packages: an `ar` container whose `data.tar.*` member holds the
+/// installed files and whose `control.tar.*` member holds the metadata.
+struct DebBackend;
+
+impl DebBackend {
+    /// Open the member whose name starts with `prefix` as a tar stream, along
+    /// with the name of its compression.
+    fn open_member(path: &str, prefix: &str) -> Result<(tar::Archive<Box<dyn Read>>, &'static str)> {
+        let (mut file, members) = ArBackend::open(path)?;
+        let member = members
+            .into_iter()
+            .find(|m| m.name.starts_with(prefix))
+            .ok_or_else(|| anyhow!("Package has no {} member", prefix))?;
+        file.seek(SeekFrom::Start(member.offset))?;
+        let (reader, compression) = package::decompress_auto(file.take(member.size))?;
+        Ok((tar::Archive::new(reader), compression))
+    }
+
+    /// Fields of the `control` file.
+    fn metadata(path: &str) -> Result<Option<ArchiveMetadata>> {
+        let (mut control, _) = Self::open_member(path, "control.tar")?;
+        for entry in control.entries()? {
+            let entry = entry?;
+            if TarBackend::entry_path(&entry) == "control" {
+                let mut text = String::new();
+                entry.take(MAX_CONTROL_BYTES).read_to_string(&mut text)?;
+                return Ok(Some(ArchiveMetadata {
+                    package: package::parse_control(&text),
+                    ..Default::default()
+                }));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Control files larger than this are cut off.
+const MAX_CONTROL_BYTES: u64 = 1024 * 1024;
+
+impl ArchiveBackend for DebBackend {
+    fn copy_file(path: &str, inner: &str, _opts: &ArchiveOptions, out: &mut dyn Write) -> Result<u64> {
+        let (mut data, _) = Self::open_member(path, "data.tar")?;
+        TarBackend::copy_from(&mut data, inner, out)
+    }
+
+    fn extract(path: &str, dest: &str, files: &[String], _opts: &ArchiveOptions) -> Result<Vec<String>> {
+        let (mut data, _) = Self::open_member(path, "data.tar")?;
+        TarBackend::extract_from(&mut data, dest, files)
+    }
+
+    fn entries(path: &str, _opts: &ArchiveOptions) -> Result<Vec<ArchiveEntry>> {
+        let (mut data, compression) = Self::open_member(path, "data.tar")?;
+        TarBackend::entries_from(&mut data, compression)
+    }
+
+    fn test(path: &str, _opts: &ArchiveOptions, job: &JobHandle) -> Result<TestOutcome> {
+        let (data, _) = Self::open_member(path, "data.tar")?;
+        TarBackend::test_from(data, job)
+    }
+
+    fn for_each_entry(path: &str, _opts: &ArchiveOptions, job: &JobHandle, visit: &mut EntryVisitor) -> Result<()> {
+        let (mut data, compression) = Self::open_member(path, "data.tar")?;
+        TarBackend::for_each_from(&mut data, compression, job, visit)
+    }
+}
+
+// =======================
+// CPIO BACKEND
+// =======================
+
+/// cpio archives (`newc` and portable formats), optionally compressed as in
+/// initramfs images. Like tar, every call streams from the start.
+struct CpioBackend;
+
+impl CpioBackend {
+    fn open(path: &str) -> Result<(package::CpioReader<Box<dyn Read>>, &'static str)> {
+        let (reader, compression) = package::decompress_auto(Source::open(path)?)?;
+        Ok((package::CpioReader::new(reader), compression))
+    }
+
+    /// The operations below work on any cpio stream, including the payload
+    /// of an RPM.
+    fn copy_from<R: Read>(reader: &mut package::CpioReader<R>, inner: &str, out: &mut dyn Write) -> Result<u64> {
+        let target = normalize_inner(inner);
+        while let Some(entry) = reader.next_entry()? {
+            if entry.path == target && !entry.is_dir() {
+                return reader.copy_data(out);
+            }
+        }
+        Err(anyhow!("Entry '{}' not found", inner))
+    }
+
+    fn extract_from<R: Read>(
+        reader: &mut package::CpioReader<R>,
+        dest: &str,
+        files: &[String],
+    ) -> Result<Vec<String>> {
+        let wanted: Vec<String> = files.iter().map(|f| normalize_inner(f)).collect();
+        let mut extracted = Vec::new();
+
+        std::fs::create_dir_all(dest)?;
+        while let Some(entry) = reader.next_entry()? {
+            let name = entry.path.clone();
+            let selected = wanted.is_empty()
+                || wanted
+                    .iter()
+                    .any(|w| name == *w || name.starts_with(&format!("{}/", w)));
+            // Only files and directories; links and device nodes are skipped.
+            if !selected || name.is_empty() || !(entry.is_dir() || entry.is_file()) {
+                continue;
+            }
+            let Some(out_path) = enclosed_path(dest, &name) else {
+                continue;
+            };
+            if entry.is_dir() {
+                std::fs::create_dir_all(&out_path)?;
+            } else {
+                if let Some(parent) = out_path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                reader.copy_data(&mut File::create(&out_path)?)?;
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    let mode = std::fs::Permissions::from_mode(entry.mode & 0o7777);
+                    std::fs::set_permissions(&out_path, mode)?;
+                }
+            }
+            extracted.push(name);
+        }
+        if extracted.is_empty() && !wanted.is_empty() {
+            return Err(anyhow!("No matching entries in archive"));
+        }
+        Ok(extracted)
+    }
+
+    fn entries_from<R: Read>(reader: &mut package::CpioReader<R>, compression: &str) -> Result<Vec<ArchiveEntry>> {
+        let mut entries = Vec::new();
+        while let Some(record) = Self::next_record(reader, compression)? {
+            entries.push(record);
+        }
+        Ok(entries)
+    }
+
+    /// Advance past the root entry (`.`) to the next one and describe it;
+    /// `None` at the end. A symlink's data is consumed to read its target.
+    fn next_record<R: Read>(reader: &mut package::CpioReader<R>, compression: &str) -> Result<Option<ArchiveEntry>> {
+        let entry = loop {
+            match reader.next_entry()? {
+                None => return Ok(None),
+                Some(entry) if entry.path.is_empty() => continue,
+                Some(entry) => break entry,
+            }
+        };
+        let is_dir = entry.is_dir();
+        let is_link = attributes::is_symlink(entry.mode);
+        // A link's target is stored as its data.
+        let mut symlink_target = None;
+        if is_link && entry.size <= attributes::MAX_LINK_TARGET {
+            let mut target = Vec::new();
+            reader.copy_data(&mut target)?;
+            symlink_target = Some(String::from_utf8_lossy(&target).into_owned());
+        }
+        Ok(Some(ArchiveEntry {
+            inner_path: entry.path,
+            entry_type: if is_dir {
+                ArchiveEntryType::Directory
+            } else if is_link {
+                ArchiveEntryType::Symlink
+            } else {
+                ArchiveEntryType::File
+            },
+            size: if is_dir { 0 } else { entry.size },
+            compressed_size: 0,
+            modified: entry.modified,
+            compression: compression.to_string(),
+            mode: Some(entry.mode),
+            owner: Some(entry.uid.to_string()),
+            group: Some(entry.gid.to_string()),
+            symlink_target,
+            ..Default::default()
+        }))
+    }
+
+    fn for_each_from<R: Read>(
+        reader: &mut package::CpioReader<R>,
+        compression: &str,
+        job: &JobHandle,
+        visit: &mut EntryVisitor,
+    ) -> Result<()> {
+        while let Some(record) = Self::next_record(reader, compression)? {
+            job.check_cancelled()?;
+            visit(&record, reader)?;
+        }
+        Ok(())
+    }
+
+    fn test_from<R: Read>(mut reader: package::CpioReader<R>, job: &JobHandle) -> Result<TestOutcome> {
+        let mut outcome = TestOutcome::default();
+        loop {
+            job.check_cancelled()?;
+            let entry = match reader.next_entry() {
+                Ok(Some(entry)) => entry,
+                Ok(None) => break,
+                Err(e) => {
+                    outcome.error = Some(e.to_string());
+                    break;
+                }
+            };
+            if !entry.is_file() {
+                continue;
+            }
+            job.begin_item(&entry.path);
+            let result = reader.copy_data(&mut test_sink(job));
+            outcome.record(entry.path, entry.size, result)?;
+            job.finish_item();
+        }
+        job.check_cancelled()?;
+        if outcome.error.is_none() {
+            outcome.error = drain_trailer(reader.into_inner());
+        }
+        Ok(outcome)
+    }
+}
+
+impl ArchiveBackend for CpioBackend {
+    fn copy_file(path: &str, inner: &str, _opts: &ArchiveOptions, out: &mut dyn Write) -> Result<u64> {
+        Self::copy_from(&mut Self::open(path)?.0, inner, out)
+    }
+
+    fn extract(path: &str, dest: &str, files: &[String], _opts: &ArchiveOptions) -> Result<Vec<String>> {
+        Self::extract_from(&mut Self::open(path)?.0, dest, files)
+    }
+
+    fn entries(path: &str, _opts: &ArchiveOptions) -> Result<Vec<ArchiveEntry>> {
+        let (mut reader, compression) = Self::open(path)?;
+        Self::entries_from(&mut reader, compression)
+    }
+
+    fn test(path: &str, _opts: &ArchiveOptions, job: &JobHandle) -> Result<TestOutcome> {
+        Self::test_from(Self::open(path)?.0, job)
+    }
+
+    fn for_each_entry(path: &str, _opts: &ArchiveOptions, job: &JobHandle, visit: &mut EntryVisitor) -> Result<()> {
+        let (mut reader, compression) = Self::open(path)?;
+        Self::for_each_from(&mut reader, compression, job, visit)
+    }
+}
+
+// =======================
+// RPM BACKEND
+// =======================
+
+/// RPM packages: a lead and two header structures followed by a compressed
+/// cpio payload.
+struct RpmBackend;
+
+impl RpmBackend {
+    fn open(path: &str) -> Result<(package::CpioReader<Box<dyn Read>>, &'static str)> {
+        let mut file = Source::open(path)?;
+        let rpm = package::read_rpm(&mut file)?;
+        file.seek(SeekFrom::Start(rpm.payload_offset))?;
+        let (reader, compression) = package::decompress_auto(file)?;
+        Ok((package::CpioReader::new(reader), compression))
+    }
+
+    /// Name, version, dependencies and other fields of the main header.
+    fn metadata(path: &str) -> Result<Option<ArchiveMetadata>> {
+        let rpm = package::read_rpm(&mut Source::open(path)?)?;
+        Ok(Some(ArchiveMetadata {
+            package: rpm.metadata,
+            ..Default::default()
+        }))
+    }
+}
+
+impl ArchiveBackend for RpmBackend {
+    fn copy_file(path: &str, inner: &str, _opts: &ArchiveOptions, out: &mut dyn Write) -> Result<u64> {
+        CpioBackend::copy_from(&mut Self::open(path)?.0, inner, out)
+    }
+
+    fn extract(path: &str, dest: &str, files: &[String], _opts: &ArchiveOptions) -> Result<Vec<String>> {
+        CpioBackend::extract_from(&mut Self::open(path)?.0, dest, files)
+    }
+
+    fn entries(path: &str, _opts: &ArchiveOptions) -> Result<Vec<ArchiveEntry>> {
+        let (mut reader, compression) = Self::open(path)?;
+        CpioBackend::entries_from(&mut reader, compression)
+    }
+
+    fn test(path: &str, _opts: &ArchiveOptions, job: &JobHandle) -> Result<TestOutcome> {
+        CpioBackend::test_from(Self::open(path)?.0, job)
+    }
+
+    fn for_each_entry(path: &str, _opts: &ArchiveOptions, job: &JobHandle, visit: &mut EntryVisitor) -> Result<()> {
+        let (mut reader, compression) = Self::open(path)?;
+        CpioBackend::for_each_from(&mut reader, compression, job, visit)
+    }
+}
+
+// =======================
+// COMPRESSED FILE BACKEND
+// =======================
+
+/// A single gzip/bzip2/xz/zstd-compressed file, presented as an archive with
+/// one entry: the decompressed file.
+struct CompressedBackend;
+
+impl CompressedBackend {
+    fn format(path: &str) -> ArchiveFormat {
+        ArchiveFormat::detect(path).unwrap_or(ArchiveFormat::Gz)
+    }
+
+    /// Name of the decompressed file: the name recorded in the gzip header,
+    /// else the archive's name without its last extension.
+    fn entry_name(path: &str) -> String {
+        if Self::format(path) == ArchiveFormat::Gz {
+            if let Ok(file) = Source::open(path) {
+                let decoder = flate2::read::GzDecoder::new(file);
+                let stored = decoder
+                    .header()
+                    .and_then(|h| h.filename())
+                    .map(|name| String::from_utf8_lossy(name).into_owned());
+                // Only the final component; the header is untrusted input.
+                if let Some(name) = stored.as_deref().and_then(|n| n.rsplit(['/', '\\']).next()) {
+                    if !matches!(name, "" | "." | "..") && !name.contains(':') {
+                        return name.to_string();
+                    }
+                }
+            }
+        }
+
+        let file_name = Path::new(path)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        match file_name.rsplit_once('.') {
+            Some((stem, _)) if !stem.is_empty() => stem.to_string(),
+            _ => file_name,
+        }
+    }
+
+    /// Uncompressed size when the format records it without decompressing:
+    /// the gzip trailer (modulo 4 GiB) or the zstd frame header. 0 otherwise.
+    fn uncompressed_size(path: &str, format: ArchiveFormat) -> Result<u64> {
+        let mut file = Source::open(path)?;
+        match format {
+            ArchiveFormat::Gz if file.size()? >= 18 => {
+                file.seek(SeekFrom::End(-4))?;
+                let mut trailer = [0u8; 4];
+                file.read_exact(&mut trailer)?;
+                Ok(u32::from_le_bytes(trailer) as u64)
+            }
+            ArchiveFormat::Zst => {
+                let mut header = Vec::with_capacity(18);
+                file.take(18).read_to_end(&mut header)?;
+                Ok(zstd::zstd_safe::get_frame_content_size(&header)
+                    .ok()
+                    .flatten()
+                    .unwrap_or(0))
+            }
+            _ => Ok(0),
+        }
+    }
+
+    /// What a gzip stream records besides the name: the compressor setting
+    /// and host OS in the header, the CRC32 of the data in the trailer.
+    fn gzip_attributes(path: &str, entry: &mut ArchiveEntry) -> Result<()> {
+        let mut file = Source::open(path)?;
+        let mut header = [0u8; 10];
+        file.read_exact(&mut header)?;
+        entry.compression_level = match header[8] {
+            2 => Some("Maximum".to_string()),
+            4 => Some("Fast".to_string()),
+            _ => None,
+        };
+        entry.host_os = attributes::gzip_host_os(header[9]);
+        file.seek(SeekFrom::End(-8))?;
+        let mut crc = [0u8; 4];
+        file.read_exact(&mut crc)?;
+        entry.crc32 = Some(u32::from_le_bytes(crc));
+        Ok(())
+    }
+
+    /// The comment from a gzip header.
+    fn metadata(path: &str) -> Result<Option<ArchiveMetadata>> {
+        let decoder = flate2::read::GzDecoder::new(Source::open(path)?);
+        let comment = decoder
+            .header()
+            .and_then(|h| h.comment())
+            .map(|c| String::from_utf8_lossy(c).into_owned())
+            .filter(|c| !c.is_empty());
+        Ok(comment.map(|comment| ArchiveMetadata {
+            comment: Some(comment),
+            ..Default::default()
+        }))
+    }
+
+    fn check_name(path: &str, inner: &str) -> Result<String> {
+        let name = Self::entry_name(path);
+        if normalize_inner(inner) != name {
+            return Err(anyhow!("Entry '{}' not found", inner));
+        }
+        Ok(name)
+    }
+}
+
+impl ArchiveBackend for CompressedBackend {
+    fn copy_file(path: &str, inner: &str, _opts: &ArchiveOptions, out: &mut dyn Write) -> Result<u64> {
+        Self::check_name(path, inner)?;
+        let mut reader = Self::format(path).decompress(Source::open(path)?)?;
+        Ok(std::io::copy(&mut reader, out)?)
+    }
+
+    fn extract(path: &str, dest: &str, files: &[String], opts: &ArchiveOptions) -> Result<Vec<String>> {
+        let name = match files {
+            [] => Self::entry_name(path),
+            [file] => Self::check_name(path, file)?,
+            _ => return Err(anyhow!("A compressed file holds a single entry")),
+        };
+        let out_path = enclosed_path(dest, &name)
+            .filter(|p| p.as_path() != Path::new(dest))
+            .ok_or_else(|| anyhow!("Refusing to extract '{}' outside the destination", name))?;
+        std::fs::create_dir_all(dest)?;
+        let mut out = File::create(out_path)?;
+        Self::copy_file(path, &name, opts, &mut out)?;
+        Ok(vec![name])
+    }
+
+    fn entries(path: &str, _opts: &ArchiveOptions) -> Result<Vec<ArchiveEntry>> {
+        let format = Self::format(path);
+        let source = Source::open(path)?;
+        let compressed_size = source.size()?;
+        let modified = source
+            .modified()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+
+        let mut entry = ArchiveEntry {
+            inner_path: Self::entry_name(path),
+            entry_type: ArchiveEntryType::File,
+            size: Self::uncompressed_size(path, format)?,
+            compressed_size,
+            modified,
+            compression: format.stream_compression().to_string(),
+            ..Default::default()
+        };
+        if format == ArchiveFormat::Gz && compressed_size >= 18 {
+            Self::gzip_attributes(path, &mut entry)?;
+        }
+        Ok(vec![entry])
+    }
+
+    /// gzip, bzip2, xz and zstd all verify a checksum at the end of the stream.
+    fn test(path: &str, _opts: &ArchiveOptions, job: &JobHandle) -> Result<TestOutcome> {
+        let name = Self::entry_name(path);
+        job.set_totals(1, 0);
+        job.begin_item(&name);
+        let result = Self::format(path)
+            .decompress(Source::open(path)?)
+            .and_then(|mut reader| Ok(std::io::copy(&mut reader, &mut test_sink(job))?));
+        let size = *result.as_ref().unwrap_or(&0);
+
+        let mut outcome = TestOutcome::default();
+        outcome.record(name, size, result)?;
+        job.finish_item();
+        job.check_cancelled()?;
+        Ok(outcome)
+    }
+
+    /// Only gzip and zstd may record the size, and gzip only modulo 4 GiB,
+    /// so the stream is decompressed once to measure it and again to read.
+    fn for_each_entry(path: &str, opts: &ArchiveOptions, job: &JobHandle, visit: &mut EntryVisitor) -> Result<()> {
+        let format = Self::format(path);
+        let mut entry = Self::entries(path, opts)?.remove(0);
+        entry.size = std::io::copy(&mut format.decompress(Source::open(path)?)?, &mut std::io::sink())?;
+        report_totals(job, std::slice::from_ref(&entry));
+        job.check_cancelled()?;
+        visit(&entry, &mut format.decompress(Source::open(path)?)?)
+    }
+}
+
+// =======================
+// Directory Tree
+// =======================
+
+/// The records of an archive arranged as a directory tree, built once and
+/// then listed at any directory.
+///
+/// Records are keyed by their normalized full path. Parent directories that
+/// have no record of their own are synthesized, and every directory's sizes
+/// are the totals of the files below it.
+struct ArchiveIndex {
+    tree: BTreeMap<String, ArchiveEntry>,
+}
+
+impl ArchiveIndex {
+    fn build(records: Vec<ArchiveEntry>) -> Self {
+        let mut tree: BTreeMap<String, ArchiveEntry> = BTreeMap::new();
+        let mut implicit = HashSet::new();
+
+        for mut record in records {
+            let path = normalize_inner(&record.inner_path);
+            if path.is_empty() {
+                continue;
+            }
+            let mut parent = path.as_str();
+            while let Some((dir, _)) = parent.rsplit_once('/') {
+                tree.entry(dir.to_string()).or_insert_with(|| {
+                    implicit.insert(dir.to_string());
+                    ArchiveEntry {
+                        inner_path: dir.to_string(),
+                        entry_type: ArchiveEntryType::Directory,
+                        ..Default::default()
+                    }
+                });
+                parent = dir;
+            }
+            if matches!(record.entry_type, ArchiveEntryType::Directory) {
+                record.size = 0;
+                record.compressed_size = 0;
+            }
+            record.inner_path = path.clone();
+            implicit.remove(&path);
+            // A later record for the same path supersedes the earlier one, as it
+            // would when extracting.
+            tree.insert(path, record);
+        }
+
+        // Roll file sizes (and the newest mtime, for synthesized directories) up
+        // into every ancestor directory.
+        let files: Vec<(String, u64, u64, i64)> = tree
+            .values()
+            .filter(|e| matches!(e.entry_type, ArchiveEntryType::File))
+            .map(|e| (e.inner_path.clone(), e.size, e.compressed_size, e.modified))
+            .collect();
+        for (path, size, compressed, modified) in files {
+            let mut parent = path.as_str();
+            while let Some((dir, _)) = parent.rsplit_once('/') {
+                if let Some(entry) = tree.get_mut(dir) {
+                    entry.size += size;
+                    entry.compressed_size += compressed;
+                    if implicit.contains(dir) {
+                        entry.modified = entry.modified.max(modified);
+                    }
+                }
+                parent = dir;
+            }
+        }
+        Self { tree }
+    }
+
+    /// Number of entries, synthesized directories included.
+    fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    /// The direct children of `inner` (or all descendants when `recursive`)
+    /// and their total file size.
+    fn list(&self, inner: &str, recursive: bool) -> Result<(Vec<ArchiveEntry>, u64)> {
+        let tree = &self.tree;
+        let prefix = normalize_inner(inner);
+        if !prefix.is_empty()
+            && !tree
+                .get(&prefix)
+                .is_some_and(|e| matches!(e.entry_type, ArchiveEntryType::Directory))
+        {
+            return Err(anyhow!("Directory '{}' not found in archive", prefix));
+        }
+        let start = if prefix.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", prefix)
+        };
+
+        let mut entries = Vec::new();
+        let mut total_size = 0;
+        for (path, entry) in tree.range(start.clone()..) {
+            let Some(rel) = path.strip_prefix(&start) else {
+                break;
+            };
+            if !recursive && rel.contains('/') {
+                continue;
+            }
+            if matches!(entry.entry_type, ArchiveEntryType::File) || !recursive {
+                total_size += entry.size;
+            }
+            let mut entry = entry.clone();
+            entry.name = rel.rsplit('/').next().unwrap_or(rel).to_string();
+            entries.push(entry);
+        }
+        Ok((entries, total_size))
+    }
+}
+
+/// A parsed archive, as kept in the index cache.
+struct IndexedArchive {
+    format: ArchiveFormat,
+    index: ArchiveIndex,
+    metadata: Option<ArchiveMetadata>,
+}
+
+// =======================
+// Nested Archives
+// =======================
+
+/// Separator between an archive entry and the path inside it,
+/// e.g. `lib/inner.jar!/META-INF`.
+pub const NESTED_SEPARATOR: &str = "!/";
+
+/// Split a filesystem path that crosses into an archive, such as
+/// `/home/me/x.zip!/docs/readme.md`, into the archive on disk and the path
+/// inside it (which may itself pass through nested archives). A `!` only
+/// counts when what precedes it is an existing file, so directories whose
+/// names end in `!` stay ordinary paths. `None` for plain paths.
+pub fn split_virtual_path(path: &str) -> Option<(String, String)> {
+    let mut search = 0;
+    while let Some(i) = path[search..].find('!').map(|i| search + i) {
+        let rest = &path[i + 1..];
+        if rest.is_empty() || rest.starts_with(['/', '\\']) {
+            let archive = &path[..i];
+            if Path::new(archive).is_file() {
+                return Some((archive.to_string(), rest.trim_start_matches(['/', '\\']).to_string()));
+            }
+        }
+        search = i + 1;
+    }
+    None
+}
+
+/// The virtual path of `inner` inside the archive at `archive`.
+pub fn virtual_path(archive: &str, inner: &str) -> String {
+    format!("{}{}{}", archive, NESTED_SEPARATOR, inner)
+}
+
+/// Inner archives larger than this are not spooled for browsing.
+const MAX_NESTED_ARCHIVE_BYTES: u64 = 512 * 1024 * 1024;
+
+/// An archive on disk that backends can open by path. For a nested archive
+/// this is a temp copy of the innermost archive; dropping it cleans up.
+struct ResolvedArchive {
+    path: String,
+    /// Nested prefix (`lib/inner.jar!/`) to prepend to paths reported back.
+    prefix: String,
+    /// A split archive being read across its volumes under `path`.
+    _mount: Option<volumes::Mount>,
+    _spool: Vec<tempfile::TempDir>,
+}
+
+/// Split `inner` at every `!/` into the normalized archive entries it passes
+/// through and the path left inside the innermost one.
+fn nested_segments(inner: &str) -> (Vec<String>, String) {
+    let mut segments: Vec<&str> = inner.split(NESTED_SEPARATOR).collect();
+    // "inner.jar!" (no trailing slash) also means the root of inner.jar
+    if let Some(last) = segments.last_mut() {
+        if let Some(stripped) = last.strip_suffix('!') {
+            *last = stripped;
+            segments.push("");
+        }
+    }
+    let rest = segments.pop().unwrap_or_default().to_string();
+    (segments.into_iter().map(normalize_inner).collect(), rest)
+}
+
+/// Nested prefix (`lib/inner.jar!/`) of paths inside the given segments.
+fn nested_prefix(segments: &[String]) -> String {
+    segments
+        .iter()
+        .map(|segment| format!("{}{}", segment, NESTED_SEPARATOR))
+        .collect()
+}
+
+/// Split `inner` at every `!/` and spool each intermediate archive entry to
+/// a temp file. Returns the innermost archive and the path left inside it.
+fn resolve_nested(path: &str, inner: &str, opts: &ArchiveOptions) -> Result<(ResolvedArchive, String)> {
+    let (segments, rest) = nested_segments(inner);
+
+    let mut resolved = ResolvedArchive {
+        path: path.to_string(),
+        prefix: nested_prefix(&segments),
+        _mount: None,
+        _spool: Vec::new(),
+    };
+    // Whichever volume of a split archive was given, the 7z tool reads 7z
+    // and RAR sets from their first volume; other sets are mounted and read
+    // across their volumes in place.
+    if let Some(set) = volumes::find(path)? {
+        let first = set.volumes[0].to_string_lossy().into_owned();
+        match detect::detect(&first) {
+            Some(ArchiveFormat::SevenZip | ArchiveFormat::Rar) => resolved.path = first,
+            _ => {
+                let mount = set.mount()?;
+                resolved.path = mount.path().to_string_lossy().into_owned();
+                resolved._mount = Some(mount);
+            }
+        }
+    }
+    for segment in &segments {
+        // Keep the entry's own name; it is the extension hint for detection
+        // and the name of a single compressed file's content.
+        let name = segment.rsplit('/').next().unwrap_or_default();
+        let spool = tempfile::Builder::new().prefix("nested-").tempdir()?;
+        let spooled = spool.path().join(name);
+        {
+            let mut limited = LimitedWriter {
+                inner: std::io::BufWriter::new(File::create(&spooled)?),
+                remaining: MAX_NESTED_ARCHIVE_BYTES,
+            };
+            dispatch_copy(&resolved.path, segment, opts, &mut limited).map_err(|e| {
+                if e.downcast_ref::<std::io::Error>()
+                    .is_some_and(|io| io.kind() == std::io::ErrorKind::FileTooLarge)
+                {
+                    anyhow!(
+                        "Nested archive '{}' exceeds the {} MiB limit",
+                        segment,
+                        MAX_NESTED_ARCHIVE_BYTES / (1024 * 1024)
+                    )
+                } else {
+                    e
+                }
+            })?;
+            limited.inner.flush()?;
+        }
+        resolved.path = spooled.to_string_lossy().into_owned();
+        resolved._spool.push(spool);
+    }
+    Ok((resolved, rest))
+}
+
+/// Writer that fails with `FileTooLarge` once `remaining` bytes are used up.
+struct LimitedWriter<W> {
+    inner: W,
+    remaining: u64,
+}
+
+impl<W: Write> Write for LimitedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if buf.len() as u64 > self.remaining {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::FileTooLarge,
+                "size limit exceeded",
+            ));
+        }
+        let n = self.inner.write(buf)?;
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn detect_format(path: &str) -> Result<ArchiveFormat> {
+    ArchiveFormat::detect(path).ok_or_else(|| anyhow!("Unsupported archive format"))
+}
+
+fn dispatch_copy(path: &str, inner: &str, opts: &ArchiveOptions, out: &mut dyn Write) -> Result<u64> {
+    let format = detect_format(path)?;
+    match format {
+        ArchiveFormat::Zip => ZipBackend::copy_file(path, inner, opts, out),
+        ArchiveFormat::SevenZip => SevenZipBackend::copy_file(path, inner, opts, out),
+        ArchiveFormat::Tar
+        | ArchiveFormat::TarGz
+        | ArchiveFormat::TarBz2
+        | ArchiveFormat::TarXz
+        | ArchiveFormat::TarZst => TarBackend::copy_file(path, inner, opts, out),
+        ArchiveFormat::Gz | ArchiveFormat::Bz2 | ArchiveFormat::Xz | ArchiveFormat::Zst => {
+            CompressedBackend::copy_file(path, inner, opts, out)
+        }
+        ArchiveFormat::Cab => CabBackend::copy_file(path, inner, opts, out),
+        ArchiveFormat::Rar => RarBackend::copy_file(path, inner, opts, out),
+        ArchiveFormat::Iso => IsoBackend::copy_file(path, inner, opts, out),
+        ArchiveFormat::Deb => DebBackend::copy_file(path, inner, opts, out),
+        ArchiveFormat::Rpm => RpmBackend::copy_file(path, inner, opts, out),
+        ArchiveFormat::Ar => ArBackend::copy_file(path, inner, opts, out),
+        ArchiveFormat::Cpio => CpioBackend::copy_file(path, inner, opts, out),
+    }
+}
+
+fn dispatch_for_each(path: &str, opts: &ArchiveOptions, job: &JobHandle, visit: &mut EntryVisitor) -> Result<()> {
+    let format = detect_format(path)?;
+    match format {
+        ArchiveFormat::Zip => ZipBackend::for_each_entry(path, opts, job, visit),
+        ArchiveFormat::SevenZip => SevenZipBackend::for_each_entry(path, opts, job, visit),
+        ArchiveFormat::Tar
+        | ArchiveFormat::TarGz
+        | ArchiveFormat::TarBz2
+        | ArchiveFormat::TarXz
+        | ArchiveFormat::TarZst => TarBackend::for_each_entry(path, opts, job, visit),
+        ArchiveFormat::Gz | ArchiveFormat::Bz2 | ArchiveFormat::Xz | ArchiveFormat::Zst => {
+            CompressedBackend::for_each_entry(path, opts, job, visit)
+        }
+        ArchiveFormat::Cab => CabBackend::for_each_entry(path, opts, job, visit),
+        ArchiveFormat::Rar => RarBackend::for_each_entry(path, opts, job, visit),
+        ArchiveFormat::Iso => IsoBackend::for_each_entry(path, opts, job, visit),
+        ArchiveFormat::Deb => DebBackend::for_each_entry(path, opts, job, visit),
+        ArchiveFormat::Rpm => RpmBackend::for_each_entry(path, opts, job, visit),
+        ArchiveFormat::Ar => ArBackend::for_each_entry(path, opts, job, visit),
+        ArchiveFormat::Cpio => CpioBackend::for_each_entry(path, opts, job, visit),
+    }
+}
+
+// =======================
+// Public API
+// =======================
+
+/// Read one file. `inner` may pass through nested archives
+/// (`lib/inner.jar!/META-INF/MANIFEST.MF`).
+pub fn read_archive_file(path: &str, inner: &str, opts: &ArchiveOptions) -> Result<Vec<u8>> {
+    let (archive, inner) = resolve_nested(path, inner, opts)?;
+    let path = archive.path.as_str();
+    let inner = inner.as_str();
+
+    let format = detect_format(path)?;
+    match format {
+        ArchiveFormat::Zip => ZipBackend::read_file(path, inner, opts),
+        ArchiveFormat::SevenZip => SevenZipBackend::read_file(path, inner, opts),
+        ArchiveFormat::Tar
+        | ArchiveFormat::TarGz
+        | ArchiveFormat::TarBz2
+        | ArchiveFormat::TarXz
+        | ArchiveFormat::TarZst => TarBackend::read_file(path, inner, opts),
+        ArchiveFormat::Gz | ArchiveFormat::Bz2 | ArchiveFormat::Xz | ArchiveFormat::Zst => {
+            CompressedBackend::read_file(path, inner, opts)
+        }
+        ArchiveFormat::Cab => CabBackend::read_file(path, inner, opts),
+        ArchiveFormat::Rar => RarBackend::read_file(path, inner, opts),
+        ArchiveFormat::Iso => IsoBackend::read_file(path, inner, opts),
+        ArchiveFormat::Deb => DebBackend::read_file(path, inner, opts),
+        ArchiveFormat::Rpm => RpmBackend::read_file(path, inner, opts),
+        ArchiveFormat::Ar => ArBackend::read_file(path, inner, opts),
+        ArchiveFormat::Cpio => CpioBackend::read_file(path, inner, opts),
+    }
+}
+
+/// Extract `files` (everything when empty) into `dest`. Files inside nested
+/// archives are grouped by the archive that contains them; a path ending in
+/// `!/` extracts that whole inner archive.
+pub fn extract_archive(
+    path: &str,
+    dest: &str,
+    files: &[String],
+    opts: &ArchiveOptions,
+) -> Result<Vec<String>> {
+    let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+    for file in files {
+        let (container, rest) = match file.rfind(NESTED_SEPARATOR) {
+            Some(i) => (&file[..i + NESTED_SEPARATOR.len()], &file[i + NESTED_SEPARATOR.len()..]),
+            None => ("", file.as_str()),
+        };
+        let index = match groups.iter().position(|(c, _)| c == container) {
+            Some(index) => index,
+            None => {
+                groups.push((container.to_string(), Vec::new()));
+                groups.len() - 1
+            }
+        };
+        if !rest.is_empty() {
+            groups[index].1.push(rest.to_string());
+        }
+    }
+    if groups.is_empty() {
+        groups.push((String::new(), Vec::new()));
+    }
+
+    let mut extracted = Vec::new();
+    for (container, files) in groups {
+        let (archive, _) = resolve_nested(path, &container, opts)?;
+        let path = archive.path.as_str();
+        let format = detect_format(path)?;
+        let names = match format {
+            ArchiveFormat::Zip => ZipBackend::extract(path, dest, &files, opts),
+            ArchiveFormat::SevenZip => SevenZipBackend::extract(path, dest, &files, opts),
+            ArchiveFormat::Tar
+            | ArchiveFormat::TarGz
+            | ArchiveFormat::TarBz2
+            | ArchiveFormat::TarXz
+            | ArchiveFormat::TarZst => TarBackend::extract(path, dest, &files, opts),
+            ArchiveFormat::Gz | ArchiveFormat::Bz2 | ArchiveFormat::Xz | ArchiveFormat::Zst => {
+                CompressedBackend::extract(path, dest, &files, opts)
+            }
+            ArchiveFormat::Cab => CabBackend::extract(path, dest, &files, opts),
+            ArchiveFormat::Rar => RarBackend::extract(path, dest, &files, opts),
+            ArchiveFormat::Iso => IsoBackend::extract(path, dest, &files, opts),
+            ArchiveFormat::Deb => DebBackend::extract(path, dest, &files, opts),
+            ArchiveFormat::Rpm => RpmBackend::extract(path, dest, &files, opts),
+            ArchiveFormat::Ar => ArBackend::extract(path, dest, &files, opts),
+            ArchiveFormat::Cpio => CpioBackend::extract(path, dest, &files, opts),
+        }?;
+        extracted.extend(names.into_iter().map(|n| format!("{}{}", archive.prefix, n)));
+    }
+    Ok(extracted)
+}
+
+/// List one directory. `inner` may pass through nested archives; paths in
+/// the listing are reported relative to the outermost archive. The parsed
+/// index is cached, so listing other directories of the same archive does
+/// not read it again until the file changes.
+pub fn list_archive(path: &str, inner: &str, opts: &ArchiveOptions) -> Result<ArchiveListing> {
+    let (segments, inner_path) = nested_segments(inner);
+    let prefix = nested_prefix(&segments);
+    let indexed = cache::get_or_load(path, &prefix, opts, || load_index(path, inner, opts))?;
+
+    let (mut entries, total_size) = indexed.index.list(&inner_path, opts.recursive)?;
+    for entry in &mut entries {
+        entry.inner_path = format!("{}{}", prefix, entry.inner_path);
+    }
+    Ok(ArchiveListing {
+        archive_path: path.to_string(),
+        inner_path: format!("{}{}", prefix, normalize_inner(&inner_path)),
+        format: indexed.format.as_str().to_string(),
+        entries,
+        total_size,
+        metadata: indexed.metadata.clone(),
+    })
+}
+
+/// Read and index the innermost archive that `inner` passes through.
+fn load_index(path: &str, inner: &str, opts: &ArchiveOptions) -> Result<IndexedArchive> {
+    let (archive, _) = resolve_nested(path, inner, opts)?;
+    let spooled = archive.path.as_str();
+
+    let format = detect_format(spooled)?;
+    let records = match format {
+        ArchiveFormat::Zip => ZipBackend::entries(spooled, opts),
+        ArchiveFormat::SevenZip => SevenZipBackend::entries(spooled, opts),
+        ArchiveFormat::Tar
+        | ArchiveFormat::TarGz
+        | ArchiveFormat::TarBz2
+        | ArchiveFormat::TarXz
+        | ArchiveFormat::TarZst => TarBackend::entries(spooled, opts),
+        ArchiveFormat::Gz | ArchiveFormat::Bz2 | ArchiveFormat::Xz | ArchiveFormat::Zst => {
+            CompressedBackend::entries(spooled, opts)
+        }
+        ArchiveFormat::Cab => CabBackend::entries(spooled, opts),
+        ArchiveFormat::Rar => RarBackend::entries(spooled, opts),
+        ArchiveFormat::Iso => IsoBackend::entries(spooled, opts),
+        ArchiveFormat::Deb => DebBackend::entries(spooled, opts),
+        ArchiveFormat::Rpm => RpmBackend::entries(spooled, opts),
+        ArchiveFormat::Ar => ArBackend::entries(spooled, opts),
+        ArchiveFormat::Cpio => CpioBackend::entries(spooled, opts),
+    }?;
+
+    let metadata = match format {
+        ArchiveFormat::Zip => ZipBackend::metadata(spooled, opts)?,
+        ArchiveFormat::SevenZip => SevenZipBackend::metadata(spooled, opts)?,
+        ArchiveFormat::Rar => SevenZipBackend::metadata(&RarBackend::first_volume(spooled), opts)?,
+        ArchiveFormat::Gz => CompressedBackend::metadata(spooled)?,
+        ArchiveFormat::Iso => IsoBackend::metadata(spooled)?,
+        ArchiveFormat::Deb => DebBackend::metadata(spooled)?,
+        ArchiveFormat::Rpm => RpmBackend::metadata(spooled)?,
+        _ => None,
+    };
+
+    Ok(IndexedArchive {
+        format,
+        index: ArchiveIndex::build(records),
+        metadata,
+    })
+}
+
+/// How far a search looks into archives.
+#[derive(Debug, Clone)]
+pub struct ArchiveSearchLimits {
+    /// Levels of archives to open: 1 lists the archive itself, 2 also the
+    /// archives inside it, and so on.
+    pub max_depth: u32,
+    /// Archives larger than this, on disk or nested, are not opened.
+    pub max_size: u64,
+}
+
+impl Default for ArchiveSearchLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 1,
+            max_size: 256 * 1024 * 1024,
+        }
+    }
+}
+
+/// Whether `name` looks like an archive a search should open. Only the name
+/// is checked, so walking a tree does not open every file.
+pub fn is_archive_name(name: &str) -> bool {
+    detect::from_extension(name).is_some()
+}
+
+/// Visit every entry below `inner`, descending into nested archives within
+/// the limits. Nested archives that cannot be read are skipped; errors
+/// reading the archive at `path` itself are returned.
+pub fn walk_archive(
+    path: &str,
+    inner: &str,
+    limits: &ArchiveSearchLimits,
+    opts: &ArchiveOptions,
+    visit: &mut dyn FnMut(&ArchiveEntry),
+) -> Result<()> {
+    let opts = ArchiveOptions {
+        recursive: true,
+        ..opts.clone()
+    };
+    walk_level(path, inner, limits.max_depth, limits, &opts, visit)
+}
+
+fn walk_level(
+    path: &str,
+    inner: &str,
+    depth: u32,
+    limits: &ArchiveSearchLimits,
+    opts: &ArchiveOptions,
+    visit: &mut dyn FnMut(&ArchiveEntry),
+) -> Result<()> {
+    let listing = list_archive(path, inner, opts)?;
+    for entry in &listing.entries {
+        visit(entry);
+        if depth > 1
+            && matches!(entry.entry_type, ArchiveEntryType::File)
+            && entry.size <= limits.max_size
+            && is_archive_name(&entry.name)
+        {
+            let nested = format!("{}{}", entry.inner_path, NESTED_SEPARATOR);
+            let _ = walk_level(path, &nested, depth - 1, limits, opts, visit);
+        }
+    }
+    Ok(())
+}
+
+/// Look up one entry by its path. `inner` may pass through nested archives;
+/// the root of a nested archive (`lib/inner.jar!/`) is reported as a
+/// directory.
+pub fn archive_entry(path: &str, inner: &str, opts: &ArchiveOptions) -> Result<ArchiveEntry> {
+    let inner = inner.trim_end_matches('/');
+    let nested_root = inner.strip_suffix('!');
+    let target = nested_root.unwrap_or(inner);
+    let (parent, name) = target.rsplit_once('/').unwrap_or(("", target));
+    if name.is_empty() {
+        return Err(ArchiveError::EntryNotFound(inner.to_string()).into());
+    }
+
+    let opts = ArchiveOptions {
+        recursive: false,
+        ..opts.clone()
+    };
+    let listing = list_archive(path, parent, &opts)?;
+    let mut entry = listing
+        .entries
+        .into_iter()
+        .find(|e| e.name == name)
+        .ok_or_else(|| ArchiveError::EntryNotFound(target.to_string()))?;
+    if nested_root.is_some() {
+        entry.entry_type = ArchiveEntryType::Directory;
+        entry.inner_path.push('!');
+    }
+    Ok(entry)
+}
+
+/// A file inside an archive, opened to be streamed.
+pub struct EntrySource {
+    /// The entry as listed.
+    pub entry: ArchiveEntry,
+    format: ArchiveFormat,
+    archive: ResolvedArchive,
+    inner: String,
+    opts: ArchiveOptions,
+    /// Offset and length of the entry's bytes in the archive file.
+    stored: Option<(u64, u64)>,
+}
+
+impl EntrySource {
+    /// Size of the decompressed data, unless the format only estimates it
+    /// (a single compressed stream).
+    pub fn size(&self) -> Option<u64> {
+        let estimated = matches!(
+            self.format,
+            ArchiveFormat::Gz | ArchiveFormat::Bz2 | ArchiveFormat::Xz | ArchiveFormat::Zst
+        );
+        (!estimated).then_some(self.entry.size)
+    }
+
+    /// Whether `copy_range` can be used: the entry is a stored, unencrypted
+    /// zip entry whose bytes sit in the archive as they are.
+    pub fn seekable(&self) -> bool {
+        self.stored.is_some()
+    }
+
+    /// Stream the whole decompressed entry into `out`.
+    pub fn copy_to(&self, out: &mut dyn Write) -> Result<u64> {
+        dispatch_copy(&self.archive.path, &self.inner, &self.opts, out)
+    }
+
+    /// Stream `len` bytes starting at `start` into `out`.
+    pub fn copy_range(&self, start: u64, len: u64, out: &mut dyn Write) -> Result<u64> {
+        let (offset, size) = self
+            .stored
+            .ok_or_else(|| anyhow!("Entry '{}' cannot be read from an offset", self.inner))?;
+        if start.saturating_add(len) > size {
+            return Err(anyhow!("Range is outside the entry"));
+        }
+        let mut file = Source::open(&self.archive.path)?;
+        file.seek(SeekFrom::Start(offset + start))?;
+        Ok(std::io::copy(&mut file.take(len), out)?)
+    }
+}
+
+/// Open one file for streaming. `inner` may pass through nested archives,
+/// which are spooled to temp files that live as long as the source.
+pub fn open_entry(path: &str, inner: &str, opts: &ArchiveOptions) -> Result<EntrySource> {
+    let entry = archive_entry(path, inner, opts)?;
+    if !matches!(entry.entry_type, ArchiveEntryType::File) {
+        return Err(ArchiveError::NotAFile(inner.to_string()).into());
+    }
+    let (archive, inner) = resolve_nested(path, inner, opts)?;
+    let format = detect_format(&archive.path)?;
+    let stored = match format {
+        ArchiveFormat::Zip => ZipBackend::stored_span(&archive.path, &inner, opts)?,
+        _ => None,
+    };
+    Ok(EntrySource {
+        entry,
+        format,
+        archive,
+        inner,
+        opts: opts.clone(),
+        stored,
+    })
+}
+
+/// Copy one entry out of the archive to `dest`, which names the copy itself
+/// as in a plain file copy. Directories (including the root of a nested
+/// archive) are copied with their contents and need `recursive`.
+pub fn copy_out(path: &str, inner: &str, dest: &str, recursive: bool, opts: &ArchiveOptions) -> Result<()> {
+    let entry = archive_entry(path, inner, opts)?;
+    let dest = Path::new(dest);
+    let parent = dest
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    std::fs::create_dir_all(parent)?;
+
+    if !matches!(entry.entry_type, ArchiveEntryType::Directory) {
+        // Written next to the destination first so a failed read leaves no
+        // partial file behind.
+        let (archive, rest) = resolve_nested(path, inner, opts)?;
+        let mut staged = tempfile::NamedTempFile::new_in(parent)?;
+        {
+            let mut out = std::io::BufWriter::new(staged.as_file_mut());
+            dispatch_copy(&archive.path, &rest, opts, &mut out)?;
+            out.flush()?;
+        }
+        // Temp files are private; give the copy the entry's permissions.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = entry.mode.map_or(0o644, |mode| mode & 0o777);
+            staged.as_file().set_permissions(std::fs::Permissions::from_mode(mode))?;
+        }
+        staged.persist(dest)?;
+        return Ok(());
+    }
+    if !recursive {
+        return Err(anyhow!("Cannot copy directory without recursive flag"));
+    }
+
+    // Extraction recreates the entry's path inside the archive, so extract
+    // into a staging directory and move just the entry into place.
+    let staging = tempfile::Builder::new().prefix(".copy-").tempdir_in(parent)?;
+    let (selection, rest) = match entry.inner_path.strip_suffix('!') {
+        Some(nested) => (format!("{}{}", nested, NESTED_SEPARATOR), String::new()),
+        None => {
+            let rest = match entry.inner_path.rfind(NESTED_SEPARATOR) {
+                Some(i) => &entry.inner_path[i + NESTED_SEPARATOR.len()..],
+                None => entry.inner_path.as_str(),
+            };
+            (entry.inner_path.clone(), normalize_inner(rest))
+        }
+    };
+    extract_archive(path, &staging.path().to_string_lossy(), &[selection], opts)?;
+    let staged = staging.path().join(&rest);
+    // A directory with no entries below it extracts to nothing.
+    if !staged.exists() {
+        std::fs::create_dir_all(&staged)?;
+    }
+    std::fs::rename(&staged, dest)?;
+    // The root of a nested archive is the staging directory itself.
+    #[cfg(unix)]
+    if rest.is_empty() {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(dest, std::fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+/// Decompress every entry of the archive and verify its checksums without
+/// writing anything to disk.
+pub fn test_archive(path: &str, opts: &ArchiveOptions, job: &JobHandle) -> Result<ArchiveTestReport> {
+    let (archive, _) = resolve_nested(path, "", opts)?;
+    let spooled = archive.path.as_str();
+
+    let format = detect_format(spooled)?;
+    let result = match format {
+        ArchiveFormat::Zip => ZipBackend::test(spooled, opts, job),
+        ArchiveFormat::SevenZip => SevenZipBackend::test(spooled, opts, job),
+        ArchiveFormat::Tar
+        | ArchiveFormat::TarGz
+        | ArchiveFormat::TarBz2
+        | ArchiveFormat::TarXz
+        | ArchiveFormat::TarZst => TarBackend::test(spooled, opts, job),
+        ArchiveFormat::Gz | ArchiveFormat::Bz2 | ArchiveFormat::Xz | ArchiveFormat::Zst => {
+            CompressedBackend::test(spooled, opts, job)
+        }
+        ArchiveFormat::Cab => CabBackend::test(spooled, opts, job),
+        ArchiveFormat::Rar => RarBackend::test(spooled, opts, job),
+        ArchiveFormat::Iso => IsoBackend::test(spooled, opts, job),
+        ArchiveFormat::Deb => DebBackend::test(spooled, opts, job),
+        ArchiveFormat::Rpm => RpmBackend::test(spooled, opts, job),
+        ArchiveFormat::Ar => ArBackend::test(spooled, opts, job),
+        ArchiveFormat::Cpio => CpioBackend::test(spooled, opts, job),
+    };
+    // An archive too damaged to read at all is still a report, not a failed
+    // job; password problems and cancellation are passed on.
+    let outcome = match result {
+        Ok(outcome) => outcome,
+        Err(e) if job.is_cancelled() || e.downcast_ref::<ArchiveError>().is_some() => return Err(e),
+        Err(e) => TestOutcome {
+            entries: Vec::new(),
+            error: Some(e.to_string()),
+        },
+    };
+
+    let failed = outcome.entries.iter().filter(|e| !e.ok).count() as u64;
+    Ok(ArchiveTestReport {
+        archive_path: path.to_string(),
+        format: format.as_str().to_string(),
+        ok: failed == 0 && outcome.error.is_none(),
+        passed: outcome.entries.len() as u64 - failed,
+        failed,
+        entries: outcome.entries,
+        error: outcome.error,
+    })
+}
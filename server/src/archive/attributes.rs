@@ -0,0 +1,258 @@
+//! Per-entry attributes that archive formats store in their own encodings:
+//! host systems, compression method numbers, Unix modes and owners.
+
+use anyhow::{anyhow, Result};
+use std::io::{Read, Seek, SeekFrom};
+
+/// File type bits of a Unix mode.
+pub(super) const S_IFMT: u32 = 0o170000;
+pub(super) const S_IFLNK: u32 = 0o120000;
+
+/// Symlink targets longer than this are not read from the archive.
+pub(super) const MAX_LINK_TARGET: u64 = 4096;
+
+pub(super) fn is_symlink(mode: u32) -> bool {
+    mode & S_IFMT == S_IFLNK
+}
+
+/// Host system byte of a zip "version made by" field (APPNOTE 4.4.2).
+pub(super) fn zip_host_os(host: u8) -> String {
+    let name = match host {
+        0 => "MS-DOS",
+        1 => "Amiga",
+        2 => "OpenVMS",
+        3 => "Unix",
+        4 => "VM/CMS",
+        5 => "Atari ST",
+        6 => "OS/2",
+        7 => "Macintosh",
+        8 => "Z-System",
+        9 => "CP/M",
+        10 => "NTFS",
+        11 => "MVS",
+        12 => "VSE",
+        13 => "Acorn RISC OS",
+        14 => "VFAT",
+        15 => "MVS",
+        16 => "BeOS",
+        17 => "Tandem",
+        18 => "OS/400",
+        19 => "macOS",
+        other => return format!("Unknown ({})", other),
+    };
+    name.to_string()
+}
+
+/// OS byte of a gzip header (RFC 1952); 255 means unknown.
+pub(super) fn gzip_host_os(os: u8) -> Option<String> {
+    let name = match os {
+        0 => "MS-DOS",
+        1 => "Amiga",
+        2 => "OpenVMS",
+        3 => "Unix",
+        4 => "VM/CMS",
+        5 => "Atari TOS",
+        6 => "OS/2",
+        7 => "Macintosh",
+        8 => "Z-System",
+        9 => "CP/M",
+        10 => "TOPS-20",
+        11 => "NTFS",
+        12 => "QDOS",
+        13 => "Acorn RISC OS",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+/// Name of a zip compression method number (APPNOTE 4.4.5).
+pub(super) fn zip_method(method: u16) -> String {
+    let name = match method {
+        0 => "Stored",
+        1 => "Shrink",
+        2..=5 => "Reduce",
+        6 => "Implode",
+        8 => "Deflate",
+        9 => "Deflate64",
+        10 => "PKWARE DCL Implode",
+        12 => "BZip2",
+        14 => "LZMA",
+        16 => "IBM z/OS CMPSC",
+        18 => "IBM TERSE",
+        19 => "LZ77",
+        93 => "Zstd",
+        95 => "XZ",
+        96 => "JPEG",
+        97 => "WavPack",
+        98 => "PPMd",
+        99 => "AES",
+        other => return format!("Method {}", other),
+    };
+    name.to_string()
+}
+
+/// The fields of a zip central directory record that the zip crate does
+/// not expose.
+pub(super) struct ZipRecord {
+    /// High byte of "version made by".
+    pub host: u8,
+    pub flags: u16,
+    pub method: u16,
+    pub external_attributes: u32,
+}
+
+impl ZipRecord {
+    /// Read the central directory record starting at `offset`.
+    pub fn read<R: Read + Seek>(file: &mut R, offset: u64) -> Result<Self> {
+        let mut record = [0u8; 46];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut record)?;
+        if record[..4] != *b"PK\x01\x02" {
+            return Err(anyhow!("Corrupt zip central directory"));
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([record[i], record[i + 1]]);
+        Ok(Self {
+            host: record[5],
+            flags: u16_at(8),
+            method: u16_at(10),
+            external_attributes: u32::from_le_bytes([record[38], record[39], record[40], record[41]]),
+        })
+    }
+
+    pub fn encrypted(&self) -> bool {
+        self.flags & 1 != 0 || self.method == 99
+    }
+
+    /// General purpose bit 11: the name and comment are UTF-8.
+    pub fn utf8_name(&self) -> bool {
+        self.flags & 0x800 != 0
+    }
+
+    /// Unix mode bits, stored in the high half of the external attributes
+    /// by Unix and macOS zippers.
+    pub fn unix_mode(&self) -> Option<u32> {
+        let mode = self.external_attributes >> 16;
+        (matches!(self.host, 3 | 19) && mode != 0).then_some(mode)
+    }
+
+    /// The method the data is compressed with; AES-encrypted entries
+    /// record it in their 0x9901 extra field.
+    pub fn compression(&self, extra: &[u8]) -> String {
+        if self.method == 99 {
+            if let Some(data) = extra_field(extra, 0x9901).filter(|d| d.len() >= 7) {
+                return zip_method(u16::from_le_bytes([data[5], data[6]]));
+            }
+        }
+        zip_method(self.method)
+    }
+
+    /// Deflate records the compressor setting in general purpose bits 1-2.
+    pub fn compression_level(&self, extra: &[u8]) -> Option<String> {
+        let method = match self.method {
+            99 => extra_field(extra, 0x9901)
+                .filter(|d| d.len() >= 7)
+                .map(|d| u16::from_le_bytes([d[5], d[6]]))?,
+            method => method,
+        };
+        if !matches!(method, 8 | 9) {
+            return None;
+        }
+        let level = match (self.flags >> 1) & 3 {
+            0 => "Normal",
+            1 => "Maximum",
+            2 => "Fast",
+            _ => "Super Fast",
+        };
+        Some(level.to_string())
+    }
+}
+
+/// Find the data of extra field `id` in a zip extra block.
+fn extra_field(extra: &[u8], id: u16) -> Option<&[u8]> {
+    let mut rest = extra;
+    while rest.len() >= 4 {
+        let tag = u16::from_le_bytes([rest[0], rest[1]]);
+        let len = u16::from_le_bytes([rest[2], rest[3]]) as usize;
+        let data = rest.get(4..4 + len)?;
+        if tag == id {
+            return Some(data);
+        }
+        rest = &rest[4 + len..];
+    }
+    None
+}
+
+/// The UTF-8 name from an Info-ZIP Unicode Path (0x7075) extra field. It
+/// is ignored when its CRC no longer matches the stored name, which means
+/// a tool unaware of the field renamed the entry.
+pub(super) fn zip_unicode_path(extra: &[u8], raw_name: &[u8]) -> Option<String> {
+    let data = extra_field(extra, 0x7075).filter(|d| d.len() > 5 && d[0] == 1)?;
+    let mut crc = flate2::Crc::new();
+    crc.update(raw_name);
+    if crc.sum() != u32::from_le_bytes([data[1], data[2], data[3], data[4]]) {
+        return None;
+    }
+    String::from_utf8(data[5..].to_vec()).ok()
+}
+
+/// Numeric owner and group from the Info-ZIP "ux" (0x7875) or PKWARE
+/// Unix (0x000d) extra fields.
+pub(super) fn zip_owner(extra: &[u8]) -> (Option<String>, Option<String>) {
+    if let Some(data) = extra_field(extra, 0x7875) {
+        // version, uid size, uid, gid size, gid; ids are little-endian.
+        let read_id = |at: usize| -> Option<(u64, usize)> {
+            let size = *data.get(at)? as usize;
+            let bytes = data.get(at + 1..at + 1 + size).filter(|b| b.len() <= 8)?;
+            let id = bytes.iter().rev().fold(0u64, |acc, &b| acc << 8 | b as u64);
+            Some((id, at + 1 + size))
+        };
+        if data.first() == Some(&1) {
+            if let Some((uid, next)) = read_id(1) {
+                let gid = read_id(next).map(|(gid, _)| gid.to_string());
+                return (Some(uid.to_string()), gid);
+            }
+        }
+    }
+    if let Some(data) = extra_field(extra, 0x000d).filter(|d| d.len() >= 12) {
+        let uid = u16::from_le_bytes([data[8], data[9]]);
+        let gid = u16::from_le_bytes([data[10], data[11]]);
+        return (Some(uid.to_string()), Some(gid.to_string()));
+    }
+    (None, None)
+}
+
+/// Parse a symbolic mode as printed by `ls -l` (`-rw-r--r--`, `drwxr-xr-x`).
+pub(super) fn parse_symbolic_mode(text: &str) -> Option<u32> {
+    let bytes = text.as_bytes();
+    if bytes.len() != 10 {
+        return None;
+    }
+    let file_type = match bytes[0] {
+        b'-' => 0o100000,
+        b'd' => 0o040000,
+        b'l' => S_IFLNK,
+        b'c' => 0o020000,
+        b'b' => 0o060000,
+        b'p' => 0o010000,
+        b's' => 0o140000,
+        _ => return None,
+    };
+    let mut mode = file_type;
+    for (i, &c) in bytes[1..].iter().enumerate() {
+        let bit = 1 << (8 - i);
+        let special = match i {
+            2 => 0o4000,
+            5 => 0o2000,
+            8 => 0o1000,
+            _ => 0,
+        };
+        match (i % 3, c) {
+            (_, b'-') => {}
+            (0, b'r') | (1, b'w') | (2, b'x') => mode |= bit,
+            (2, b's') | (2, b't') => mode |= bit | special,
+            (2, b'S') | (2, b'T') => mode |= special,
+            _ => return None,
+        }
+    }
+    Some(mode)
+}
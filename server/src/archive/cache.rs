@@ -0,0 +1,157 @@
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+use super::volumes;
+use super::{ArchiveOptions, IndexedArchive};
+
+/// Most archive indexes kept at once.
+const MAX_ARCHIVES: usize = 32;
+/// Most entries kept across all indexes, so a few huge archives cannot use
+/// unbounded memory.
+const MAX_ENTRIES: usize = 2_000_000;
+
+// =======================
+// Cache
+// =======================
+
+/// What an index was loaded for: the archive on disk, the nested archive
+/// inside it (`lib/inner.jar!/`, empty for the archive itself), the
+/// password, since encrypted 7z headers cannot be listed without one, and
+/// the zip name encoding, which changes the names themselves. Only a
+/// SHA-256 digest of the password is kept, so the cache never holds it in
+/// plain text.
+#[derive(PartialEq, Eq)]
+struct Key {
+    path: String,
+    nested: String,
+    password: Option<[u8; 32]>,
+    name_encoding: Option<String>,
+}
+
+/// The archive file as it was when indexed, every volume of it for a split
+/// archive; any change makes the index stale.
+#[derive(Clone, PartialEq, Eq)]
+struct Stamp(Vec<FileStamp>);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+impl Stamp {
+    fn of(path: &str) -> Result<Self> {
+        match volumes::find(path)? {
+            Some(set) => Self::of_volumes(&set.volumes),
+            None => Self::of_volumes(&[PathBuf::from(path)]),
+        }
+    }
+
+    fn of_volumes(volumes: &[PathBuf]) -> Result<Self> {
+        let files = volumes
+            .iter()
+            .map(|volume| {
+                let metadata = std::fs::metadata(volume)?;
+                Ok(FileStamp {
+                    size: metadata.len(),
+                    modified: metadata.modified().ok(),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self(files))
+    }
+}
+
+struct Slot {
+    key: Key,
+    stamp: Stamp,
+    index: Arc<IndexedArchive>,
+    last_used: u64,
+}
+
+/// Least-recently-used cache of parsed archive indexes.
+#[derive(Default)]
+struct IndexCache {
+    slots: Vec<Slot>,
+    clock: u64,
+}
+
+fn cache() -> &'static Mutex<IndexCache> {
+    static CACHE: OnceLock<Mutex<IndexCache>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(IndexCache::default()))
+}
+
+/// The index of `nested` inside the archive at `path`: the cached one while
+/// the file's size and modification time are unchanged, otherwise a fresh
+/// one from `load`. Loading happens outside the lock, so a slow archive does
+/// not hold up listings of others.
+pub(super) fn get_or_load(
+    path: &str,
+    nested: &str,
+    opts: &ArchiveOptions,
+    load: impl FnOnce() -> Result<IndexedArchive>,
+) -> Result<Arc<IndexedArchive>> {
+    let stamp = Stamp::of(path)?;
+    let key = Key {
+        path: path.to_string(),
+        nested: nested.to_string(),
+        password: opts.password.as_deref().map(|p| Sha256::digest(p).into()),
+        name_encoding: opts.name_encoding.clone(),
+    };
+
+    if let Some(index) = cache().lock().unwrap().get(&key, &stamp) {
+        return Ok(index);
+    }
+    let index = Arc::new(load()?);
+    cache().lock().unwrap().insert(key, stamp, index.clone());
+    Ok(index)
+}
+
+impl IndexCache {
+    fn get(&mut self, key: &Key, stamp: &Stamp) -> Option<Arc<IndexedArchive>> {
+        self.clock += 1;
+        let slot = self
+            .slots
+            .iter_mut()
+            .find(|slot| slot.key == *key && slot.stamp == *stamp)?;
+        slot.last_used = self.clock;
+        Some(slot.index.clone())
+    }
+
+    fn insert(&mut self, key: Key, stamp: Stamp, index: Arc<IndexedArchive>) {
+        // Everything read from an older version of this file is stale too.
+        self.slots
+            .retain(|slot| slot.key != key && !(slot.key.path == key.path && slot.stamp != stamp));
+        // An index too large to keep would only push everything else out.
+        if index.index.len() > MAX_ENTRIES {
+            return;
+        }
+        self.clock += 1;
+        self.slots.push(Slot {
+            key,
+            stamp,
+            index,
+            last_used: self.clock,
+        });
+
+        while self.slots.len() > MAX_ARCHIVES || self.entries() > MAX_ENTRIES {
+            let Some(oldest) = self
+                .slots
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, slot)| slot.last_used)
+                .map(|(i, _)| i)
+            else {
+                break;
+            };
+            self.slots.remove(oldest);
+        }
+    }
+
+    fn entries(&self) -> usize {
+        self.slots.iter().map(|slot| slot.index.index.len()).sum()
+    }
+}
@@ -0,0 +1,412 @@
+use anyhow::{anyhow, Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::fs::File;
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+use super::{volumes, ArchiveFormat};
+use crate::jobs::{JobHandle, ProgressReader};
+use crate::protocol::CreateArchiveOptions;
+
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+
+// =======================
+// Source collection
+// =======================
+
+pub(super) enum ItemKind {
+    File,
+    Directory,
+    Symlink(PathBuf),
+}
+
+/// A filesystem item queued for archiving.
+pub(super) struct SourceItem {
+    pub path: PathBuf,
+    /// Name stored in the archive: forward slashes, no leading slash.
+    pub name: String,
+    pub kind: ItemKind,
+    pub size: u64,
+    pub mtime: Option<SystemTime>,
+    pub mode: Option<u32>,
+}
+
+pub(crate) fn build_globset(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).with_context(|| format!("Invalid glob '{}'", pattern))?);
+    }
+    Ok(builder.build()?)
+}
+
+/// Walk every source and produce the flat, ordered list of archive entries.
+pub(super) fn collect_items(
+    sources: &[String],
+    options: &CreateArchiveOptions,
+    store_symlinks: bool,
+) -> Result<Vec<SourceItem>> {
+    let exclude = build_globset(&options.exclude)?;
+    let mut items = Vec::new();
+
+    for source in sources {
+        let root = Path::new(source);
+        if std::fs::symlink_metadata(root).is_err() {
+            anyhow::bail!("Source does not exist: {}", source);
+        }
+
+        let base = match &options.base_path {
+            Some(base) => PathBuf::from(base),
+            None => root.parent().map(Path::to_path_buf).unwrap_or_default(),
+        };
+        let stored_name = |path: &Path| -> Option<String> {
+            let rel = path.strip_prefix(&base).ok()?;
+            Some(rel.to_string_lossy().replace('\\', "/"))
+        };
+
+        let walker = WalkDir::new(root)
+            .follow_links(!store_symlinks)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|e| match stored_name(e.path()) {
+                Some(name) => !exclude.is_match(&name) && !exclude.is_match(e.file_name()),
+                None => true,
+            });
+
+        for entry in walker {
+            let entry = entry?;
+            let name = stored_name(entry.path()).ok_or_else(|| {
+                anyhow!(
+                    "{} is not under base path {}",
+                    entry.path().display(),
+                    base.display()
+                )
+            })?;
+            if name.is_empty() {
+                // The base path itself
+                continue;
+            }
+
+            let metadata = entry.metadata()?;
+            let kind = if store_symlinks && entry.path_is_symlink() {
+                ItemKind::Symlink(std::fs::read_link(entry.path())?)
+            } else if metadata.is_dir() {
+                ItemKind::Directory
+            } else {
+                ItemKind::File
+            };
+
+            #[cfg(unix)]
+            let mode = Some(metadata.permissions().mode() & 0o7777);
+            #[cfg(not(unix))]
+            let mode = None;
+
+            items.push(SourceItem {
+                path: entry.path().to_path_buf(),
+                name,
+                size: if matches!(kind, ItemKind::File) {
+                    metadata.len()
+                } else {
+                    0
+                },
+                kind,
+                mtime: metadata.modified().ok(),
+                mode,
+            });
+        }
+    }
+
+    Ok(items)
+}
+
+// =======================
+// Public entry point
+// =======================
+
+/// What `create_archive` wrote.
+pub struct CreatedArchive {
+    /// Names stored in the archive
+    pub entries: Vec<String>,
+    /// The archive file, or its volumes when split
+    pub files: Vec<String>,
+}
+
+/// Create `destination` from `sources`, reporting progress to `job`.
+pub fn create_archive(
+    sources: &[String],
+    destination: &str,
+    format: ArchiveFormat,
+    options: &CreateArchiveOptions,
+    job: &JobHandle,
+) -> Result<CreatedArchive> {
+    if sources.is_empty() {
+        anyhow::bail!("No sources to archive");
+    }
+    if options.password.is_some() && format != ArchiveFormat::SevenZip {
+        anyhow::bail!("Password protection is only supported for 7z archives");
+    }
+    if let Some(size) = options.split_size {
+        volumes::check_split_size(size)?;
+    }
+
+    // 7z has no notion of symlinks; archive what they point to instead.
+    let store_symlinks = options.store_symlinks && format != ArchiveFormat::SevenZip;
+    let items = collect_items(sources, options, store_symlinks)?;
+    let total_bytes = items.iter().map(|i| i.size).sum();
+    job.set_totals(items.len() as u64, total_bytes);
+
+    let mut tmp = staging_file(destination)?;
+    let level = options.compression_level;
+    let out = tmp.as_file_mut();
+
+    match format {
+        ArchiveFormat::Zip => write_zip(out, &items, level, job)?,
+        ArchiveFormat::SevenZip => write_7z(out, &items, level, options.password.as_deref(), job)?,
+        ArchiveFormat::Tar
+        | ArchiveFormat::TarGz
+        | ArchiveFormat::TarBz2
+        | ArchiveFormat::TarXz
+        | ArchiveFormat::TarZst => {
+            let encoder = format.compress(out, level)?;
+            write_tar(encoder, &items, job)?.finish()?;
+        }
+        ArchiveFormat::Gz
+        | ArchiveFormat::Bz2
+        | ArchiveFormat::Xz
+        | ArchiveFormat::Zst
+        | ArchiveFormat::Cab
+        | ArchiveFormat::Rar
+        | ArchiveFormat::Iso
+        | ArchiveFormat::Deb
+        | ArchiveFormat::Rpm
+        | ArchiveFormat::Ar
+        | ArchiveFormat::Cpio => {
+            anyhow::bail!("Creating {} archives is not supported", format.as_str())
+        }
+    }
+
+    let entries = items.into_iter().map(|i| i.name).collect();
+    let files = persist(tmp, destination, options.split_size)?;
+    Ok(CreatedArchive { entries, files })
+}
+
+/// Temp file to build an archive in, next to `destination`. Archives are
+/// moved into place only once complete, so a failed or cancelled job never
+/// leaves a truncated archive behind.
+pub(super) fn staging_file(destination: &str) -> Result<tempfile::NamedTempFile> {
+    let dir = match Path::new(destination).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    Ok(tempfile::NamedTempFile::new_in(dir)?)
+}
+
+/// Move a finished archive into place at `destination`, or split it into
+/// volumes there. Returns the files written.
+pub(super) fn persist(
+    mut tmp: tempfile::NamedTempFile,
+    destination: &str,
+    split_size: Option<u64>,
+) -> Result<Vec<String>> {
+    if let Some(size) = split_size {
+        return volumes::split(tmp.as_file_mut(), destination, size);
+    }
+
+    // Temp files are created owner-only; give the archive regular file permissions.
+    #[cfg(unix)]
+    tmp.as_file()
+        .set_permissions(std::fs::Permissions::from_mode(0o644))?;
+
+    tmp.persist(destination)
+        .with_context(|| format!("Failed to write {}", destination))?;
+    Ok(vec![destination.to_string()])
+}
+
+// =======================
+// Writers
+// =======================
+
+fn write_zip<W: Write + Seek>(
+    out: W,
+    items: &[SourceItem],
+    level: Option<u32>,
+    job: &JobHandle,
+) -> Result<()> {
+    let mut zip = zip::ZipWriter::new(out);
+    for item in items {
+        append_zip_item(&mut zip, item, level, job)?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+/// Compress one filesystem item into `zip`.
+pub(super) fn append_zip_item<W: Write + Seek>(
+    zip: &mut zip::ZipWriter<W>,
+    item: &SourceItem,
+    level: Option<u32>,
+    job: &JobHandle,
+) -> Result<()> {
+    job.check_cancelled()?;
+    job.begin_item(&item.name);
+
+    let options = zip_options(item.size, item.mtime, item.mode, level);
+    match &item.kind {
+        ItemKind::Directory => zip.add_directory(item.name.as_str(), options)?,
+        ItemKind::Symlink(target) => {
+            zip.add_symlink(item.name.as_str(), target.to_string_lossy(), options)?
+        }
+        ItemKind::File => {
+            zip.start_file(item.name.as_str(), options)?;
+            let mut reader = ProgressReader::new(File::open(&item.path)?, job);
+            io::copy(&mut reader, zip)?;
+        }
+    }
+    job.finish_item();
+    Ok(())
+}
+
+/// Options for one zip entry. Level 0 stores without compression.
+pub(super) fn zip_options(
+    size: u64,
+    mtime: Option<SystemTime>,
+    mode: Option<u32>,
+    level: Option<u32>,
+) -> zip::write::FileOptions {
+    let level = level.unwrap_or(6).min(9);
+    let (method, level) = if level == 0 {
+        (zip::CompressionMethod::Stored, None)
+    } else {
+        (zip::CompressionMethod::Deflated, Some(level as i32))
+    };
+    let mut options = zip::write::FileOptions::default()
+        .compression_method(method)
+        .compression_level(level)
+        .large_file(size >= u32::MAX as u64);
+    if let Some(mtime) = mtime.and_then(zip_datetime) {
+        options = options.last_modified_time(mtime);
+    }
+    if let Some(mode) = mode {
+        options = options.unix_permissions(mode);
+    }
+    options
+}
+
+pub(super) fn zip_datetime(time: SystemTime) -> Option<zip::DateTime> {
+    use chrono::{Datelike, Timelike};
+
+    // Listings read zip timestamps back as UTC, so store them the same way.
+    let dt = chrono::DateTime::<chrono::Utc>::from(time);
+    zip::DateTime::from_date_and_time(
+        u16::try_from(dt.year()).ok()?,
+        dt.month() as u8,
+        dt.day() as u8,
+        dt.hour() as u8,
+        dt.minute() as u8,
+        dt.second() as u8,
+    )
+    .ok()
+}
+
+fn write_tar<W: Write>(out: W, items: &[SourceItem], job: &JobHandle) -> Result<W> {
+    let mut builder = tar::Builder::new(out);
+    for item in items {
+        append_tar_item(&mut builder, item, job)?;
+    }
+    Ok(builder.into_inner()?)
+}
+
+/// Append one filesystem item to a tar stream.
+pub(super) fn append_tar_item<W: Write>(
+    builder: &mut tar::Builder<W>,
+    item: &SourceItem,
+    job: &JobHandle,
+) -> Result<()> {
+    job.check_cancelled()?;
+    job.begin_item(&item.name);
+
+    let mut header = tar::Header::new_gnu();
+    let mtime = item
+        .mtime
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    header.set_mtime(mtime);
+
+    match &item.kind {
+        ItemKind::Directory => {
+            header.set_entry_type(tar::EntryType::Directory);
+            header.set_mode(item.mode.unwrap_or(0o755));
+            header.set_size(0);
+            builder.append_data(&mut header, format!("{}/", item.name), io::empty())?;
+        }
+        ItemKind::Symlink(target) => {
+            header.set_entry_type(tar::EntryType::Symlink);
+            header.set_mode(item.mode.unwrap_or(0o777));
+            header.set_size(0);
+            builder.append_link(&mut header, &item.name, target)?;
+        }
+        ItemKind::File => {
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_mode(item.mode.unwrap_or(0o644));
+            header.set_size(item.size);
+            // Never write more than the header promises if the file grew meanwhile.
+            let file = File::open(&item.path)?.take(item.size);
+            builder.append_data(&mut header, &item.name, ProgressReader::new(file, job))?;
+        }
+    }
+    job.finish_item();
+    Ok(())
+}
+
+fn write_7z<W: Write + Seek>(
+    out: W,
+    items: &[SourceItem],
+    level: Option<u32>,
+    password: Option<&str>,
+    job: &JobHandle,
+) -> Result<()> {
+    use sevenz_rust::SevenZArchiveEntry;
+
+    let mut writer = seven_zip_writer(out, level, password)?;
+    for item in items {
+        job.check_cancelled()?;
+        job.begin_item(&item.name);
+
+        let entry = SevenZArchiveEntry::from_path(&item.path, item.name.clone());
+        match item.kind {
+            ItemKind::Directory => {
+                writer.push_archive_entry::<File>(entry, None)?;
+            }
+            _ => {
+                let reader = ProgressReader::new(File::open(&item.path)?, job);
+                writer.push_archive_entry(entry, Some(reader))?;
+            }
+        }
+        job.finish_item();
+    }
+
+    writer.finish()?;
+    Ok(())
+}
+
+/// A 7z writer compressing with LZMA2 at `level`, AES-encrypted when a
+/// password is given.
+pub(super) fn seven_zip_writer<W: Write + Seek>(
+    out: W,
+    level: Option<u32>,
+    password: Option<&str>,
+) -> Result<sevenz_rust::SevenZWriter<W>> {
+    use sevenz_rust::{lzma::LZMA2Options, AesEncoderOptions, SevenZWriter};
+
+    let mut writer = SevenZWriter::new(out)?;
+    let mut methods = vec![LZMA2Options::with_preset(level.unwrap_or(6).min(9)).into()];
+    if let Some(password) = password {
+        // Methods are listed outermost first: encrypt the compressed stream.
+        methods.insert(0, AesEncoderOptions::new(password.into()).into());
+    }
+    writer.set_content_methods(methods);
+    Ok(writer)
+}
@@ -0,0 +1,197 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use super::volumes::Source;
+use super::{package, ArchiveFormat};
+
+/// Offset of the first ISO 9660 / UDF volume descriptor identifier.
+const ISO_DESCRIPTOR_OFFSET: u64 = 0x8001;
+
+/// Zip-based containers that only differ from `.zip` by name.
+const ZIP_EXTENSIONS: &[&str] = &[
+    ".zip", ".jar", ".war", ".ear", ".apk", ".aar", ".ipa", ".xpi", ".crx", ".whl", ".nupkg",
+    ".vsix", ".epub", ".docx", ".xlsx", ".pptx", ".odt", ".ods", ".odp",
+];
+
+// =======================
+// Detection
+// =======================
+
+/// Identify the archive at `path` from its signature bytes. The extension is
+/// only consulted when the file cannot be read or has no known signature.
+pub(super) fn detect(path: &str) -> Option<ArchiveFormat> {
+    match sniff(path) {
+        Ok(Some(format)) => Some(format),
+        _ => from_extension(path),
+    }
+}
+
+/// Guess the format from the file name alone.
+pub(super) fn from_extension(path: &str) -> Option<ArchiveFormat> {
+    let lower = path.to_lowercase();
+    let has = |exts: &[&str]| exts.iter().any(|ext| lower.ends_with(ext));
+
+    // Compound extensions must be checked before their last component.
+    if has(ZIP_EXTENSIONS) {
+        Some(ArchiveFormat::Zip)
+    } else if has(&[".7z"]) {
+        Some(ArchiveFormat::SevenZip)
+    } else if has(&[".tar.gz", ".tgz"]) {
+        Some(ArchiveFormat::TarGz)
+    } else if has(&[".tar.bz2", ".tbz2", ".tbz"]) {
+        Some(ArchiveFormat::TarBz2)
+    } else if has(&[".tar.xz", ".txz"]) {
+        Some(ArchiveFormat::TarXz)
+    } else if has(&[".tar.zst", ".tar.zstd", ".tzst"]) {
+        Some(ArchiveFormat::TarZst)
+    } else if has(&[".tar"]) {
+        Some(ArchiveFormat::Tar)
+    } else if has(&[".gz"]) {
+        Some(ArchiveFormat::Gz)
+    } else if has(&[".bz2"]) {
+        Some(ArchiveFormat::Bz2)
+    } else if has(&[".xz"]) {
+        Some(ArchiveFormat::Xz)
+    } else if has(&[".zst", ".zstd"]) {
+        Some(ArchiveFormat::Zst)
+    } else if has(&[".cab"]) {
+        Some(ArchiveFormat::Cab)
+    } else if has(&[".rar"]) || is_old_rar_volume(&lower) {
+        Some(ArchiveFormat::Rar)
+    } else if has(&[".iso"]) {
+        Some(ArchiveFormat::Iso)
+    } else if has(&[".deb", ".udeb"]) {
+        Some(ArchiveFormat::Deb)
+    } else if has(&[".rpm"]) {
+        Some(ArchiveFormat::Rpm)
+    } else if has(&[".a", ".ar"]) {
+        Some(ArchiveFormat::Ar)
+    } else if has(&[".cpio"]) {
+        Some(ArchiveFormat::Cpio)
+    } else {
+        None
+    }
+}
+
+/// Continuation volumes of old-style RAR sets: `.r00`, `.r01`, ...
+pub(super) fn is_old_rar_volume(lower: &str) -> bool {
+    let ext = lower.rsplit('.').next().unwrap_or_default();
+    ext.len() == 3 && ext.starts_with('r') && ext[1..].bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Match the leading bytes of the file against known signatures.
+fn sniff(path: &str) -> std::io::Result<Option<ArchiveFormat>> {
+    let mut file = Source::open(path)?;
+    let mut head = Vec::with_capacity(512);
+    (&mut file).take(512).read_to_end(&mut head)?;
+    let starts = |magic: &[u8]| head.starts_with(magic);
+
+    let format = if starts(b"PK\x03\x04") || starts(b"PK\x05\x06") || starts(b"PK\x07\x08") {
+        Some(ArchiveFormat::Zip)
+    } else if starts(b"Cr24") {
+        // Chrome extensions: a signed header followed by a plain zip
+        Some(ArchiveFormat::Zip)
+    } else if starts(&[0x37, 0x7A, 0xBC, 0xAF, 0x27, 0x1C]) {
+        Some(ArchiveFormat::SevenZip)
+    } else if starts(b"Rar!\x1A\x07") {
+        Some(ArchiveFormat::Rar)
+    } else if starts(b"MSCF") {
+        Some(ArchiveFormat::Cab)
+    } else if package::is_rpm(&head) {
+        Some(ArchiveFormat::Rpm)
+    } else if package::is_ar(&head) {
+        // Debian packages are ar archives whose first member is `debian-binary`.
+        if head.get(8..21) == Some(&b"debian-binary"[..]) {
+            Some(ArchiveFormat::Deb)
+        } else {
+            Some(ArchiveFormat::Ar)
+        }
+    } else if package::is_cpio(&head) {
+        Some(ArchiveFormat::Cpio)
+    } else if let Some(single) = stream_format(&head) {
+        Some(compressed(path, single))
+    } else if is_tar_header(&head) {
+        Some(ArchiveFormat::Tar)
+    } else if is_iso(&mut file)? {
+        Some(ArchiveFormat::Iso)
+    } else {
+        None
+    };
+    Ok(format)
+}
+
+/// The single-file format for a gzip, bzip2, xz or zstd stream signature.
+pub(super) fn stream_format(head: &[u8]) -> Option<ArchiveFormat> {
+    let starts = |magic: &[u8]| head.starts_with(magic);
+    if starts(&[0x1F, 0x8B]) {
+        Some(ArchiveFormat::Gz)
+    } else if starts(b"BZh") {
+        Some(ArchiveFormat::Bz2)
+    } else if starts(&[0xFD, b'7', b'z', b'X', b'Z', 0x00]) {
+        Some(ArchiveFormat::Xz)
+    } else if starts(&[0x28, 0xB5, 0x2F, 0xFD]) {
+        Some(ArchiveFormat::Zst)
+    } else {
+        None
+    }
+}
+
+/// A compressed stream is a tarball or cpio archive (initramfs images) when
+/// its first decompressed block says so, and a single compressed file
+/// otherwise.
+fn compressed(path: &str, single: ArchiveFormat) -> ArchiveFormat {
+    let block = Source::open(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|file| single.decompress(file))
+        .and_then(|reader| {
+            let mut block = Vec::with_capacity(512);
+            reader.take(512).read_to_end(&mut block)?;
+            Ok(block)
+        });
+    match block {
+        Ok(block) if is_tar_header(&block) => match single {
+            ArchiveFormat::Gz => ArchiveFormat::TarGz,
+            ArchiveFormat::Bz2 => ArchiveFormat::TarBz2,
+            ArchiveFormat::Xz => ArchiveFormat::TarXz,
+            ArchiveFormat::Zst => ArchiveFormat::TarZst,
+            other => other,
+        },
+        Ok(block) if package::is_cpio(&block) => ArchiveFormat::Cpio,
+        _ => single,
+    }
+}
+
+/// POSIX tar headers carry a `ustar` magic; old v7 headers are recognized by
+/// their checksum instead.
+fn is_tar_header(block: &[u8]) -> bool {
+    if block.len() < 512 || block[0] == 0 {
+        return false;
+    }
+    if &block[257..262] == b"ustar" {
+        return true;
+    }
+
+    let field = String::from_utf8_lossy(&block[148..156]);
+    let Ok(stored) = u32::from_str_radix(field.trim_matches(|c: char| c == ' ' || c == '\0'), 8)
+    else {
+        return false;
+    };
+    // The checksum is computed with its own field filled with spaces.
+    let sum: u32 = block[..512]
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| if (148..156).contains(&i) { b' ' as u32 } else { b as u32 })
+        .sum();
+    sum == stored
+}
+
+/// ISO 9660 volumes (`CD001`) and UDF volumes (`BEA01` recognition sequence)
+/// both start their descriptors 32 KiB into the image.
+fn is_iso(file: &mut Source) -> std::io::Result<bool> {
+    if file.size()? < ISO_DESCRIPTOR_OFFSET + 5 {
+        return Ok(false);
+    }
+    file.seek(SeekFrom::Start(ISO_DESCRIPTOR_OFFSET))?;
+    let mut id = [0u8; 5];
+    file.read_exact(&mut id)?;
+    Ok(&id == b"CD001" || &id == b"BEA01")
+}
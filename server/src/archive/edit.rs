@@ -0,0 +1,451 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashSet;
+use std::fs::File;
+use std::path::Path;
+
+use super::create::{append_tar_item, append_zip_item, collect_items, SourceItem};
+use super::{normalize_inner, ArchiveFormat, ArchiveOptions, TarBackend, ZipBackend};
+use crate::jobs::JobHandle;
+use crate::protocol::CreateArchiveOptions;
+
+// =======================
+// Edit plan
+// =======================
+
+/// What to change while rewriting an archive. Existing entries are mapped
+/// through the plan one by one; new items are appended at the end.
+#[derive(Default)]
+struct EditPlan {
+    /// Entries (and everything below them) to drop.
+    delete: Vec<String>,
+    /// Entry or directory prefix to rename, and its new path.
+    rename: Option<(String, String)>,
+    /// Names of added items; older entries with the same name are replaced.
+    replace: HashSet<String>,
+    /// Set once any existing entry was deleted or renamed.
+    matched: bool,
+}
+
+impl EditPlan {
+    /// New name for an existing entry, or `None` to drop it. A trailing `/`
+    /// on directory entries is preserved.
+    fn map(&mut self, name: &str) -> Option<String> {
+        let normalized = name.replace('\\', "/");
+        let trimmed = normalized.trim_end_matches('/');
+        let under = |prefix: &str| trimmed == prefix || trimmed.starts_with(&format!("{}/", prefix));
+
+        if self.delete.iter().any(|d| under(d)) {
+            self.matched = true;
+            return None;
+        }
+        if self.replace.contains(trimmed) {
+            return None;
+        }
+        if let Some((from, to)) = &self.rename {
+            if under(from) {
+                self.matched = true;
+                return Some(format!("{}{}", to, &normalized[from.len()..]));
+            }
+        }
+        Some(normalized)
+    }
+}
+
+// =======================
+// Public operations
+// =======================
+
+/// Add filesystem items under `inner_dir` inside the archive, replacing
+/// entries that already exist with the same name.
+pub fn add_to_archive(
+    path: &str,
+    inner_dir: &str,
+    sources: &[String],
+    options: &CreateArchiveOptions,
+    job: &JobHandle,
+) -> Result<Vec<String>> {
+    let format = editable_format(path)?;
+    if options.password.is_some() {
+        anyhow::bail!("Encrypted entries can only be written to 7z archives");
+    }
+    let inner_dir = normalize_inner(inner_dir);
+    reject_nested(&inner_dir)?;
+    let store_symlinks = options.store_symlinks;
+
+    let mut items = collect_items(sources, options, store_symlinks)?;
+    for item in &mut items {
+        if !inner_dir.is_empty() {
+            item.name = format!("{}/{}", inner_dir, item.name);
+        }
+    }
+    job.set_totals(items.len() as u64, items.iter().map(|i| i.size).sum());
+
+    let mut plan = EditPlan {
+        replace: items.iter().map(|i| i.name.clone()).collect(),
+        ..Default::default()
+    };
+    rewrite(path, format, &mut plan, &items, options.compression_level, job)?;
+    Ok(items.into_iter().map(|i| i.name).collect())
+}
+
+/// Remove entries (directories recursively) from the archive.
+pub fn delete_from_archive(path: &str, inner_paths: &[String], job: &JobHandle) -> Result<Vec<String>> {
+    let format = editable_format(path)?;
+    let delete: Vec<String> = inner_paths.iter().map(|p| normalize_inner(p)).collect();
+    if delete.iter().any(|p| p.is_empty()) {
+        anyhow::bail!("Refusing to delete the archive root");
+    }
+    delete.iter().try_for_each(|p| reject_nested(p))?;
+
+    let mut plan = EditPlan {
+        delete: delete.clone(),
+        ..Default::default()
+    };
+    rewrite(path, format, &mut plan, &[], None, job)?;
+    if !plan.matched {
+        return Err(anyhow!("No matching entries in archive"));
+    }
+    Ok(delete)
+}
+
+/// Rename or move an entry (a directory renames everything below it).
+pub fn rename_in_archive(path: &str, from: &str, to: &str, job: &JobHandle) -> Result<Vec<String>> {
+    let format = editable_format(path)?;
+    let from = normalize_inner(from);
+    let to = normalize_inner(to);
+    if from.is_empty() || to.is_empty() {
+        anyhow::bail!("Both the old and the new inner path are required");
+    }
+    reject_nested(&from)?;
+    reject_nested(&to)?;
+    if to.starts_with(&format!("{}/", from)) {
+        anyhow::bail!("Cannot move '{}' into itself", from);
+    }
+
+    let mut plan = EditPlan {
+        rename: Some((from.clone(), to.clone())),
+        ..Default::default()
+    };
+    rewrite(path, format, &mut plan, &[], None, job)?;
+    if !plan.matched {
+        return Err(anyhow!("Entry '{}' not found", from));
+    }
+    Ok(vec![to])
+}
+
+/// Edits rewrite the outer archive only; an inner path that reaches into
+/// a nested archive (`lib/inner.jar!/x`) would add a literal `inner.jar!`
+/// directory to it instead.
+fn reject_nested(inner: &str) -> Result<()> {
+    if inner.contains("!/") || inner.ends_with('!') {
+        anyhow::bail!("Cannot edit '{}': it is inside a nested archive", inner);
+    }
+    Ok(())
+}
+
+fn editable_format(path: &str) -> Result<ArchiveFormat> {
+    if super::volumes::find(path)?.is_some() {
+        return Err(anyhow!("Split archives cannot be edited; join the volumes first"));
+    }
+    match ArchiveFormat::detect(path) {
+        Some(
+            format @ (ArchiveFormat::Zip
+            | ArchiveFormat::Tar
+            | ArchiveFormat::TarGz
+            | ArchiveFormat::TarBz2
+            | ArchiveFormat::TarXz
+            | ArchiveFormat::TarZst),
+        ) => Ok(format),
+        _ => Err(anyhow!("Editing is only supported for zip and tar archives")),
+    }
+}
+
+// =======================
+// Rewriting
+// =======================
+
+/// Rewrite `path` into a temp file next to it, applying `plan` and appending
+/// `added`, then atomically replace the original.
+fn rewrite(
+    path: &str,
+    format: ArchiveFormat,
+    plan: &mut EditPlan,
+    added: &[SourceItem],
+    level: Option<u32>,
+    job: &JobHandle,
+) -> Result<()> {
+    let original = Path::new(path);
+    let dir = match original.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let permissions = std::fs::metadata(original)?.permissions();
+    let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+
+    match format {
+        ArchiveFormat::Zip => rewrite_zip(path, tmp.as_file_mut(), plan, added, level, job)?,
+        _ => rewrite_tar(path, format, tmp.as_file_mut(), plan, added, level, job)?,
+    }
+
+    tmp.as_file().set_permissions(permissions)?;
+    tmp.persist(original)
+        .with_context(|| format!("Failed to replace {}", path))?;
+    Ok(())
+}
+
+/// Untouched and renamed entries are copied raw, keeping their compressed
+/// bytes; only added files are compressed.
+fn rewrite_zip(
+    path: &str,
+    out: &mut File,
+    plan: &mut EditPlan,
+    added: &[SourceItem],
+    level: Option<u32>,
+    job: &JobHandle,
+) -> Result<()> {
+    let mut source = zip::ZipArchive::new(super::Source::open(path)?)?;
+    let (names, _) = ZipBackend::entry_names(&mut source, path, &ArchiveOptions::default())?;
+    let mut zip = zip::ZipWriter::new(out);
+    zip.set_raw_comment(source.comment().to_vec());
+
+    // Names are always written back decoded: the zip writer stores them as
+    // UTF-8 and sets the flag, so legacy names come out readable.
+    for (i, name) in names.iter().enumerate() {
+        job.check_cancelled()?;
+        let entry = source.by_index_raw(i)?;
+        match plan.map(name) {
+            None => continue,
+            Some(new_name) => zip.raw_copy_file_rename(entry, new_name)?,
+        }
+    }
+
+    for item in added {
+        append_zip_item(&mut zip, item, level, job)?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+/// Tar entries carry no compression of their own, so their data is copied
+/// as is; only the outer stream is compressed again. Headers are rebuilt
+/// from each entry's resolved metadata: GNU sparse entries are written out
+/// expanded as regular files, and PAX records other than the names the
+/// builder writes itself are carried over.
+fn rewrite_tar(
+    path: &str,
+    format: ArchiveFormat,
+    out: &mut File,
+    plan: &mut EditPlan,
+    added: &[SourceItem],
+    level: Option<u32>,
+    job: &JobHandle,
+) -> Result<()> {
+    let mut source = TarBackend::open(path)?;
+    let mut builder = tar::Builder::new(format.compress(out, level)?);
+
+    for entry in source.entries()? {
+        job.check_cancelled()?;
+        let mut entry = entry?;
+        let name = TarBackend::entry_path(&entry);
+        let entry_type = entry.header().entry_type();
+        // Global records apply to every entry after them; keep them in place.
+        if entry_type.is_pax_global_extensions() {
+            let header = entry.header().clone();
+            builder.append(&header, &mut entry)?;
+            continue;
+        }
+        if entry_type.is_gnu_longname() || entry_type.is_gnu_longlink() || name.is_empty() {
+            continue;
+        }
+        let Some(new_name) = plan.map(&name) else {
+            continue;
+        };
+
+        let records = pax_records(&mut entry, &name)?;
+        builder.append_pax_extensions(records.iter().map(|(key, value)| (key.as_str(), value.as_slice())))?;
+        let mut header = fresh_header(&entry)?;
+        if entry_type.is_symlink() || entry_type.is_hard_link() {
+            let target = entry
+                .link_name()?
+                .ok_or_else(|| anyhow!("Link '{}' has no target", name))?
+                .into_owned();
+            builder.append_link(&mut header, &new_name, target)?;
+        } else if entry_type.is_dir() {
+            builder.append_data(&mut header, format!("{}/", new_name), std::io::empty())?;
+        } else {
+            builder.append_data(&mut header, &new_name, &mut entry)?;
+        }
+    }
+
+    for item in added {
+        append_tar_item(&mut builder, item, job)?;
+    }
+    builder.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// The entry's PAX records, minus the names the builder writes for the new
+/// path. PAX sparse files keep their map in the data, which is not
+/// expanded on reading, so they cannot be rewritten.
+fn pax_records<R: std::io::Read>(entry: &mut tar::Entry<R>, name: &str) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut records = Vec::new();
+    let Some(extensions) = entry.pax_extensions()? else {
+        return Ok(records);
+    };
+    for extension in extensions {
+        let extension = extension?;
+        let key = extension.key().map_err(|_| anyhow!("Entry '{}' has a malformed PAX record", name))?;
+        if key.starts_with("GNU.sparse.") {
+            anyhow::bail!("Cannot rewrite '{}': PAX sparse files are not supported", name);
+        }
+        if key != "path" && key != "linkpath" {
+            records.push((key.to_string(), extension.value_bytes().to_vec()));
+        }
+    }
+    Ok(records)
+}
+
+/// A GNU header with the entry's metadata and its real (expanded) size.
+fn fresh_header<R: std::io::Read>(entry: &tar::Entry<R>) -> Result<tar::Header> {
+    let source = entry.header();
+    let mut header = tar::Header::new_gnu();
+    let entry_type = match source.entry_type() {
+        tar::EntryType::GNUSparse => tar::EntryType::Regular,
+        entry_type => entry_type,
+    };
+    header.set_entry_type(entry_type);
+    let linked = entry_type.is_symlink() || entry_type.is_hard_link();
+    header.set_size(if linked || entry_type.is_dir() { 0 } else { entry.size() });
+    // Some writers leave numeric fields blank; copying never failed on them.
+    header.set_mode(source.mode().unwrap_or(0o644));
+    header.set_uid(source.uid().unwrap_or(0));
+    header.set_gid(source.gid().unwrap_or(0));
+    header.set_mtime(source.mtime().unwrap_or(0));
+    if let Ok(Some(user)) = source.username() {
+        header.set_username(user)?;
+    }
+    if let Ok(Some(group)) = source.groupname() {
+        header.set_groupname(group)?;
+    }
+    if entry_type.is_character_special() || entry_type.is_block_special() {
+        header.set_device_major(source.device_major()?.unwrap_or(0))?;
+        header.set_device_minor(source.device_minor()?.unwrap_or(0))?;
+    }
+    Ok(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    const HOLE: u64 = 100_000;
+
+    fn octal(field: &mut [u8], value: u64) {
+        let text = format!("{:0width$o}", value, width = field.len() - 1);
+        field[..text.len()].copy_from_slice(text.as_bytes());
+        field[text.len()] = 0;
+    }
+
+    /// `sparse.bin` (a hole, then `hello`), `other.txt`, and `pax.txt`
+    /// whose metadata lives in PAX records.
+    fn write_archive(path: &Path) {
+        let mut builder = tar::Builder::new(File::create(path).unwrap());
+
+        let mut header = tar::Header::new_gnu();
+        header.set_path("sparse.bin").unwrap();
+        header.set_entry_type(tar::EntryType::GNUSparse);
+        header.set_mode(0o644);
+        header.set_size(5);
+        let gnu = header.as_gnu_mut().unwrap();
+        octal(&mut gnu.sparse[0].offset, HOLE);
+        octal(&mut gnu.sparse[0].numbytes, 5);
+        octal(&mut gnu.realsize, HOLE + 5);
+        header.set_cksum();
+        builder.append(&header, &b"hello"[..]).unwrap();
+
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        builder.append_data(&mut header, "other.txt", &b"other"[..]).unwrap();
+
+        builder
+            .append_pax_extensions([
+                ("path", &b"pax.txt"[..]),
+                ("mtime", b"1700000000.25"),
+                ("uname", "a-user-name-longer-than-thirty-two-bytes".as_bytes()),
+                ("SCHILY.xattr.user.note", b"kept"),
+            ])
+            .unwrap();
+        let mut header = tar::Header::new_ustar();
+        header.set_mtime(1_700_000_000);
+        header.set_size(3);
+        builder.append_data(&mut header, "pax.txt", &b"pax"[..]).unwrap();
+        builder.into_inner().unwrap();
+    }
+
+    #[test]
+    fn rewrites_sparse_and_pax_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.tar");
+        write_archive(&path);
+        let path = path.to_str().unwrap();
+        let job = JobHandle::detached("test");
+        delete_from_archive(path, &["other.txt".to_string()], &job).unwrap();
+        rename_in_archive(path, "pax.txt", "renamed.txt", &job).unwrap();
+
+        let mut archive = tar::Archive::new(File::open(path).unwrap());
+        let mut entries = archive.entries().unwrap();
+
+        let mut sparse = entries.next().unwrap().unwrap();
+        assert_eq!(sparse.path().unwrap().to_str(), Some("sparse.bin"));
+        assert_eq!(sparse.header().entry_type(), tar::EntryType::Regular);
+        let mut data = Vec::new();
+        sparse.read_to_end(&mut data).unwrap();
+        assert_eq!(data.len() as u64, HOLE + 5);
+        assert!(data[..HOLE as usize].iter().all(|&b| b == 0));
+        assert_eq!(&data[HOLE as usize..], b"hello");
+
+        let mut pax = entries.next().unwrap().unwrap();
+        assert_eq!(pax.path().unwrap().to_str(), Some("renamed.txt"));
+        let records: Vec<(String, Vec<u8>)> = pax
+            .pax_extensions()
+            .unwrap()
+            .unwrap()
+            .map(|e| {
+                let e = e.unwrap();
+                (e.key().unwrap().to_string(), e.value_bytes().to_vec())
+            })
+            .collect();
+        assert_eq!(
+            records,
+            [
+                ("mtime".to_string(), b"1700000000.25".to_vec()),
+                ("uname".to_string(), b"a-user-name-longer-than-thirty-two-bytes".to_vec()),
+                ("SCHILY.xattr.user.note".to_string(), b"kept".to_vec()),
+            ]
+        );
+        let mut data = String::new();
+        pax.read_to_string(&mut data).unwrap();
+        assert_eq!(data, "pax");
+        assert!(entries.next().is_none());
+    }
+
+    #[test]
+    fn rejects_pax_sparse_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.tar");
+        let mut builder = tar::Builder::new(File::create(&path).unwrap());
+        builder.append_pax_extensions([("GNU.sparse.major", &b"1"[..])]).unwrap();
+        let mut header = tar::Header::new_ustar();
+        header.set_size(0);
+        builder.append_data(&mut header, "sparse.bin", std::io::empty()).unwrap();
+        let mut header = tar::Header::new_ustar();
+        header.set_size(0);
+        builder.append_data(&mut header, "other.txt", std::io::empty()).unwrap();
+        builder.into_inner().unwrap();
+
+        let job = JobHandle::detached("test");
+        let error = delete_from_archive(path.to_str().unwrap(), &["other.txt".to_string()], &job).unwrap_err();
+        assert!(error.to_string().contains("PAX sparse"), "{error}");
+    }
+}
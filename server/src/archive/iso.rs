@@ -0,0 +1,434 @@
+use anyhow::{anyhow, Result};
+use std::collections::HashSet;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use super::volumes::Source;
+
+const SECTOR: u64 = 2048;
+/// Volume descriptors start at sector 16, after the system area.
+const FIRST_DESCRIPTOR: u64 = 16;
+/// Upper bound on descriptors scanned before giving up on a terminator.
+const MAX_DESCRIPTORS: u64 = 64;
+/// Directories nested deeper than this are ignored (guards cyclic images).
+const MAX_DEPTH: usize = 64;
+/// Directory extents larger than this are treated as corrupt.
+const MAX_DIR_BYTES: u64 = 16 * 1024 * 1024;
+
+// =======================
+// Image
+// =======================
+
+/// A file or directory in the image, with its full path.
+pub(super) struct IsoEntry {
+    pub path: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: i64,
+    /// Unix mode from Rock Ridge, when present.
+    pub mode: Option<u32>,
+    /// Rock Ridge symlink target.
+    pub symlink: Option<String>,
+    /// (start sector, byte length) of each extent; large files have several.
+    extents: Vec<(u32, u64)>,
+}
+
+/// An ISO 9660 image with its directory tree read into memory. Names come
+/// from Rock Ridge when present, else Joliet, else the plain 8.3 names.
+pub(super) struct IsoImage {
+    file: Source,
+    pub volume_label: Option<String>,
+    pub created: Option<i64>,
+    pub entries: Vec<IsoEntry>,
+}
+
+/// The fields of a directory record the reader needs.
+struct DirRecord<'a> {
+    extent: u32,
+    size: u64,
+    modified: i64,
+    flags: u8,
+    name: &'a [u8],
+    system_use: &'a [u8],
+}
+
+const FLAG_DIRECTORY: u8 = 0x02;
+const FLAG_MULTI_EXTENT: u8 = 0x80;
+
+impl<'a> DirRecord<'a> {
+    fn parse(rec: &'a [u8]) -> Option<Self> {
+        if rec.len() < 34 {
+            return None;
+        }
+        let name_len = rec[32] as usize;
+        let name = rec.get(33..33 + name_len)?;
+        // A padding byte keeps the system use area at an even offset.
+        let su_start = 33 + name_len + (name_len + 1) % 2;
+        Some(Self {
+            extent: u32::from_le_bytes(rec[2..6].try_into().ok()?),
+            size: u32::from_le_bytes(rec[10..14].try_into().ok()?) as u64,
+            modified: record_datetime(&rec[18..25]).unwrap_or(0),
+            flags: rec[25],
+            name,
+            system_use: rec.get(su_start..).unwrap_or_default(),
+        })
+    }
+
+    /// `.` and `..` are stored as the single bytes 0 and 1.
+    fn is_self_or_parent(&self) -> bool {
+        self.name == [0] || self.name == [1]
+    }
+}
+
+/// How names are decoded for the tree being walked.
+#[derive(Clone, Copy, PartialEq)]
+enum Naming {
+    Plain,
+    Joliet,
+    /// Rock Ridge, with the SUSP skip length from the root's `SP` entry.
+    RockRidge(usize),
+}
+
+impl IsoImage {
+    /// Read the volume descriptors and the directory tree. `Ok(None)` when
+    /// the image has no ISO 9660 descriptors, as in a pure UDF image.
+    pub fn open(path: &str) -> Result<Option<Self>> {
+        let mut file = Source::open(path)?;
+        let mut primary = None;
+        let mut joliet = None;
+
+        for n in FIRST_DESCRIPTOR..FIRST_DESCRIPTOR + MAX_DESCRIPTORS {
+            let Some(sector) = read_sector(&mut file, n)? else {
+                break;
+            };
+            if &sector[1..6] != b"CD001" {
+                break;
+            }
+            match sector[0] {
+                1 if primary.is_none() => primary = Some(sector),
+                2 if is_joliet(&sector) => joliet = Some(sector),
+                255 => break,
+                _ => {}
+            }
+        }
+        let Some(primary) = primary else {
+            return Ok(None);
+        };
+
+        let volume_label = joliet
+            .as_ref()
+            .map(|svd| utf16_name(&svd[40..72]))
+            .filter(|label| !label.is_empty())
+            .or_else(|| Some(String::from_utf8_lossy(&primary[40..72]).trim().to_string()))
+            .filter(|label| !label.is_empty());
+
+        let mut image = Self {
+            file,
+            volume_label,
+            created: descriptor_datetime(&primary[813..830]),
+            entries: Vec::new(),
+        };
+
+        let root = DirRecord::parse(&primary[156..190])
+            .ok_or_else(|| anyhow!("Corrupt ISO 9660 root directory record"))?;
+        let naming = match image.rock_ridge_skip(&root)? {
+            Some(skip) => Naming::RockRidge(skip),
+            None if joliet.is_some() => Naming::Joliet,
+            None => Naming::Plain,
+        };
+        let (extent, size) = match (&naming, &joliet) {
+            (Naming::Joliet, Some(svd)) => {
+                let root = DirRecord::parse(&svd[156..190])
+                    .ok_or_else(|| anyhow!("Corrupt Joliet root directory record"))?;
+                (root.extent, root.size)
+            }
+            _ => (root.extent, root.size),
+        };
+        image.walk(extent, size, naming)?;
+        Ok(Some(image))
+    }
+
+    /// Stream an entry's data into `out`.
+    pub fn copy_entry(&mut self, index: usize, out: &mut dyn Write) -> Result<u64> {
+        let extents = self.entries[index].extents.clone();
+        let mut written = 0;
+        for (sector, len) in extents {
+            self.file.seek(SeekFrom::Start(sector as u64 * SECTOR))?;
+            written += std::io::copy(&mut (&mut self.file).take(len), out)?;
+        }
+        Ok(written)
+    }
+
+    /// Rock Ridge images announce themselves with an `SP` entry in the
+    /// system use area of the root directory's `.` record.
+    fn rock_ridge_skip(&mut self, root: &DirRecord) -> Result<Option<usize>> {
+        let data = self.read_extent(root.extent, root.size.min(SECTOR))?;
+        let Some(dot) = data.first().and_then(|&len| data.get(..len as usize)) else {
+            return Ok(None);
+        };
+        let Some(dot) = DirRecord::parse(dot) else {
+            return Ok(None);
+        };
+        let area = dot.system_use;
+        let is_sp = area.len() >= 7 && &area[0..2] == b"SP" && area[4] == 0xBE && area[5] == 0xEF;
+        Ok(is_sp.then(|| area[6] as usize))
+    }
+
+    fn read_extent(&mut self, sector: u32, size: u64) -> Result<Vec<u8>> {
+        if size > MAX_DIR_BYTES {
+            return Err(anyhow!("Directory extent at sector {} is too large", sector));
+        }
+        self.file.seek(SeekFrom::Start(sector as u64 * SECTOR))?;
+        let mut data = Vec::with_capacity(size as usize);
+        (&mut self.file).take(size).read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    /// Read every directory breadth-first from the root extent.
+    fn walk(&mut self, root_extent: u32, root_size: u64, naming: Naming) -> Result<()> {
+        let mut visited = HashSet::from([root_extent]);
+        let mut pending = vec![(root_extent, root_size, String::new(), 0usize)];
+
+        while let Some((extent, size, prefix, depth)) = pending.pop() {
+            let data = self.read_extent(extent, size)?;
+            // Index of a multi-extent file still waiting for its next part
+            let mut continuing: Option<usize> = None;
+            let mut pos = 0;
+
+            while pos < data.len() {
+                let len = data[pos] as usize;
+                if len == 0 {
+                    // Records never cross sectors; the rest of this one is padding.
+                    pos = (pos / SECTOR as usize + 1) * SECTOR as usize;
+                    continue;
+                }
+                let Some(record) = data.get(pos..pos + len).and_then(DirRecord::parse) else {
+                    break;
+                };
+                pos += len;
+                if record.is_self_or_parent() {
+                    continue;
+                }
+
+                let mut rr = RockRidge::default();
+                let name = match naming {
+                    Naming::RockRidge(skip) => {
+                        let area = record.system_use.get(skip..).unwrap_or_default();
+                        self.parse_susp(area, &mut rr, 0)?;
+                        if rr.relocated {
+                            // Shown where its `CL` link points instead.
+                            continue;
+                        }
+                        rr.name.take().unwrap_or_else(|| plain_name(record.name))
+                    }
+                    Naming::Joliet => utf16_name(record.name),
+                    Naming::Plain => plain_name(record.name),
+                };
+                if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+                    continue;
+                }
+                let path = if prefix.is_empty() {
+                    name
+                } else {
+                    format!("{}/{}", prefix, name)
+                };
+
+                if let Some(index) = continuing.filter(|&i| self.entries[i].path == path) {
+                    let entry = &mut self.entries[index];
+                    entry.size += record.size;
+                    entry.extents.push((record.extent, record.size));
+                    if record.flags & FLAG_MULTI_EXTENT == 0 {
+                        continuing = None;
+                    }
+                    continue;
+                }
+
+                // A Rock Ridge `CL` entry is a file record standing in for a
+                // directory that was relocated to keep the tree shallow.
+                let (is_dir, extent, dir_size) = match rr.child_link {
+                    Some(target) => (true, target, self.dot_size(target)?),
+                    None => (record.flags & FLAG_DIRECTORY != 0, record.extent, record.size),
+                };
+                if is_dir && depth < MAX_DEPTH && visited.insert(extent) {
+                    pending.push((extent, dir_size, path.clone(), depth + 1));
+                }
+
+                self.entries.push(IsoEntry {
+                    path,
+                    is_dir,
+                    size: if is_dir || rr.symlink.is_some() { 0 } else { record.size },
+                    modified: rr.modified.unwrap_or(record.modified),
+                    mode: rr.mode,
+                    symlink: rr.symlink,
+                    extents: if is_dir { Vec::new() } else { vec![(record.extent, record.size)] },
+                });
+                continuing = (record.flags & FLAG_MULTI_EXTENT != 0 && !is_dir)
+                    .then(|| self.entries.len() - 1);
+            }
+        }
+        Ok(())
+    }
+
+    /// Size of a directory, read from the `.` record at its start.
+    fn dot_size(&mut self, extent: u32) -> Result<u64> {
+        let data = self.read_extent(extent, SECTOR)?;
+        let dot = data
+            .first()
+            .and_then(|&len| data.get(..len as usize))
+            .and_then(DirRecord::parse)
+            .ok_or_else(|| anyhow!("Corrupt relocated directory at sector {}", extent))?;
+        Ok(dot.size)
+    }
+
+    /// Parse a System Use Sharing Protocol area, following `CE` continuations.
+    fn parse_susp(&mut self, area: &[u8], rr: &mut RockRidge, depth: usize) -> Result<()> {
+        let mut pos = 0;
+        while pos + 4 <= area.len() {
+            let len = area[pos + 2] as usize;
+            if len < 4 || pos + len > area.len() {
+                break;
+            }
+            let data = &area[pos + 4..pos + len];
+            match &area[pos..pos + 2] {
+                // Flags 0x02/0x04 mark `.` and `..`, which carry no name.
+                b"NM" if !data.is_empty() && data[0] & 0x06 == 0 => {
+                    rr.name
+                        .get_or_insert_with(String::new)
+                        .push_str(&String::from_utf8_lossy(&data[1..]));
+                }
+                b"PX" if data.len() >= 4 => {
+                    rr.mode = Some(u32::from_le_bytes(data[0..4].try_into()?) & 0o7777);
+                }
+                b"SL" if !data.is_empty() => rr.push_symlink(&data[1..]),
+                b"TF" if !data.is_empty() => rr.modified = tf_modified(data).or(rr.modified),
+                b"CL" if data.len() >= 4 => {
+                    rr.child_link = Some(u32::from_le_bytes(data[0..4].try_into()?));
+                }
+                b"RE" => rr.relocated = true,
+                b"CE" if data.len() >= 20 && depth < 8 => {
+                    let block = u32::from_le_bytes(data[0..4].try_into()?);
+                    let offset = u32::from_le_bytes(data[8..12].try_into()?) as u64;
+                    let length = u32::from_le_bytes(data[16..20].try_into()?) as u64;
+                    self.file
+                        .seek(SeekFrom::Start(block as u64 * SECTOR + offset))?;
+                    let mut more = Vec::new();
+                    (&mut self.file).take(length.min(SECTOR)).read_to_end(&mut more)?;
+                    self.parse_susp(&more, rr, depth + 1)?;
+                }
+                b"ST" => break,
+                _ => {}
+            }
+            pos += len;
+        }
+        Ok(())
+    }
+}
+
+// =======================
+// Rock Ridge
+// =======================
+
+#[derive(Default)]
+struct RockRidge {
+    name: Option<String>,
+    mode: Option<u32>,
+    symlink: Option<String>,
+    modified: Option<i64>,
+    child_link: Option<u32>,
+    relocated: bool,
+    /// The last symlink component continues in the next `SL` entry.
+    continues: bool,
+}
+
+impl RockRidge {
+    /// Append the components of one `SL` entry to the symlink target.
+    fn push_symlink(&mut self, mut components: &[u8]) {
+        let target = self.symlink.get_or_insert_with(String::new);
+        while components.len() >= 2 {
+            let flags = components[0];
+            let len = components[1] as usize;
+            let Some(content) = components.get(2..2 + len) else {
+                break;
+            };
+            if !self.continues && !target.is_empty() && !target.ends_with('/') {
+                target.push('/');
+            }
+            match flags {
+                f if f & 0x02 != 0 => target.push('.'),
+                f if f & 0x04 != 0 => target.push_str(".."),
+                f if f & 0x08 != 0 => target.push('/'),
+                _ => target.push_str(&String::from_utf8_lossy(content)),
+            }
+            self.continues = flags & 0x01 != 0;
+            components = &components[2 + len..];
+        }
+    }
+}
+
+/// Modification time from a `TF` entry. Timestamps appear in flag-bit order
+/// (creation, modify, access, ...), as 7-byte or 17-byte values.
+fn tf_modified(data: &[u8]) -> Option<i64> {
+    let flags = data[0];
+    if flags & 0x02 == 0 {
+        return None;
+    }
+    let long = flags & 0x80 != 0;
+    let width = if long { 17 } else { 7 };
+    let start = 1 + (flags & 0x01) as usize * width;
+    let stamp = data.get(start..start + width)?;
+    if long {
+        descriptor_datetime(stamp)
+    } else {
+        record_datetime(stamp)
+    }
+}
+
+// =======================
+// Helpers
+// =======================
+
+fn read_sector(file: &mut Source, n: u64) -> Result<Option<Vec<u8>>> {
+    file.seek(SeekFrom::Start(n * SECTOR))?;
+    let mut sector = Vec::with_capacity(SECTOR as usize);
+    file.take(SECTOR).read_to_end(&mut sector)?;
+    Ok((sector.len() == SECTOR as usize).then_some(sector))
+}
+
+/// Supplementary descriptors with a UCS-2 escape sequence are Joliet.
+fn is_joliet(svd: &[u8]) -> bool {
+    matches!(&svd[88..91], b"%/@" | b"%/C" | b"%/E")
+}
+
+/// `README.TXT;1` → `README.TXT`; `DIR.` → `DIR`.
+fn plain_name(raw: &[u8]) -> String {
+    let name = String::from_utf8_lossy(raw);
+    let name = name.split(';').next().unwrap_or_default();
+    name.strip_suffix('.').unwrap_or(name).to_string()
+}
+
+/// Joliet names are big-endian UCS-2 and keep the `;1` version suffix.
+fn utf16_name(raw: &[u8]) -> String {
+    let units: Vec<u16> = raw
+        .chunks_exact(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .collect();
+    let name = String::from_utf16_lossy(&units);
+    let name = name.trim_end_matches(['\0', ' ']);
+    name.split(';').next().unwrap_or_default().to_string()
+}
+
+/// 7-byte directory record time: years since 1900, month, day, hour,
+/// minute, second, GMT offset in 15-minute steps.
+fn record_datetime(b: &[u8]) -> Option<i64> {
+    let date = chrono::NaiveDate::from_ymd_opt(1900 + b[0] as i32, b[1] as u32, b[2] as u32)?;
+    let time = date.and_hms_opt(b[3] as u32, b[4] as u32, b[5] as u32)?;
+    Some(time.and_utc().timestamp() - (b[6] as i8) as i64 * 15 * 60)
+}
+
+/// 17-byte descriptor time: `YYYYMMDDHHMMSScc` digits plus the GMT offset.
+fn descriptor_datetime(b: &[u8]) -> Option<i64> {
+    let digits = std::str::from_utf8(&b[..16]).ok()?;
+    let field = |range: std::ops::Range<usize>| digits.get(range)?.parse::<u32>().ok();
+    let date = chrono::NaiveDate::from_ymd_opt(field(0..4)? as i32, field(4..6)?, field(6..8)?)?;
+    let time = date.and_hms_opt(field(8..10)?, field(10..12)?, field(12..14)?)?;
+    Some(time.and_utc().timestamp() - (b[16] as i8) as i64 * 15 * 60)
+}
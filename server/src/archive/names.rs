@@ -0,0 +1,126 @@
+use anyhow::{anyhow, Result};
+use encoding_rs::Encoding;
+
+/// CP437 characters for bytes 0x80-0xFF; the lower half is ASCII.
+const CP437_HIGH: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+/// The encoding of zip entry names stored without the UTF-8 flag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum NameEncoding {
+    /// The zip specification's default, which encoding_rs does not cover.
+    Cp437,
+    Other(&'static Encoding),
+}
+
+impl NameEncoding {
+    /// Parse a user-supplied label: `cp437` or any WHATWG encoding label
+    /// (`utf-8`, `cp866`, `shift_jis`, `gbk`, `windows-1251`, ...).
+    pub fn from_label(label: &str) -> Result<Self> {
+        let label = label.trim();
+        if ["cp437", "ibm437", "437"].iter().any(|l| l.eq_ignore_ascii_case(label)) {
+            return Ok(Self::Cp437);
+        }
+        Encoding::for_label(label.as_bytes())
+            .map(Self::Other)
+            .ok_or_else(|| anyhow!("Unknown name encoding '{}'", label))
+    }
+
+    /// Guess the encoding shared by `names`. Names that are all valid UTF-8
+    /// are taken as UTF-8, as written by zippers that omit the flag. A
+    /// Western European guess means CP437, since DOS and Windows zippers
+    /// use the OEM code page rather than windows-1252.
+    pub fn detect<'a>(names: impl IntoIterator<Item = &'a [u8]>) -> Self {
+        let mut detector = chardetng::EncodingDetector::new();
+        let mut utf8 = true;
+        for name in names {
+            utf8 &= std::str::from_utf8(name).is_ok();
+            detector.feed(name, false);
+            detector.feed(b"\n", false);
+        }
+        if utf8 {
+            return Self::Other(encoding_rs::UTF_8);
+        }
+        detector.feed(&[], true);
+        match detector.guess(None, false) {
+            encoding if encoding == encoding_rs::WINDOWS_1252 => Self::Cp437,
+            encoding => Self::Other(encoding),
+        }
+    }
+
+    pub fn decode(&self, raw: &[u8]) -> String {
+        match self {
+            Self::Cp437 => raw
+                .iter()
+                .map(|&b| if b < 0x80 { b as char } else { CP437_HIGH[b as usize - 0x80] })
+                .collect(),
+            Self::Other(encoding) => encoding.decode_without_bom_handling(raw).0.into_owned(),
+        }
+    }
+
+    /// Lowercase label that `from_label` accepts back.
+    pub fn label(&self) -> String {
+        match self {
+            Self::Cp437 => "cp437".to_string(),
+            Self::Other(encoding) => encoding.name().to_lowercase(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_labels() {
+        assert_eq!(NameEncoding::from_label(" IBM437 ").unwrap(), NameEncoding::Cp437);
+        assert_eq!(NameEncoding::from_label("437").unwrap(), NameEncoding::Cp437);
+        assert_eq!(
+            NameEncoding::from_label("sjis").unwrap(),
+            NameEncoding::Other(encoding_rs::SHIFT_JIS)
+        );
+        assert!(NameEncoding::from_label("klingon").is_err());
+        for label in ["cp437", "utf-8", "windows-1251", "shift_jis", "gbk", "ibm866"] {
+            let encoding = NameEncoding::from_label(label).unwrap();
+            assert_eq!(NameEncoding::from_label(&encoding.label()).unwrap(), encoding);
+        }
+    }
+
+    #[test]
+    fn decodes_cp437() {
+        assert_eq!(NameEncoding::Cp437.decode(b"Caf\x82/readme.txt"), "Café/readme.txt");
+        assert_eq!(NameEncoding::Cp437.decode(b"\x80\x9b\xe1\xfe\xff"), "Ç¢ß■\u{a0}");
+        assert_eq!(NameEncoding::Cp437.decode(b"\x00\x7f"), "\u{0}\u{7f}");
+    }
+
+    #[test]
+    fn decodes_other_encodings() {
+        let cp1251 = NameEncoding::from_label("windows-1251").unwrap();
+        assert_eq!(cp1251.decode(b"\xcf\xf0\xe8\xe2\xe5\xf2.txt"), "Привет.txt");
+        // No BOM sniffing: the name is taken as it is.
+        let utf8 = NameEncoding::Other(encoding_rs::UTF_8);
+        assert_eq!(utf8.decode(b"\xef\xbb\xbfx"), "\u{feff}x");
+    }
+
+    #[test]
+    fn detects_encodings() {
+        let detect = |names: &[&[u8]]| NameEncoding::detect(names.iter().copied());
+        assert_eq!(detect(&[b"plain.txt", "naïve.txt".as_bytes()]), NameEncoding::Other(encoding_rs::UTF_8));
+        assert_eq!(detect(&[]), NameEncoding::Other(encoding_rs::UTF_8));
+        let shift_jis = encoding_rs::SHIFT_JIS.encode("日本語のファイル名.txt").0;
+        assert_eq!(detect(&[&shift_jis]), NameEncoding::Other(encoding_rs::SHIFT_JIS));
+        // Western European guesses are the DOS code page, not windows-1252.
+        assert_eq!(
+            detect(&[b"R\x82sum\x82 fran\x87ais.doc", b"D\x82j\x85 vu \x85 la plage.txt"]),
+            NameEncoding::Cp437
+        );
+    }
+}
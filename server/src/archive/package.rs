@@ -0,0 +1,487 @@
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+
+use super::volumes::Source;
+use super::{detect, ArchiveFormat};
+
+const AR_MAGIC: &[u8] = b"!<arch>\n";
+const AR_HEADER_LEN: u64 = 60;
+
+const CPIO_TRAILER: &str = "TRAILER!!!";
+/// cpio names longer than this are treated as corrupt.
+const MAX_CPIO_NAME: u64 = 64 * 1024;
+
+const RPM_LEAD_MAGIC: &[u8] = &[0xED, 0xAB, 0xEE, 0xDB];
+const RPM_HEADER_MAGIC: &[u8] = &[0x8E, 0xAD, 0xE8];
+const RPM_LEAD_LEN: u64 = 96;
+/// Header stores larger than this are treated as corrupt.
+const MAX_RPM_HEADER_BYTES: u64 = 64 * 1024 * 1024;
+
+// =======================
+// Stream Compression
+// =======================
+
+/// Wrap `reader` in the decompressor its leading bytes call for. Returns the
+/// stream and the compression name shown in listings.
+pub(super) fn decompress_auto<'a, R: Read + 'a>(reader: R) -> Result<(Box<dyn Read + 'a>, &'static str)> {
+    let mut reader = BufReader::new(reader);
+    let head = reader.fill_buf()?;
+    if let Some(format) = detect::stream_format(head) {
+        return Ok((format.decompress(reader)?, format.stream_compression()));
+    }
+    // Legacy `.lzma` streams, still found in old packages
+    if head.starts_with(&[0x5D, 0x00, 0x00]) {
+        let stream = xz2::stream::Stream::new_lzma_decoder(u64::MAX)?;
+        return Ok((Box::new(xz2::read::XzDecoder::new_stream(reader, stream)), "LZMA"));
+    }
+    Ok((Box::new(reader), ArchiveFormat::Tar.stream_compression()))
+}
+
+// =======================
+// ar
+// =======================
+
+/// A member of a Unix `ar` archive (static libraries, Debian packages).
+pub(super) struct ArMember {
+    pub name: String,
+    /// Offset of the member's data in the archive file.
+    pub offset: u64,
+    pub size: u64,
+    pub modified: i64,
+    /// Octal mode, owner and group ids; blank in archives made with `ar D`.
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+pub(super) fn is_ar(head: &[u8]) -> bool {
+    head.starts_with(AR_MAGIC)
+}
+
+/// Read the member table of an `ar` archive. GNU (`//` table) and BSD
+/// (`#1/len`) long names are resolved; symbol tables are skipped.
+pub(super) fn read_ar(file: &mut Source) -> Result<Vec<ArMember>> {
+    let len = file.size()?;
+    let mut magic = [0u8; 8];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut magic)?;
+    if !is_ar(&magic) {
+        return Err(anyhow!("Not an ar archive"));
+    }
+
+    let mut members = Vec::new();
+    let mut long_names = Vec::new();
+    let mut pos = AR_MAGIC.len() as u64;
+
+    while pos + AR_HEADER_LEN <= len {
+        let mut header = [0u8; AR_HEADER_LEN as usize];
+        file.seek(SeekFrom::Start(pos))?;
+        file.read_exact(&mut header)?;
+        if &header[58..60] != b"`\n" {
+            return Err(anyhow!("Corrupt ar member header at offset {}", pos));
+        }
+        let field = |range: std::ops::Range<usize>| String::from_utf8_lossy(&header[range]).trim().to_string();
+        let raw_name = field(0..16);
+        let size: u64 = field(48..58)
+            .parse()
+            .map_err(|_| anyhow!("Corrupt ar member size at offset {}", pos))?;
+        let mut offset = pos + AR_HEADER_LEN;
+        let mut data_size = size.min(len.saturating_sub(offset));
+        // Member data is padded to an even offset.
+        pos = offset + size + size % 2;
+
+        let name = if raw_name == "/" || raw_name == "/SYM64/" {
+            continue;
+        } else if raw_name == "//" {
+            long_names.clear();
+            file.take(data_size).read_to_end(&mut long_names)?;
+            continue;
+        } else if let Some(len) = raw_name.strip_prefix("#1/").and_then(|n| n.parse::<u64>().ok()) {
+            // BSD: the name is stored at the start of the data.
+            let len = len.min(data_size);
+            let mut name = Vec::new();
+            file.take(len).read_to_end(&mut name)?;
+            offset += len;
+            data_size -= len;
+            String::from_utf8_lossy(&name).trim_end_matches('\0').to_string()
+        } else if let Some(index) = raw_name.strip_prefix('/').and_then(|n| n.parse::<usize>().ok()) {
+            // GNU: an offset into the `//` table, each name ending in "/\n".
+            let rest = long_names.get(index..).unwrap_or_default();
+            let end = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+            String::from_utf8_lossy(&rest[..end]).trim_end_matches('/').to_string()
+        } else {
+            raw_name.trim_end_matches('/').to_string()
+        };
+        if name.is_empty() || name.starts_with("__.SYMDEF") {
+            continue;
+        }
+
+        members.push(ArMember {
+            name,
+            offset,
+            size: data_size,
+            modified: field(16..28).parse().unwrap_or(0),
+            mode: u32::from_str_radix(&field(40..48), 8).ok(),
+            uid: field(28..34).parse().ok(),
+            gid: field(34..40).parse().ok(),
+        });
+    }
+    Ok(members)
+}
+
+// =======================
+// cpio
+// =======================
+
+/// Header of one cpio entry.
+pub(super) struct CpioEntry {
+    /// Normalised path: no `./` prefix and no trailing slash.
+    pub path: String,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    pub modified: i64,
+}
+
+impl CpioEntry {
+    pub fn is_dir(&self) -> bool {
+        self.mode & 0o170000 == 0o040000
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.mode & 0o170000 == 0o100000
+    }
+}
+
+/// `newc`/`crc` (hex) and portable `odc` (octal) headers.
+pub(super) fn is_cpio(head: &[u8]) -> bool {
+    head.starts_with(b"070701") || head.starts_with(b"070702") || head.starts_with(b"070707")
+}
+
+/// Sequential reader over a cpio stream; like tar, it has no index.
+pub(super) struct CpioReader<R> {
+    inner: R,
+    /// Data of the current entry not yet read
+    remaining: u64,
+    /// Alignment padding after the current entry's data
+    padding: u64,
+}
+
+impl<R: Read> CpioReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            remaining: 0,
+            padding: 0,
+        }
+    }
+
+    /// Advance to the next entry, skipping whatever is left of the current
+    /// one. `None` at the trailer.
+    pub fn next_entry(&mut self) -> Result<Option<CpioEntry>> {
+        let skip = self.remaining + self.padding;
+        std::io::copy(&mut (&mut self.inner).take(skip), &mut std::io::sink())?;
+        self.remaining = 0;
+        self.padding = 0;
+
+        let mut magic = [0u8; 6];
+        self.inner.read_exact(&mut magic)?;
+        // (mode, uid, gid, mtime, size, name size, 4-byte aligned)
+        let (mode, uid, gid, modified, size, name_size, aligned) = match &magic {
+            b"070701" | b"070702" => {
+                let mut header = [0u8; 104];
+                self.inner.read_exact(&mut header)?;
+                let hex = |i: usize| number(&header[i * 8..i * 8 + 8], 16);
+                (hex(1)?, hex(2)?, hex(3)?, hex(5)?, hex(6)?, hex(11)?, true)
+            }
+            b"070707" => {
+                let mut header = [0u8; 70];
+                self.inner.read_exact(&mut header)?;
+                let octal = |range: std::ops::Range<usize>| number(&header[range], 8);
+                (
+                    octal(12..18)?,
+                    octal(18..24)?,
+                    octal(24..30)?,
+                    octal(42..53)?,
+                    octal(59..70)?,
+                    octal(53..59)?,
+                    false,
+                )
+            }
+            _ => return Err(anyhow!("Unsupported cpio header (only newc and odc are supported)")),
+        };
+        if name_size > MAX_CPIO_NAME {
+            return Err(anyhow!("Corrupt cpio entry name"));
+        }
+
+        let mut name = Vec::new();
+        (&mut self.inner).take(name_size).read_to_end(&mut name)?;
+        if aligned {
+            // The 110-byte header plus the name is padded to 4 bytes.
+            let pad = (4 - (110 + name_size) % 4) % 4;
+            std::io::copy(&mut (&mut self.inner).take(pad), &mut std::io::sink())?;
+        }
+        let name = String::from_utf8_lossy(&name).trim_end_matches('\0').to_string();
+        if name == CPIO_TRAILER {
+            return Ok(None);
+        }
+
+        self.remaining = size;
+        self.padding = if aligned { (4 - size % 4) % 4 } else { 0 };
+        Ok(Some(CpioEntry {
+            path: name
+                .trim_start_matches("./")
+                .trim_start_matches('/')
+                .trim_end_matches('/')
+                .to_string(),
+            mode: mode as u32,
+            uid: uid as u32,
+            gid: gid as u32,
+            size,
+            modified: modified as i64,
+        }))
+    }
+
+    /// The underlying stream, positioned after the trailer once
+    /// `next_entry` has returned `None`.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Stream the current entry's data into `out`.
+    pub fn copy_data(&mut self, out: &mut dyn Write) -> Result<u64> {
+        let expected = self.remaining;
+        let copied = std::io::copy(&mut (&mut self.inner).take(expected), out)?;
+        self.remaining = 0;
+        if copied < expected {
+            return Err(anyhow!("Unexpected end of cpio archive"));
+        }
+        Ok(copied)
+    }
+}
+
+/// Reads the current entry's data, ending where the entry does.
+impl<R: Read> Read for CpioReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = buf.len().min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+        if len == 0 {
+            return Ok(0);
+        }
+        let n = self.inner.read(&mut buf[..len])?;
+        if n == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "Unexpected end of cpio archive",
+            ));
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+fn number(field: &[u8], radix: u32) -> Result<u64> {
+    std::str::from_utf8(field)
+        .ok()
+        .and_then(|s| u64::from_str_radix(s, radix).ok())
+        .ok_or_else(|| anyhow!("Corrupt cpio header"))
+}
+
+// =======================
+// RPM
+// =======================
+
+/// Header tags reported in the package metadata, with their keys.
+const RPM_TAGS: &[(u32, &str)] = &[
+    (1000, "name"),
+    (1003, "epoch"),
+    (1001, "version"),
+    (1002, "release"),
+    (1022, "architecture"),
+    (1004, "summary"),
+    (1005, "description"),
+    (1014, "license"),
+    (1016, "group"),
+    (1020, "homepage"),
+    (1011, "vendor"),
+    (1015, "packager"),
+    (1009, "installed-size"),
+    (1006, "build-time"),
+];
+
+/// (names, flags, versions) tags of the dependency lists.
+const RPM_REQUIRES: (u32, u32, u32) = (1049, 1048, 1050);
+const RPM_PROVIDES: (u32, u32, u32) = (1047, 1112, 1113);
+
+/// Package fields and payload location of an RPM.
+pub(super) struct RpmPackage {
+    pub metadata: BTreeMap<String, String>,
+    /// Offset of the compressed cpio payload.
+    pub payload_offset: u64,
+}
+
+pub(super) fn is_rpm(head: &[u8]) -> bool {
+    head.starts_with(RPM_LEAD_MAGIC)
+}
+
+/// Read the lead, signature and main header of an RPM.
+pub(super) fn read_rpm(file: &mut Source) -> Result<RpmPackage> {
+    let mut lead = [0u8; RPM_LEAD_LEN as usize];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut lead)?;
+    if !is_rpm(&lead) {
+        return Err(anyhow!("Not an RPM package"));
+    }
+
+    let (_, signature_len) = RpmHeader::read(file)?;
+    // The signature header is padded to a multiple of 8 bytes.
+    let mut pos = RPM_LEAD_LEN + signature_len;
+    pos += (8 - pos % 8) % 8;
+    file.seek(SeekFrom::Start(pos))?;
+    let (header, header_len) = RpmHeader::read(file)?;
+
+    let mut metadata = BTreeMap::new();
+    for &(tag, key) in RPM_TAGS {
+        if let Some(value) = header.values(tag).into_iter().next() {
+            metadata.insert(key.to_string(), value);
+        }
+    }
+    for (key, tags) in [("depends", RPM_REQUIRES), ("provides", RPM_PROVIDES)] {
+        let list = header.dependencies(tags);
+        if !list.is_empty() {
+            metadata.insert(key.to_string(), list);
+        }
+    }
+
+    Ok(RpmPackage {
+        metadata,
+        payload_offset: pos + header_len,
+    })
+}
+
+/// An RPM header structure: an index of (tag, type, offset, count) records
+/// into a data store.
+struct RpmHeader {
+    index: Vec<(u32, u32, u32, u32)>,
+    store: Vec<u8>,
+}
+
+impl RpmHeader {
+    /// Read the header at the current position; also returns its length.
+    fn read(file: &mut Source) -> Result<(Self, u64)> {
+        let mut intro = [0u8; 16];
+        file.read_exact(&mut intro)?;
+        if !intro.starts_with(RPM_HEADER_MAGIC) {
+            return Err(anyhow!("Corrupt RPM header"));
+        }
+        let be = |bytes: &[u8]| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let count = be(&intro[8..12]) as u64;
+        let store_len = be(&intro[12..16]) as u64;
+        if count * 16 + store_len > MAX_RPM_HEADER_BYTES {
+            return Err(anyhow!("Corrupt RPM header"));
+        }
+
+        let mut raw = vec![0u8; (count * 16) as usize];
+        file.read_exact(&mut raw)?;
+        let index = raw
+            .chunks_exact(16)
+            .map(|r| (be(&r[0..4]), be(&r[4..8]), be(&r[8..12]), be(&r[12..16])))
+            .collect();
+        let mut store = vec![0u8; store_len as usize];
+        file.read_exact(&mut store)?;
+        Ok((Self { index, store }, 16 + count * 16 + store_len))
+    }
+
+    /// Values of a tag as strings; integers are formatted in decimal.
+    fn values(&self, tag: u32) -> Vec<String> {
+        let Some(&(_, kind, offset, count)) = self.index.iter().find(|e| e.0 == tag) else {
+            return Vec::new();
+        };
+        let data = self.store.get(offset as usize..).unwrap_or_default();
+        match kind {
+            // INT32
+            4 => data
+                .chunks_exact(4)
+                .take(count as usize)
+                .map(|c| u32::from_be_bytes([c[0], c[1], c[2], c[3]]).to_string())
+                .collect(),
+            // STRING, STRING_ARRAY, I18NSTRING (the first locale is the default)
+            6 | 8 | 9 => {
+                let take = if kind == 8 { count as usize } else { 1 };
+                data.split(|&b| b == 0)
+                    .take(take)
+                    .map(|s| String::from_utf8_lossy(s).into_owned())
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// A dependency list in Debian style: `bash, libc.so.6 >= 2.34`.
+    /// `rpmlib(...)` features are internal to rpm and left out.
+    fn dependencies(&self, (names, flags, versions): (u32, u32, u32)) -> String {
+        let names = self.values(names);
+        let flags = self.values(flags);
+        let versions = self.values(versions);
+        let mut list: Vec<String> = Vec::new();
+
+        for (i, name) in names.iter().enumerate() {
+            if name.starts_with("rpmlib(") {
+                continue;
+            }
+            let flag: u32 = flags.get(i).and_then(|f| f.parse().ok()).unwrap_or(0);
+            let op = match flag & 0x0E {
+                0x02 => "<<",
+                0x04 => ">>",
+                0x08 => "=",
+                0x0A => "<=",
+                0x0C => ">=",
+                _ => "",
+            };
+            let entry = match versions.get(i) {
+                Some(version) if !version.is_empty() && !op.is_empty() => {
+                    format!("{} {} {}", name, op, version)
+                }
+                _ => name.clone(),
+            };
+            if !list.contains(&entry) {
+                list.push(entry);
+            }
+        }
+        list.join(", ")
+    }
+}
+
+// =======================
+// Debian control
+// =======================
+
+/// Parse a Debian `control` file. Keys are lowercased and `Package`
+/// becomes `name`, matching the keys reported for RPMs.
+pub(super) fn parse_control(text: &str) -> BTreeMap<String, String> {
+    let mut fields: BTreeMap<String, String> = BTreeMap::new();
+    let mut current: Option<String> = None;
+
+    for line in text.lines() {
+        if line.starts_with([' ', '\t']) {
+            // Continuation; a lone "." stands for an empty line.
+            if let Some(value) = current.as_ref().and_then(|key| fields.get_mut(key)) {
+                let line = line.trim();
+                value.push('\n');
+                value.push_str(if line == "." { "" } else { line });
+            }
+        } else if let Some((key, value)) = line.split_once(':') {
+            let mut key = key.trim().to_lowercase();
+            if key == "package" {
+                key = "name".to_string();
+            }
+            fields.insert(key.clone(), value.trim().to_string());
+            current = Some(key);
+        } else if line.trim().is_empty() {
+            // Only the first paragraph describes the binary package.
+            break;
+        }
+    }
+    fields
+}
@@ -0,0 +1,265 @@
+use anyhow::Result;
+use globset::GlobSet;
+use std::io::{self, Read, Seek, Write};
+use std::time::{Duration, UNIX_EPOCH};
+
+use super::create::{build_globset, persist, seven_zip_writer, staging_file, zip_options};
+use super::{dispatch_for_each, normalize_inner, resolve_nested, volumes, ArchiveFormat, ArchiveOptions, StreamEncoder};
+use crate::jobs::{JobHandle, ProgressReader};
+use crate::protocol::{ArchiveEntry, ArchiveEntryType, CreateArchiveOptions};
+
+/// Set in 7z attributes when the high 16 bits hold a Unix mode.
+const FILE_ATTRIBUTE_UNIX_EXTENSION: u32 = 0x8000;
+const FILE_ATTRIBUTE_DIRECTORY: u32 = 0x10;
+
+// =======================
+// Public entry point
+// =======================
+
+/// What `repack_archive` wrote.
+pub struct RepackedArchive {
+    /// Names stored in the new archive
+    pub entries: Vec<String>,
+    /// Symbolic links left out because the new format cannot store them
+    pub skipped: Vec<String>,
+    /// The archive file, or its volumes when split
+    pub files: Vec<String>,
+}
+
+/// Copy every entry of the archive at `path` into a new `format` archive at
+/// `destination`, keeping paths, modification times and modes. Entries are
+/// streamed from one archive into the other without being extracted.
+///
+/// `opts` opens the source; `options` configures the new archive as for
+/// CREATE_ARCHIVE, with `exclude` matched against inner paths. `base_path`
+/// and `store_symlinks` do not apply.
+pub fn repack_archive(
+    path: &str,
+    destination: &str,
+    format: ArchiveFormat,
+    opts: &ArchiveOptions,
+    options: &CreateArchiveOptions,
+    job: &JobHandle,
+) -> Result<RepackedArchive> {
+    if options.password.is_some() && format != ArchiveFormat::SevenZip {
+        anyhow::bail!("Password protection is only supported for 7z archives");
+    }
+    if let Some(size) = options.split_size {
+        volumes::check_split_size(size)?;
+    }
+    let exclude = build_globset(&options.exclude)?;
+    let (archive, _) = resolve_nested(path, "", opts)?;
+
+    let mut tmp = staging_file(destination)?;
+    let mut entries = Vec::new();
+    let mut skipped = Vec::new();
+    {
+        let mut writer = RepackWriter::new(tmp.as_file_mut(), format, options)?;
+        dispatch_for_each(&archive.path, opts, job, &mut |record, data| {
+            let name = normalize_inner(&record.inner_path);
+            if name.is_empty() || is_excluded(&exclude, &name) {
+                return Ok(());
+            }
+            job.begin_item(&name);
+            if writer.add(&name, record, &mut ProgressReader::new(data, job))? {
+                entries.push(name);
+            } else {
+                skipped.push(name);
+            }
+            job.finish_item();
+            Ok(())
+        })?;
+        job.check_cancelled()?;
+        writer.finish()?;
+    }
+
+    let files = persist(tmp, destination, options.split_size)?;
+    Ok(RepackedArchive {
+        entries,
+        skipped,
+        files,
+    })
+}
+
+/// Whether `name` or any directory above it matches an exclude pattern,
+/// by full path or by its own name.
+fn is_excluded(exclude: &GlobSet, name: &str) -> bool {
+    let mut path = name;
+    loop {
+        let own_name = path.rsplit('/').next().unwrap_or(path);
+        if exclude.is_match(path) || exclude.is_match(own_name) {
+            return true;
+        }
+        match path.rsplit_once('/') {
+            Some((parent, _)) => path = parent,
+            None => return false,
+        }
+    }
+}
+
+// =======================
+// Writers
+// =======================
+
+/// The new archive, fed one source record at a time.
+enum RepackWriter<W: Write + Seek> {
+    Zip(zip::ZipWriter<W>, Option<u32>),
+    Tar(tar::Builder<StreamEncoder<W>>),
+    SevenZip(sevenz_rust::SevenZWriter<W>),
+}
+
+impl<W: Write + Seek> RepackWriter<W> {
+    fn new(out: W, format: ArchiveFormat, options: &CreateArchiveOptions) -> Result<Self> {
+        let level = options.compression_level;
+        Ok(match format {
+            ArchiveFormat::Zip => Self::Zip(zip::ZipWriter::new(out), level),
+            ArchiveFormat::SevenZip => {
+                Self::SevenZip(seven_zip_writer(out, level, options.password.as_deref())?)
+            }
+            ArchiveFormat::Tar
+            | ArchiveFormat::TarGz
+            | ArchiveFormat::TarBz2
+            | ArchiveFormat::TarXz
+            | ArchiveFormat::TarZst => Self::Tar(tar::Builder::new(format.compress(out, level)?)),
+            ArchiveFormat::Gz
+            | ArchiveFormat::Bz2
+            | ArchiveFormat::Xz
+            | ArchiveFormat::Zst
+            | ArchiveFormat::Cab
+            | ArchiveFormat::Rar
+            | ArchiveFormat::Iso
+            | ArchiveFormat::Deb
+            | ArchiveFormat::Rpm
+            | ArchiveFormat::Ar
+            | ArchiveFormat::Cpio => {
+                anyhow::bail!("Creating {} archives is not supported", format.as_str())
+            }
+        })
+    }
+
+    /// Store one record as `name`. Returns false when the record is a link
+    /// this format cannot hold, or whose target the source did not record.
+    fn add(&mut self, name: &str, record: &ArchiveEntry, data: &mut dyn Read) -> Result<bool> {
+        let mtime = (record.modified > 0)
+            .then(|| UNIX_EPOCH + Duration::from_secs(record.modified as u64));
+        let mode = record.mode.map(|mode| mode & 0o7777);
+
+        match self {
+            Self::Zip(zip, level) => match record.entry_type {
+                ArchiveEntryType::Directory => {
+                    zip.add_directory(name, zip_options(0, mtime, mode, *level))?;
+                }
+                ArchiveEntryType::Symlink => {
+                    let Some(target) = &record.symlink_target else {
+                        return Ok(false);
+                    };
+                    zip.add_symlink(name, target, zip_options(0, mtime, mode, *level))?;
+                }
+                ArchiveEntryType::File => {
+                    zip.start_file(name, zip_options(record.size, mtime, mode, *level))?;
+                    io::copy(data, zip)?;
+                }
+            },
+            Self::Tar(builder) => {
+                let mut header = tar::Header::new_gnu();
+                header.set_mtime(record.modified.max(0) as u64);
+                match record.entry_type {
+                    ArchiveEntryType::Directory => {
+                        header.set_entry_type(tar::EntryType::Directory);
+                        header.set_mode(mode.unwrap_or(0o755));
+                        header.set_size(0);
+                        builder.append_data(&mut header, format!("{}/", name), io::empty())?;
+                    }
+                    ArchiveEntryType::Symlink => {
+                        let Some(target) = &record.symlink_target else {
+                            return Ok(false);
+                        };
+                        header.set_entry_type(tar::EntryType::Symlink);
+                        header.set_mode(mode.unwrap_or(0o777));
+                        header.set_size(0);
+                        builder.append_link(&mut header, name, target)?;
+                    }
+                    ArchiveEntryType::File => {
+                        header.set_entry_type(tar::EntryType::Regular);
+                        header.set_mode(mode.unwrap_or(0o644));
+                        header.set_size(record.size);
+                        let data = ExactReader {
+                            inner: data,
+                            remaining: record.size,
+                        };
+                        builder.append_data(&mut header, name, data)?;
+                    }
+                }
+            }
+            Self::SevenZip(writer) => {
+                let is_dir = matches!(record.entry_type, ArchiveEntryType::Directory);
+                // 7z has no notion of symlinks.
+                if matches!(record.entry_type, ArchiveEntryType::Symlink) {
+                    return Ok(false);
+                }
+                let mut entry = sevenz_rust::SevenZArchiveEntry::new();
+                entry.name = name.to_string();
+                entry.is_directory = is_dir;
+                entry.has_stream = !is_dir;
+                if let Some(date) = mtime.and_then(|t| t.try_into().ok()) {
+                    entry.last_modified_date = date;
+                    entry.has_last_modified_date = true;
+                }
+                if let Some(mode) = mode {
+                    let file_type = if is_dir { 0o040000 } else { 0o100000 };
+                    entry.has_windows_attributes = true;
+                    entry.windows_attributes = FILE_ATTRIBUTE_UNIX_EXTENSION | ((file_type | mode) << 16);
+                    if is_dir {
+                        entry.windows_attributes |= FILE_ATTRIBUTE_DIRECTORY;
+                    }
+                }
+                if is_dir {
+                    writer.push_archive_entry::<io::Empty>(entry, None)?;
+                } else {
+                    writer.push_archive_entry(entry, Some(data))?;
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            Self::Zip(mut zip, _) => {
+                zip.finish()?;
+            }
+            Self::Tar(builder) => {
+                builder.into_inner()?.finish()?;
+            }
+            Self::SevenZip(writer) => {
+                writer.finish()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads exactly `remaining` bytes, failing if `inner` ends sooner, so a
+/// tar header never promises more data than follows it.
+struct ExactReader<R> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<R: Read> Read for ExactReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+        if len == 0 {
+            return Ok(0);
+        }
+        let n = self.inner.read(&mut buf[..len])?;
+        if n == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Entry is shorter than its recorded size",
+            ));
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
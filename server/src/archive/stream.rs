@@ -0,0 +1,451 @@
+use anyhow::Result;
+use flate2::write::DeflateEncoder;
+use std::fs::File;
+use std::io::{self, Read, Write};
+
+use super::create::{append_tar_item, collect_items, zip_datetime, ItemKind, SourceItem};
+use super::ArchiveFormat;
+use crate::jobs::JobHandle;
+use crate::protocol::CreateArchiveOptions;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x0807_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const ZIP64_END_SIGNATURE: u32 = 0x0606_4b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x0706_4b50;
+const END_SIGNATURE: u32 = 0x0605_4b50;
+
+/// The crc and sizes follow the data in a descriptor.
+const FLAG_DATA_DESCRIPTOR: u16 = 1 << 3;
+/// Names are UTF-8.
+const FLAG_UTF8: u16 = 1 << 11;
+
+/// Files at least this large get zip64 sizes up front. It leaves room for
+/// deflate to grow incompressible data past 4 GiB.
+const ZIP64_THRESHOLD: u64 = 0xF000_0000;
+
+// =======================
+// Public entry point
+// =======================
+
+/// Write an archive of `sources` to `out` while it is being built, for
+/// formats that never seek back: zip and the tar family. Options are those
+/// of CREATE_ARCHIVE, except that archives cannot be encrypted or split.
+pub fn stream_archive(
+    sources: &[String],
+    format: ArchiveFormat,
+    options: &CreateArchiveOptions,
+    out: &mut dyn Write,
+) -> Result<()> {
+    if sources.is_empty() {
+        anyhow::bail!("No sources to archive");
+    }
+    if options.password.is_some() || options.split_size.is_some() {
+        anyhow::bail!("Streamed archives cannot be encrypted or split");
+    }
+    let items = collect_items(sources, options, options.store_symlinks)?;
+    let job = JobHandle::detached("STREAM_ARCHIVE");
+    let level = options.compression_level;
+
+    match format {
+        ArchiveFormat::Zip => {
+            let mut zip = ZipStream::new(out);
+            for item in &items {
+                zip.add(item, level)?;
+            }
+            zip.finish()?;
+        }
+        ArchiveFormat::Tar
+        | ArchiveFormat::TarGz
+        | ArchiveFormat::TarBz2
+        | ArchiveFormat::TarXz
+        | ArchiveFormat::TarZst => {
+            let mut builder = tar::Builder::new(format.compress(out, level)?);
+            for item in &items {
+                append_tar_item(&mut builder, item, &job)?;
+            }
+            builder.into_inner()?.finish()?;
+        }
+        _ => anyhow::bail!("Streaming {} archives is not supported", format.as_str()),
+    }
+    Ok(())
+}
+
+// =======================
+// Zip stream
+// =======================
+
+/// A zip writer for outputs that cannot seek. The zip crate's writer goes
+/// back to fill in each local header once the data is written; here the
+/// crc and sizes follow each entry in a data descriptor instead.
+struct ZipStream<W: Write> {
+    out: CountingWriter<W>,
+    records: Vec<CentralRecord>,
+    /// `ZIP64_THRESHOLD`, lowered by tests.
+    zip64_threshold: u64,
+}
+
+/// What the central directory repeats about an entry.
+struct CentralRecord {
+    name: Vec<u8>,
+    flags: u16,
+    method: u16,
+    time: u16,
+    date: u16,
+    crc: u32,
+    compressed: u64,
+    size: u64,
+    offset: u64,
+    /// Unix mode including the file type
+    mode: u32,
+    zip64: bool,
+}
+
+impl<W: Write> ZipStream<W> {
+    fn new(out: W) -> Self {
+        Self {
+            out: CountingWriter { inner: out, count: 0 },
+            records: Vec::new(),
+            zip64_threshold: ZIP64_THRESHOLD,
+        }
+    }
+
+    /// Append one filesystem item. Files are deflated as they are read;
+    /// directories and links are stored with their sizes known up front.
+    fn add(&mut self, item: &SourceItem, level: Option<u32>) -> Result<()> {
+        let (time, date) = item
+            .mtime
+            .and_then(zip_datetime)
+            .map_or((0, 0x21), |dt| (dt.timepart(), dt.datepart()));
+        let mode = item.mode.unwrap_or(0);
+        let mut name = item.name.clone().into_bytes();
+        if matches!(item.kind, ItemKind::Directory) {
+            name.push(b'/');
+        }
+        // Headers give the name's length in 16 bits.
+        if name.len() > u16::MAX as usize {
+            anyhow::bail!("'{}' is too long for a zip entry name: {} bytes", item.name, name.len());
+        }
+        let mut record = CentralRecord {
+            name,
+            flags: FLAG_UTF8,
+            method: 0,
+            time,
+            date,
+            crc: 0,
+            compressed: 0,
+            size: 0,
+            offset: self.out.count,
+            mode,
+            zip64: false,
+        };
+
+        match &item.kind {
+            ItemKind::Directory => {
+                record.mode = 0o040000 | if mode == 0 { 0o755 } else { mode };
+                self.write_local_header(&record)?;
+            }
+            ItemKind::Symlink(target) => {
+                let target = target.to_string_lossy().into_owned().into_bytes();
+                let mut crc = flate2::Crc::new();
+                crc.update(&target);
+                record.mode = 0o120000 | if mode == 0 { 0o777 } else { mode };
+                record.crc = crc.sum();
+                record.size = target.len() as u64;
+                record.compressed = record.size;
+                self.write_local_header(&record)?;
+                self.out.write_all(&target)?;
+            }
+            ItemKind::File => {
+                record.flags |= FLAG_DATA_DESCRIPTOR;
+                record.method = 8;
+                record.mode = 0o100000 | if mode == 0 { 0o644 } else { mode };
+                record.zip64 = item.size >= self.zip64_threshold;
+                self.write_local_header(&record)?;
+
+                // Never read more than the size collected, like tar.
+                let mut data = File::open(&item.path)?.take(item.size);
+                let start = self.out.count;
+                let mut crc = flate2::Crc::new();
+                let compression = flate2::Compression::new(level.unwrap_or(6).min(9));
+                let mut encoder = DeflateEncoder::new(&mut self.out, compression);
+                let mut buf = vec![0u8; 64 * 1024];
+                loop {
+                    let n = data.read(&mut buf)?;
+                    if n == 0 {
+                        break;
+                    }
+                    crc.update(&buf[..n]);
+                    encoder.write_all(&buf[..n])?;
+                    record.size += n as u64;
+                }
+                encoder.finish()?;
+
+                record.crc = crc.sum();
+                record.compressed = self.out.count - start;
+                if !record.zip64 && record.compressed > u32::MAX as u64 {
+                    anyhow::bail!("'{}' does not fit in a zip entry without zip64", item.name);
+                }
+                self.write_data_descriptor(&record)?;
+            }
+        }
+        self.records.push(record);
+        Ok(())
+    }
+
+    fn write_local_header(&mut self, record: &CentralRecord) -> io::Result<()> {
+        // With a descriptor the header's crc and sizes are zero; zip64
+        // headers flag the sizes as living in the extra field.
+        let described = record.flags & FLAG_DATA_DESCRIPTOR != 0;
+        let (crc, compressed, size) = match (record.zip64, described) {
+            (true, _) => (0, u32::MAX, u32::MAX),
+            (false, true) => (0, 0, 0),
+            (false, false) => (record.crc, record.compressed as u32, record.size as u32),
+        };
+        let mut header = Vec::with_capacity(30 + record.name.len() + 20);
+        put_u32(&mut header, LOCAL_HEADER_SIGNATURE);
+        put_u16(&mut header, if record.zip64 { 45 } else { 20 });
+        put_u16(&mut header, record.flags);
+        put_u16(&mut header, record.method);
+        put_u16(&mut header, record.time);
+        put_u16(&mut header, record.date);
+        put_u32(&mut header, crc);
+        put_u32(&mut header, compressed);
+        put_u32(&mut header, size);
+        put_u16(&mut header, record.name.len() as u16);
+        put_u16(&mut header, if record.zip64 { 20 } else { 0 });
+        header.extend_from_slice(&record.name);
+        if record.zip64 {
+            put_u16(&mut header, 0x0001);
+            put_u16(&mut header, 16);
+            put_u64(&mut header, 0);
+            put_u64(&mut header, 0);
+        }
+        self.out.write_all(&header)
+    }
+
+    fn write_data_descriptor(&mut self, record: &CentralRecord) -> io::Result<()> {
+        let mut descriptor = Vec::with_capacity(24);
+        put_u32(&mut descriptor, DATA_DESCRIPTOR_SIGNATURE);
+        put_u32(&mut descriptor, record.crc);
+        if record.zip64 {
+            put_u64(&mut descriptor, record.compressed);
+            put_u64(&mut descriptor, record.size);
+        } else {
+            put_u32(&mut descriptor, record.compressed as u32);
+            put_u32(&mut descriptor, record.size as u32);
+        }
+        self.out.write_all(&descriptor)
+    }
+
+    /// Write the central directory and end records.
+    fn finish(mut self) -> Result<W> {
+        let start = self.out.count;
+        for record in &self.records {
+            let zip64 = record.zip64
+                || record.compressed >= u32::MAX as u64
+                || record.size >= u32::MAX as u64
+                || record.offset >= u32::MAX as u64;
+            let version = if zip64 { 45 } else { 20 };
+            let clamp = |value: u64| if zip64 { u32::MAX } else { value as u32 };
+            // MS-DOS directory bit alongside the Unix mode.
+            let dos = if record.mode & 0o170000 == 0o040000 { 0x10 } else { 0 };
+
+            let mut header = Vec::with_capacity(46 + record.name.len() + 28);
+            put_u32(&mut header, CENTRAL_HEADER_SIGNATURE);
+            put_u16(&mut header, 0x0300 | version); // made by Unix
+            put_u16(&mut header, version);
+            put_u16(&mut header, record.flags);
+            put_u16(&mut header, record.method);
+            put_u16(&mut header, record.time);
+            put_u16(&mut header, record.date);
+            put_u32(&mut header, record.crc);
+            put_u32(&mut header, clamp(record.compressed));
+            put_u32(&mut header, clamp(record.size));
+            put_u16(&mut header, record.name.len() as u16);
+            put_u16(&mut header, if zip64 { 28 } else { 0 });
+            put_u16(&mut header, 0); // comment length
+            put_u16(&mut header, 0); // disk number
+            put_u16(&mut header, 0); // internal attributes
+            put_u32(&mut header, record.mode << 16 | dos);
+            put_u32(&mut header, clamp(record.offset));
+            header.extend_from_slice(&record.name);
+            if zip64 {
+                put_u16(&mut header, 0x0001);
+                put_u16(&mut header, 24);
+                put_u64(&mut header, record.size);
+                put_u64(&mut header, record.compressed);
+                put_u64(&mut header, record.offset);
+            }
+            self.out.write_all(&header)?;
+        }
+
+        let end = self.out.count;
+        let (count, size) = (self.records.len() as u64, end - start);
+        let mut trailer = Vec::with_capacity(98);
+        if count >= u16::MAX as u64 || size >= u32::MAX as u64 || start >= u32::MAX as u64 {
+            put_u32(&mut trailer, ZIP64_END_SIGNATURE);
+            put_u64(&mut trailer, 44); // size of the rest of this record
+            put_u16(&mut trailer, 0x0300 | 45);
+            put_u16(&mut trailer, 45);
+            put_u32(&mut trailer, 0); // this disk
+            put_u32(&mut trailer, 0); // disk with the central directory
+            put_u64(&mut trailer, count);
+            put_u64(&mut trailer, count);
+            put_u64(&mut trailer, size);
+            put_u64(&mut trailer, start);
+            put_u32(&mut trailer, ZIP64_LOCATOR_SIGNATURE);
+            put_u32(&mut trailer, 0);
+            put_u64(&mut trailer, end);
+            put_u32(&mut trailer, 1); // total disks
+        }
+        put_u32(&mut trailer, END_SIGNATURE);
+        put_u16(&mut trailer, 0);
+        put_u16(&mut trailer, 0);
+        put_u16(&mut trailer, count.min(u16::MAX as u64) as u16);
+        put_u16(&mut trailer, count.min(u16::MAX as u64) as u16);
+        put_u32(&mut trailer, size.min(u32::MAX as u64) as u32);
+        put_u32(&mut trailer, start.min(u32::MAX as u64) as u32);
+        put_u16(&mut trailer, 0); // comment length
+        self.out.write_all(&trailer)?;
+        self.out.flush()?;
+        Ok(self.out.inner)
+    }
+}
+
+/// Tracks the offset of everything written, which zip records point to.
+struct CountingWriter<W> {
+    inner: W,
+    count: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn put_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::{Path, PathBuf};
+
+    fn item(path: PathBuf, name: &str, kind: ItemKind) -> SourceItem {
+        let size = std::fs::metadata(&path).map_or(0, |m| m.len());
+        SourceItem {
+            path,
+            name: name.to_string(),
+            kind,
+            size,
+            mtime: None,
+            mode: Some(0o640),
+        }
+    }
+
+    /// `dir/`, a compressible `dir/text.txt`, an incompressible `noise.bin`
+    /// and `link` pointing at the text file.
+    fn items(dir: &Path) -> Vec<SourceItem> {
+        let text = dir.join("text.txt");
+        std::fs::write(&text, "all work and no play ".repeat(5000)).unwrap();
+        let noise = dir.join("noise.bin");
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let bytes: Vec<u8> = (0..70_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        std::fs::write(&noise, bytes).unwrap();
+        vec![
+            item(dir.to_path_buf(), "dir", ItemKind::Directory),
+            item(text, "dir/text.txt", ItemKind::File),
+            item(noise, "noise.bin", ItemKind::File),
+            item(PathBuf::new(), "link", ItemKind::Symlink(PathBuf::from("dir/text.txt"))),
+        ]
+    }
+
+    fn write(items: &[SourceItem], zip64_threshold: u64) -> Vec<u8> {
+        let mut zip = ZipStream::new(Vec::new());
+        zip.zip64_threshold = zip64_threshold;
+        for item in items {
+            zip.add(item, None).unwrap();
+        }
+        zip.finish().unwrap()
+    }
+
+    fn assert_reads_back(bytes: Vec<u8>, items: &[SourceItem]) {
+        let mut zip = zip::ZipArchive::new(io::Cursor::new(bytes)).unwrap();
+        assert_eq!(zip.len(), items.len());
+
+        let dir = zip.by_name("dir/").unwrap();
+        assert!(dir.is_dir());
+        assert_eq!(dir.unix_mode(), Some(0o040640));
+        drop(dir);
+
+        for item in &items[1..3] {
+            let mut file = zip.by_name(&item.name).unwrap();
+            assert_eq!(file.unix_mode(), Some(0o100640));
+            let mut data = Vec::new();
+            // The reader checks the crc once it reaches the end.
+            file.read_to_end(&mut data).unwrap();
+            assert_eq!(data, std::fs::read(&item.path).unwrap(), "{}", item.name);
+        }
+
+        let mut link = zip.by_name("link").unwrap();
+        assert_eq!(link.unix_mode(), Some(0o120640));
+        let mut target = String::new();
+        link.read_to_string(&mut target).unwrap();
+        assert_eq!(target, "dir/text.txt");
+    }
+
+    #[test]
+    fn reads_back_with_the_zip_crate() {
+        let dir = tempfile::tempdir().unwrap();
+        let items = items(dir.path());
+        assert_reads_back(write(&items, ZIP64_THRESHOLD), &items);
+    }
+
+    #[test]
+    fn reads_back_forced_zip64() {
+        let dir = tempfile::tempdir().unwrap();
+        let items = items(dir.path());
+        let bytes = write(&items, 0);
+        // Both files were written with zip64 local headers and descriptors.
+        let local = LOCAL_HEADER_SIGNATURE.to_le_bytes();
+        let zip64_headers = bytes
+            .windows(6)
+            .filter(|w| w[..4] == local && w[4..] == 45u16.to_le_bytes())
+            .count();
+        assert_eq!(zip64_headers, 2);
+        assert_reads_back(bytes, &items);
+    }
+
+    #[test]
+    fn rejects_names_too_long_for_zip() {
+        let name = "a/".repeat(32_768);
+        let mut zip = ZipStream::new(Vec::new());
+        let error = zip.add(&item(PathBuf::new(), &name, ItemKind::Directory), None).unwrap_err();
+        assert!(error.to_string().contains("too long"), "{error}");
+        assert!(zip.out.inner.is_empty());
+    }
+}
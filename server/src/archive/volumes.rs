@@ -0,0 +1,718 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+use super::{detect, ArchiveError};
+
+/// The end of central directory record sits in the last 22 bytes plus an
+/// optional comment of up to 64 KiB.
+const MAX_EOCD_SEARCH: u64 = 22 + 0xFFFF;
+/// Smallest volume size accepted when splitting.
+const MIN_SPLIT_SIZE: u64 = 64 * 1024;
+/// Volume numbers are three digits wide when splitting.
+const MAX_SPLIT_VOLUMES: u64 = 999;
+
+// =======================
+// Volume sets
+// =======================
+
+/// How the volumes of a set are named.
+enum Naming {
+    /// `name.z01`, `name.z02`, ..., `name.zip` (spanned zip)
+    Zip,
+    /// `name.001`, `name.002`, ... (7-Zip) or `name.00`, `name.01`, ...
+    /// (`split -d`); plain byte splits.
+    Numbered { width: usize },
+    /// `name.aa`, `name.ab`, ... (`split`); plain byte splits.
+    Lettered { width: usize },
+}
+
+/// The volumes of a split archive, in order.
+pub(super) struct VolumeSet {
+    /// Name of the whole archive: a volume's name without its volume suffix.
+    pub name: String,
+    pub volumes: Vec<PathBuf>,
+    naming: Naming,
+}
+
+/// Find the volume set `path` belongs to, from any of its members. `None`
+/// when `path` is not part of a split archive.
+pub(super) fn find(path: &str) -> Result<Option<VolumeSet>> {
+    let path = Path::new(path);
+    let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+        return Ok(None);
+    };
+    let Some((stem, suffix)) = file_name.rsplit_once('.') else {
+        return Ok(None);
+    };
+    if stem.is_empty() {
+        return Ok(None);
+    }
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let lower = suffix.to_ascii_lowercase();
+    let is_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+
+    if lower == "zip" || (lower.len() >= 3 && lower.starts_with('z') && is_digits(&lower[1..])) {
+        let upper = suffix.starts_with('Z');
+        return zip_set(dir, stem, upper);
+    }
+
+    let (naming, first) = if suffix.len() >= 2 && is_digits(suffix) {
+        let width = suffix.len();
+        // `split -d` counts from 0, 7-Zip from 1.
+        let zero = format!("{:0width$}", 0);
+        let first = if dir.join(format!("{}.{}", stem, zero)).exists() {
+            zero
+        } else {
+            format!("{:0width$}", 1)
+        };
+        (Naming::Numbered { width }, first)
+    } else if suffix.len() >= 2
+        && suffix.bytes().all(|b| b.is_ascii_lowercase())
+        && detect::from_extension(stem).is_some()
+        // `name.tar.gz` is an archive, not volume `gz` of `name.tar`
+        && detect::from_extension(file_name).is_none()
+    {
+        let width = suffix.len();
+        (Naming::Lettered { width }, "a".repeat(width))
+    } else {
+        return Ok(None);
+    };
+
+    // Only a set when the first volume exists and starts an archive we know.
+    let first = dir.join(format!("{}.{}", stem, first));
+    let known = || {
+        detect::from_extension(stem).is_some() || detect::detect(&first.to_string_lossy()).is_some()
+    };
+    if !first.exists() || !known() {
+        return Ok(None);
+    }
+
+    let mut numbers: Vec<u64> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter_map(|name| {
+            let suffix = name.strip_prefix(stem)?.strip_prefix('.')?;
+            suffix_number(&naming, suffix)
+        })
+        .collect();
+    numbers.sort_unstable();
+    let (Some(&low), Some(&high)) = (numbers.first(), numbers.last()) else {
+        return Ok(None);
+    };
+
+    let volumes = (low..=high)
+        .map(|n| dir.join(format!("{}.{}", stem, volume_suffix(&naming, n))))
+        .collect();
+    let set = VolumeSet {
+        name: stem.to_string(),
+        volumes,
+        naming,
+    };
+    set.check_present()?;
+    Ok(Some(set))
+}
+
+/// A spanned zip: `.z01` ... `.zNN` and the `.zip` holding the central
+/// directory, whose end record gives the number of volumes.
+fn zip_set(dir: &Path, stem: &str, upper: bool) -> Result<Option<VolumeSet>> {
+    let volume = |ext: String| {
+        let ext = if upper { ext.to_uppercase() } else { ext };
+        dir.join(format!("{}.{}", stem, ext))
+    };
+    let last = volume("zip".to_string());
+    if !volume("z01".to_string()).exists() {
+        // A plain zip
+        return Ok(None);
+    }
+    if !last.exists() {
+        return Err(missing(&last));
+    }
+
+    let mut file = File::open(&last)?;
+    let (_, eocd) = find_eocd(&mut file)?;
+    let last_disk = u16::from_le_bytes([eocd[4], eocd[5]]) as u32;
+
+    let mut volumes: Vec<PathBuf> = (1..=last_disk).map(|n| volume(format!("z{:02}", n))).collect();
+    volumes.push(last);
+    let set = VolumeSet {
+        name: format!("{}.zip", stem),
+        volumes,
+        naming: Naming::Zip,
+    };
+    set.check_present()?;
+    Ok(Some(set))
+}
+
+fn suffix_number(naming: &Naming, suffix: &str) -> Option<u64> {
+    match *naming {
+        Naming::Numbered { width } if suffix.len() == width => suffix.parse().ok(),
+        Naming::Lettered { width } if suffix.len() == width => suffix.bytes().try_fold(0u64, |n, b| {
+            b.is_ascii_lowercase().then(|| n * 26 + (b - b'a') as u64)
+        }),
+        _ => None,
+    }
+}
+
+fn volume_suffix(naming: &Naming, mut n: u64) -> String {
+    match *naming {
+        Naming::Numbered { width } => format!("{:0width$}", n),
+        Naming::Lettered { width } => {
+            let mut letters = vec![b'a'; width];
+            for letter in letters.iter_mut().rev() {
+                *letter = b'a' + (n % 26) as u8;
+                n /= 26;
+            }
+            String::from_utf8(letters).unwrap_or_default()
+        }
+        Naming::Zip => format!("z{:02}", n),
+    }
+}
+
+fn missing(volume: &Path) -> anyhow::Error {
+    let name = volume
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    ArchiveError::MissingVolume(name).into()
+}
+
+impl VolumeSet {
+    fn check_present(&self) -> Result<()> {
+        match self.volumes.iter().find(|v| !v.exists()) {
+            Some(volume) => Err(missing(volume)),
+            None => Ok(()),
+        }
+    }
+
+    /// Register the set for reading as one file, without joining it on
+    /// disk. Spanned zips record offsets per volume, so their central
+    /// directory is rebased in memory and read in place of the stored one.
+    pub fn mount(&self) -> Result<Mount> {
+        let mut layout = Layout {
+            volumes: Vec::with_capacity(self.volumes.len()),
+            len: 0,
+            patches: Vec::new(),
+            modified: None,
+        };
+        for volume in &self.volumes {
+            let metadata = std::fs::metadata(volume).map_err(|_| missing(volume))?;
+            layout.volumes.push((volume.clone(), layout.len));
+            layout.len += metadata.len();
+            layout.modified = layout.modified.max(metadata.modified().ok());
+        }
+        if matches!(self.naming, Naming::Zip) {
+            let starts: Vec<u64> = layout.volumes.iter().map(|(_, start)| *start).collect();
+            layout.patches = rebase_zip(&mut Chain::new(Arc::new(layout.clone())), &starts)?;
+        }
+
+        // The stand-in path never exists on disk, so it cannot shadow a
+        // real file, and its name keeps the extension detection relies on.
+        let dir = tempfile::Builder::new().prefix("volumes-").tempdir()?;
+        let path = dir.path().join(&self.name);
+        mounts().lock().unwrap().insert(path.clone(), Arc::new(layout));
+        Ok(Mount { path, _dir: dir })
+    }
+}
+
+// =======================
+// Reading split archives
+// =======================
+
+/// Where each volume's bytes sit in the archive read as one file, and
+/// byte ranges served from memory instead of the volumes.
+#[derive(Clone)]
+struct Layout {
+    /// Each volume and its offset in the whole.
+    volumes: Vec<(PathBuf, u64)>,
+    len: u64,
+    patches: Vec<(u64, Vec<u8>)>,
+    /// The newest volume's modification time.
+    modified: Option<SystemTime>,
+}
+
+fn mounts() -> &'static Mutex<HashMap<PathBuf, Arc<Layout>>> {
+    static MOUNTS: OnceLock<Mutex<HashMap<PathBuf, Arc<Layout>>>> = OnceLock::new();
+    MOUNTS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// A split archive that `Source::open` reads across its volumes under a
+/// stand-in path. It is unregistered when dropped.
+pub(super) struct Mount {
+    path: PathBuf,
+    _dir: tempfile::TempDir,
+}
+
+impl Mount {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Mount {
+    fn drop(&mut self) {
+        mounts().lock().unwrap().remove(&self.path);
+    }
+}
+
+/// The first volume of a mounted split archive, from which the 7z tool
+/// reads the whole set itself; any other path as it is.
+pub(super) fn first_volume(path: &str) -> String {
+    match mounts().lock().unwrap().get(Path::new(path)) {
+        Some(layout) => layout.volumes[0].0.to_string_lossy().into_owned(),
+        None => path.to_string(),
+    }
+}
+
+/// An archive opened for reading: a file, or a mounted split archive.
+pub(super) enum Source {
+    File(File),
+    Volumes(Chain),
+}
+
+impl Source {
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref();
+        let layout = mounts().lock().unwrap().get(path).cloned();
+        match layout {
+            Some(layout) => Ok(Self::Volumes(Chain::new(layout))),
+            None => File::open(path).map(Self::File),
+        }
+    }
+
+    pub fn size(&self) -> std::io::Result<u64> {
+        match self {
+            Self::File(file) => Ok(file.metadata()?.len()),
+            Self::Volumes(chain) => Ok(chain.layout.len),
+        }
+    }
+
+    pub fn modified(&self) -> Option<SystemTime> {
+        match self {
+            Self::File(file) => file.metadata().ok()?.modified().ok(),
+            Self::Volumes(chain) => chain.layout.modified,
+        }
+    }
+}
+
+impl Read for Source {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::File(file) => file.read(buf),
+            Self::Volumes(chain) => chain.read(buf),
+        }
+    }
+}
+
+impl Seek for Source {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            Self::File(file) => file.seek(pos),
+            Self::Volumes(chain) => chain.seek(pos),
+        }
+    }
+}
+
+/// The volumes of a split archive read back to back.
+pub(super) struct Chain {
+    layout: Arc<Layout>,
+    pos: u64,
+    /// The volume last read from, by index.
+    open: Option<(usize, File)>,
+}
+
+impl Chain {
+    fn new(layout: Arc<Layout>) -> Self {
+        Chain {
+            layout,
+            pos: 0,
+            open: None,
+        }
+    }
+}
+
+impl Read for Chain {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let layout = self.layout.clone();
+        if buf.is_empty() || self.pos >= layout.len {
+            return Ok(0);
+        }
+        let pos = self.pos;
+        if let Some((start, bytes)) = layout
+            .patches
+            .iter()
+            .find(|(start, bytes)| (*start..start + bytes.len() as u64).contains(&pos))
+        {
+            let from = (pos - start) as usize;
+            let n = (bytes.len() - from).min(buf.len());
+            buf[..n].copy_from_slice(&bytes[from..from + n]);
+            self.pos += n as u64;
+            return Ok(n);
+        }
+
+        // The last volume starting at or before `pos`; empty ones are skipped.
+        let index = layout.volumes.partition_point(|(_, start)| *start <= pos) - 1;
+        let (volume, start) = &layout.volumes[index];
+        let end = layout
+            .volumes
+            .get(index + 1)
+            .map_or(layout.len, |(_, next)| *next)
+            .min(layout.patches.iter().map(|(at, _)| *at).filter(|&at| at > pos).min().unwrap_or(u64::MAX));
+        if self.open.as_ref().map(|(open, _)| *open) != Some(index) {
+            self.open = Some((index, File::open(volume)?));
+        }
+        let Some((_, file)) = self.open.as_mut() else {
+            unreachable!("opened above");
+        };
+        file.seek(SeekFrom::Start(pos - start))?;
+        let want = (end - pos).min(buf.len() as u64) as usize;
+        let n = file.read(&mut buf[..want])?;
+        if n == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                format!("Volume '{}' is shorter than expected", volume.display()),
+            ));
+        }
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for Chain {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(delta) => self.layout.len.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        };
+        self.pos = target.ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "Seek before the start of the archive")
+        })?;
+        Ok(self.pos)
+    }
+}
+
+// =======================
+// Spanned zip
+// =======================
+
+/// Locate the end of central directory record; returns its offset and the
+/// fixed 22-byte part.
+fn find_eocd<R: Read + Seek>(file: &mut R) -> Result<(u64, [u8; 22])> {
+    let len = file.seek(SeekFrom::End(0))?;
+    let start = len.saturating_sub(MAX_EOCD_SEARCH);
+    let mut tail = Vec::new();
+    file.seek(SeekFrom::Start(start))?;
+    file.read_to_end(&mut tail)?;
+
+    let pos = tail
+        .windows(4)
+        .rposition(|w| w == b"PK\x05\x06")
+        .filter(|&pos| pos + 22 <= tail.len())
+        .ok_or_else(|| anyhow!("Zip end of central directory not found"))?;
+    let mut eocd = [0u8; 22];
+    eocd.copy_from_slice(&tail[pos..pos + 22]);
+    Ok((start + pos as u64, eocd))
+}
+
+/// The central directory and end record of a spanned zip read as one file,
+/// with per-volume offsets rebased to offsets in the whole and everything
+/// marked as on disk 0, each with its position.
+fn rebase_zip<R: Read + Seek>(file: &mut R, starts: &[u64]) -> Result<Vec<(u64, Vec<u8>)>> {
+    let zip64 = || anyhow!("Split zip64 archives are not supported");
+    let u16_at = |b: &[u8], i: usize| u16::from_le_bytes([b[i], b[i + 1]]);
+    let u32_at = |b: &[u8], i: usize| u32::from_le_bytes([b[i], b[i + 1], b[i + 2], b[i + 3]]);
+    let start = |disk: u16| {
+        starts
+            .get(disk as usize)
+            .copied()
+            .ok_or_else(|| anyhow!("Corrupt split zip: volume {} out of range", disk + 1))
+    };
+
+    let (eocd_pos, mut eocd) = find_eocd(file)?;
+    let total = u16_at(&eocd, 10);
+    let cd_size = u32_at(&eocd, 12);
+    let cd_offset = u32_at(&eocd, 16);
+    if total == 0xFFFF || cd_offset == 0xFFFF_FFFF || cd_size == 0xFFFF_FFFF {
+        return Err(zip64());
+    }
+    let cd_start = start(u16_at(&eocd, 6))? + cd_offset as u64;
+    let cd_start32 = u32::try_from(cd_start).map_err(|_| zip64())?;
+
+    let mut cd = vec![0u8; cd_size as usize];
+    file.seek(SeekFrom::Start(cd_start))?;
+    file.read_exact(&mut cd)
+        .context("Corrupt split zip: central directory is truncated")?;
+
+    let mut pos = 0;
+    for _ in 0..total {
+        let record = cd
+            .get_mut(pos..pos + 46)
+            .filter(|r| r.starts_with(b"PK\x01\x02"))
+            .ok_or_else(|| anyhow!("Corrupt split zip: bad central directory record"))?;
+        let disk = u16_at(record, 34);
+        let relative = u32_at(record, 42);
+        if disk == 0xFFFF || relative == 0xFFFF_FFFF {
+            return Err(zip64());
+        }
+        let absolute = u32::try_from(start(disk)? + relative as u64).map_err(|_| zip64())?;
+        record[34..36].copy_from_slice(&0u16.to_le_bytes());
+        record[42..46].copy_from_slice(&absolute.to_le_bytes());
+        let variable = u16_at(record, 28) as usize + u16_at(record, 30) as usize + u16_at(record, 32) as usize;
+        pos += 46 + variable;
+    }
+
+    eocd[4..8].copy_from_slice(&[0; 4]);
+    eocd[8..10].copy_from_slice(&total.to_le_bytes());
+    eocd[16..20].copy_from_slice(&cd_start32.to_le_bytes());
+    Ok(vec![(cd_start, cd), (eocd_pos, eocd.to_vec())])
+}
+
+// =======================
+// Splitting
+// =======================
+
+/// Reject volume sizes too small to be useful before any work is done.
+pub(super) fn check_split_size(size: u64) -> Result<()> {
+    if size < MIN_SPLIT_SIZE {
+        return Err(anyhow!("Split size must be at least {} KiB", MIN_SPLIT_SIZE / 1024));
+    }
+    Ok(())
+}
+
+/// Split the finished archive in `source` into `<destination>.001`, `.002`,
+/// ... of `size` bytes each. Returns the volume paths.
+pub(super) fn split(source: &mut File, destination: &str, size: u64) -> Result<Vec<String>> {
+    check_split_size(size)?;
+    let total = source.metadata()?.len();
+    let count = total.div_ceil(size).max(1);
+    if count > MAX_SPLIT_VOLUMES {
+        return Err(anyhow!(
+            "Split size too small: {} volumes needed, at most {} allowed",
+            count,
+            MAX_SPLIT_VOLUMES
+        ));
+    }
+
+    let names: Vec<String> = (1..=count).map(|n| format!("{}.{:03}", destination, n)).collect();
+    if let Some(existing) = names.iter().find(|n| Path::new(n).exists()) {
+        return Err(anyhow!("Destination already exists: {}", existing));
+    }
+    let dir = match Path::new(destination).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    source.seek(SeekFrom::Start(0))?;
+    for name in &names {
+        let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+        std::io::copy(&mut (&mut *source).take(size), tmp.as_file_mut())?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            tmp.as_file()
+                .set_permissions(std::fs::Permissions::from_mode(0o644))?;
+        }
+        tmp.persist(name)
+            .with_context(|| format!("Failed to write {}", name))?;
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+
+    fn touch(dir: &Path, name: &str, content: &[u8]) -> String {
+        let path = dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn names(set: &VolumeSet) -> Vec<String> {
+        set.volumes
+            .iter()
+            .map(|v| v.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    fn zip_of(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        for (name, content) in files {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(content).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    /// Cut a zip in two at `at`, a local header offset, and record offsets
+    /// per volume the way spanning zippers do.
+    fn span(zip: &[u8], at: usize) -> (Vec<u8>, Vec<u8>) {
+        let (first, mut last) = (zip[..at].to_vec(), zip[at..].to_vec());
+        let u16_at = |b: &[u8], i: usize| u16::from_le_bytes([b[i], b[i + 1]]) as usize;
+        let u32_at = |b: &[u8], i: usize| u32::from_le_bytes([b[i], b[i + 1], b[i + 2], b[i + 3]]) as usize;
+        let eocd = last.windows(4).rposition(|w| w == b"PK\x05\x06").unwrap();
+        let total = u16_at(&last, eocd + 10);
+        let cd = u32_at(&last, eocd + 16) - at;
+        let mut pos = cd;
+        for _ in 0..total {
+            let offset = u32_at(&last, pos + 42);
+            if offset >= at {
+                last[pos + 34..pos + 36].copy_from_slice(&1u16.to_le_bytes());
+                last[pos + 42..pos + 46].copy_from_slice(&((offset - at) as u32).to_le_bytes());
+            }
+            pos += 46 + u16_at(&last, pos + 28) + u16_at(&last, pos + 30) + u16_at(&last, pos + 32);
+        }
+        last[eocd + 4..eocd + 8].copy_from_slice(&[1, 0, 1, 0]);
+        last[eocd + 8..eocd + 10].copy_from_slice(&0u16.to_le_bytes());
+        last[eocd + 16..eocd + 20].copy_from_slice(&(cd as u32).to_le_bytes());
+        (first, last)
+    }
+
+    #[test]
+    fn numbers_volumes() {
+        let numbered = Naming::Numbered { width: 3 };
+        assert_eq!(volume_suffix(&numbered, 7), "007");
+        assert_eq!(suffix_number(&numbered, "012"), Some(12));
+        assert_eq!(suffix_number(&numbered, "12"), None);
+        let lettered = Naming::Lettered { width: 2 };
+        assert_eq!(suffix_number(&lettered, "aa"), Some(0));
+        assert_eq!(suffix_number(&lettered, "ba"), Some(26));
+        assert_eq!(suffix_number(&lettered, "aB"), None);
+        for n in [0, 1, 25, 26, 675] {
+            assert_eq!(suffix_number(&lettered, &volume_suffix(&lettered, n)), Some(n));
+        }
+        assert_eq!(volume_suffix(&Naming::Zip, 1), "z01");
+        assert_eq!(volume_suffix(&Naming::Zip, 12), "z12");
+    }
+
+    #[test]
+    fn finds_numbered_sets_from_any_volume() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "a.7z.001", b"7z");
+        let middle = touch(dir.path(), "a.7z.002", b"");
+        touch(dir.path(), "a.7z.003", b"");
+        touch(dir.path(), "a.7z.03", b"");
+        let set = find(&middle).unwrap().unwrap();
+        assert_eq!(set.name, "a.7z");
+        assert_eq!(names(&set), ["a.7z.001", "a.7z.002", "a.7z.003"]);
+
+        // `split -d` counts from zero.
+        let second = touch(dir.path(), "b.tar.01", b"");
+        touch(dir.path(), "b.tar.00", b"");
+        let set = find(&second).unwrap().unwrap();
+        assert_eq!(names(&set), ["b.tar.00", "b.tar.01"]);
+    }
+
+    #[test]
+    fn finds_lettered_sets() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "c.tar.gz.aa", b"");
+        let last = touch(dir.path(), "c.tar.gz.ac", b"");
+        touch(dir.path(), "c.tar.gz.ab", b"");
+        let set = find(&last).unwrap().unwrap();
+        assert_eq!(set.name, "c.tar.gz");
+        assert_eq!(names(&set), ["c.tar.gz.aa", "c.tar.gz.ab", "c.tar.gz.ac"]);
+    }
+
+    #[test]
+    fn ignores_files_that_are_not_volumes() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["notes.001", "d.tar.gz", "e.zip", "f.zip.ab", ".001"] {
+            let path = touch(dir.path(), name, b"plain text");
+            assert!(find(&path).unwrap().is_none(), "{name}");
+        }
+    }
+
+    #[test]
+    fn reports_missing_volumes() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "g.7z.001", b"");
+        let last = touch(dir.path(), "g.7z.003", b"");
+        let error = find(&last).err().unwrap();
+        assert!(matches!(
+            error.downcast_ref::<ArchiveError>(),
+            Some(ArchiveError::MissingVolume(name)) if name == "g.7z.002"
+        ));
+
+        touch(dir.path(), "h.z01", b"");
+        assert!(find(&dir.path().join("h.z01").to_string_lossy()).is_err());
+    }
+
+    #[test]
+    fn mounts_spanned_zips() {
+        let zip = zip_of(&[("a.txt", b"first volume"), ("b.txt", b"second volume")]);
+        let at = 30 + "a.txt".len() + "first volume".len();
+        assert_eq!(&zip[at..at + 4], b"PK\x03\x04");
+        let (first, last) = span(&zip, at);
+
+        let dir = tempfile::tempdir().unwrap();
+        let z01 = touch(dir.path(), "s.Z01", &first);
+        touch(dir.path(), "s.ZIP", &last);
+        let set = find(&z01).unwrap().unwrap();
+        assert_eq!(set.name, "s.zip");
+        assert_eq!(names(&set), ["s.Z01", "s.ZIP"]);
+
+        let mount = set.mount().unwrap();
+        let mut whole = Vec::new();
+        Source::open(mount.path()).unwrap().read_to_end(&mut whole).unwrap();
+        assert_eq!(whole, zip);
+        let mut archive = zip::ZipArchive::new(Source::open(mount.path()).unwrap()).unwrap();
+        let mut content = String::new();
+        archive.by_name("b.txt").unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content, "second volume");
+
+        let path = mount.path().to_path_buf();
+        drop(mount);
+        assert!(Source::open(&path).is_err());
+    }
+
+    #[test]
+    fn reads_across_volumes() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = touch(dir.path(), "h.tar.001", b"abc");
+        touch(dir.path(), "h.tar.002", b"");
+        touch(dir.path(), "h.tar.003", b"defg");
+        let mount = find(&first).unwrap().unwrap().mount().unwrap();
+        assert_eq!(first_volume(&mount.path().to_string_lossy()), first);
+        assert_eq!(mount.path().file_name().unwrap(), "h.tar");
+
+        let mut source = Source::open(mount.path()).unwrap();
+        assert_eq!(source.size().unwrap(), 7);
+        let mut all = String::new();
+        source.read_to_string(&mut all).unwrap();
+        assert_eq!(all, "abcdefg");
+
+        source.seek(SeekFrom::Start(2)).unwrap();
+        let mut middle = [0u8; 3];
+        source.read_exact(&mut middle).unwrap();
+        assert_eq!(&middle, b"cde");
+        assert_eq!(source.seek(SeekFrom::End(-1)).unwrap(), 6);
+        assert!(source.seek(SeekFrom::Current(-7)).is_err());
+
+        // A volume that shrank after mounting is an error, not a short read.
+        std::fs::write(dir.path().join("h.tar.003"), b"d").unwrap();
+        source.seek(SeekFrom::Start(0)).unwrap();
+        assert!(source.read_to_end(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn rejects_out_of_range_volumes() {
+        let zip = zip_of(&[("a.txt", b"x")]);
+        let (_, mut last) = span(&zip, 0);
+        // The central directory claims to start on a third volume.
+        let eocd = last.windows(4).rposition(|w| w == b"PK\x05\x06").unwrap();
+        last[eocd + 6..eocd + 8].copy_from_slice(&2u16.to_le_bytes());
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(&last).unwrap();
+        assert!(rebase_zip(&mut file, &[0, 0]).is_err());
+    }
+}
@@ -1,4 +1,6 @@
 use crate::protocol::*;
+use crate::archive::{ArchiveError, ArchiveOptions, ArchiveSearchLimits};
+use crate::search::{Candidate, Kind, NameMatches, SearchQuery};
 use anyhow::{Context, Result};
 use chrono::Utc;
 use std::fs;
@@ -20,19 +22,23 @@ use winreg::RegKey;
 // Move winapi imports inside the function where they're used
 // or conditionally import them only when needed
 
+/// Matches returned by a fuzzy SEARCH_FILES without a `limit`.
+const DEFAULT_FUZZY_LIMIT: usize = 100;
+
 pub struct CommandExecutor;
 
 impl CommandExecutor {
     pub fn execute(command: Command) -> Response {
         let command_id = command.id().to_string();
-        let timestamp = Utc::now().timestamp();
 
         let result = match command {
-            Command::ListDirectory { path, show_hidden, .. } => {
-                Self::list_directory(&path, show_hidden)
+            Command::ListDirectory { path, show_hidden, password, .. } => {
+                let opts = ArchiveOptions { password, ..Default::default() };
+                Self::list_directory(&path, show_hidden, &opts)
             }
-            Command::ReadFile { path, encoding, .. } => {
-                Self::read_file(&path, encoding.as_deref())
+            Command::ReadFile { path, encoding, password, .. } => {
+                let opts = ArchiveOptions { password, ..Default::default() };
+                Self::read_file(&path, encoding.as_deref(), &opts)
             }
             Command::WriteFile { path, content, encoding, .. } => {
                 Self::write_file(&path, &content, encoding.as_deref())
@@ -46,14 +52,38 @@ impl CommandExecutor {
             Command::MoveFile { source, destination, .. } => {
                 Self::move_file(&source, &destination)
             }
-            Command::CopyFile { source, destination, recursive, .. } => {
-                Self::copy_file(&source, &destination, recursive)
+            Command::CopyFile { source, destination, recursive, password, .. } => {
+                let opts = ArchiveOptions { password, ..Default::default() };
+                Self::copy_file(&source, &destination, recursive, &opts)
             }
-            Command::GetFileInfo { path, .. } => {
-                Self::get_file_info(&path)
+            Command::GetFileInfo { path, password, .. } => {
+                let opts = ArchiveOptions { password, ..Default::default() };
+                Self::get_file_info(&path, &opts)
             }
-            Command::SearchFiles { path, pattern, recursive, .. } => {
-                Self::search_files(&path, &pattern, recursive)
+            Command::SearchContent { path, query, options, .. } => {
+                Self::search_content(&path, &query, &options, None)
+            }
+            Command::SearchFiles {
+                path,
+                pattern,
+                recursive,
+                password,
+                include_archives,
+                archive_depth,
+                max_archive_size,
+                fuzzy,
+                recent_paths,
+                limit,
+                ..
+            } => {
+                let opts = ArchiveOptions { password, recursive, ..Default::default() };
+                let defaults = ArchiveSearchLimits::default();
+                let limits = include_archives.then(|| ArchiveSearchLimits {
+                    max_depth: archive_depth.unwrap_or(defaults.max_depth),
+                    max_size: max_archive_size.unwrap_or(defaults.max_size),
+                });
+                let ranking = fuzzy.then(|| (recent_paths.as_slice(), limit.unwrap_or(DEFAULT_FUZZY_LIMIT)));
+                Self::search_files(&path, &pattern, &opts, limits.as_ref(), ranking)
             }
             Command::ListDrives { .. } => {
                 Self::list_drives()
@@ -61,17 +91,80 @@ impl CommandExecutor {
             Command::GetOsInfo { .. } => {
                 Self::get_os_info()
             }
-            Command::ListArchive { archive_path, inner_path, .. } => {
-                Self::list_archive(&archive_path, &inner_path)
+            Command::ListArchive { archive_path, inner_path, password, recursive, name_encoding, .. } => {
+                let opts = ArchiveOptions { password, recursive, name_encoding };
+                Self::list_archive(&archive_path, &inner_path, &opts)
+            }
+            Command::ReadArchiveFile { archive_path, inner_path, encoding, password, name_encoding, .. } => {
+                let opts = ArchiveOptions { password, name_encoding, ..Default::default() };
+                Self::read_archive_file(&archive_path, &inner_path, encoding.as_deref(), &opts)
+            }
+            Command::ExtractArchive { archive_path, destination, inner_paths, password, name_encoding, .. } => {
+                let opts = ArchiveOptions { password, name_encoding, ..Default::default() };
+                Self::extract_archive(&archive_path, &destination, &inner_paths, &opts)
+            }
+            Command::CreateArchive { sources, destination, format, options, .. } => {
+                Self::create_archive(sources, destination, &format, options)
+            }
+            Command::ArchiveAdd { archive_path, inner_path, sources, options, .. } => {
+                Self::archive_add(archive_path, inner_path, sources, options)
+            }
+            Command::ArchiveDelete { archive_path, inner_paths, .. } => {
+                Self::archive_delete(archive_path, inner_paths)
+            }
+            Command::ArchiveRename { archive_path, inner_path, new_inner_path, .. } => {
+                Self::archive_rename(archive_path, inner_path, new_inner_path)
+            }
+            Command::TestArchive { archive_path, password, .. } => {
+                let opts = ArchiveOptions { password, ..Default::default() };
+                Self::test_archive(archive_path, opts)
+            }
+            Command::RepackArchive { archive_path, destination, format, password, options, .. } => {
+                let opts = ArchiveOptions { password, ..Default::default() };
+                Self::repack_archive(archive_path, destination, &format, opts, options)
+            }
+            Command::GetJobStatus { job_id, .. } => {
+                crate::jobs::status(&job_id).map(ResponseData::Job)
+            }
+            Command::CancelJob { job_id, .. } => {
+                crate::jobs::cancel(&job_id).map(ResponseData::Job)
+            }
+            Command::IndexStatus { .. } => {
+                Ok(ResponseData::IndexStatus(crate::search::index::status()))
+            }
+            Command::Reindex { roots, exclude, .. } => {
+                let roots = roots.map(|roots| roots.iter().map(|r| Self::resolve_path(r)).collect());
+                crate::search::index::reindex(roots, exclude).map(ResponseData::Job)
             }
-            Command::ReadArchiveFile { archive_path, inner_path, encoding, .. } => {
-                Self::read_archive_file(&archive_path, &inner_path, encoding.as_deref())
+            Command::QueryIndex { query, path, limit, .. } => {
+                let path = path.map(|p| Self::resolve_path(&p));
+                let limit = limit.unwrap_or(crate::search::index::DEFAULT_QUERY_LIMIT);
+                crate::search::index::query(&query, path.as_deref(), limit).map(ResponseData::SearchResult)
             }
-            Command::ExtractArchive { archive_path, destination, inner_paths, .. } => {
-                Self::extract_archive(&archive_path, &destination, &inner_paths)
+            Command::CompletePath { path, cwd, limit, .. } => {
+                let cwd = cwd.map(|c| Self::resolve_path(&c));
+                let limit = limit.unwrap_or(crate::search::complete::DEFAULT_COMPLETION_LIMIT);
+                crate::search::complete::complete_path(&path, cwd.as_deref(), limit).map(ResponseData::PathCompletions)
             }
         };
+        Self::respond(command_id, result)
+    }
 
+    /// Like `execute`, but SEARCH_CONTENT hands each file's matches to `emit`
+    /// as it finds them and answers with the totals only. `emit` returning
+    /// false stops the search.
+    pub fn execute_streamed(command: Command, emit: &mut dyn FnMut(ContentMatches) -> bool) -> Response {
+        match command {
+            Command::SearchContent { id, path, query, options, .. } => {
+                let result = Self::search_content(&path, &query, &options, Some(emit));
+                Self::respond(id, result)
+            }
+            command => Self::execute(command),
+        }
+    }
+
+    fn respond(command_id: String, result: Result<ResponseData>) -> Response {
+        let timestamp = Utc::now().timestamp();
         match result {
             Ok(data) => Response::Success {
                 command_id,
@@ -82,7 +175,10 @@ impl CommandExecutor {
                 command_id,
                 timestamp,
                 error: ErrorInfo {
-                    code: "EXECUTION_ERROR".to_string(),
+                    code: e
+                        .downcast_ref::<ArchiveError>()
+                        .map_or("EXECUTION_ERROR", ArchiveError::code)
+                        .to_string(),
                     message: e.to_string(),
                     details: None,
                 },
@@ -441,8 +537,11 @@ impl CommandExecutor {
     // Directory / File operations
     // -------------------------------------------------------------------------
 
-    fn list_directory(path: &str, show_hidden: bool) -> Result<ResponseData> {
+    fn list_directory(path: &str, show_hidden: bool, opts: &ArchiveOptions) -> Result<ResponseData> {
         let path = Self::resolve_path(path);
+        if let Some((archive, inner)) = crate::archive::split_virtual_path(&path) {
+            return Self::list_archive_directory(&path, &archive, &inner, show_hidden, opts);
+        }
         let path_buf = Path::new(&path);
 
         if !path_buf.exists() {
@@ -470,6 +569,17 @@ impl CommandExecutor {
             entries.push(file_info);
         }
 
+        Self::sort_entries(&mut entries);
+
+        Ok(ResponseData::DirectoryListing(DirectoryListing {
+            path,
+            entries,
+            total_size,
+        }))
+    }
+
+    /// Directories first, then symlinks, then files; by name within each.
+    fn sort_entries(entries: &mut [FileInfo]) {
         entries.sort_by_key(|e| {
             let type_rank = match e.file_type {
                 FileType::Directory => 0u8,
@@ -478,16 +588,26 @@ impl CommandExecutor {
             };
             (type_rank, e.name.to_lowercase(), e.name.clone())
         });
-
-        Ok(ResponseData::DirectoryListing(DirectoryListing {
-            path,
-            entries,
-            total_size,
-        }))
     }
 
-    fn read_file(path: &str, encoding: Option<&str>) -> Result<ResponseData> {
+    fn read_file(path: &str, encoding: Option<&str>, opts: &ArchiveOptions) -> Result<ResponseData> {
         let path = Self::resolve_path(path);
+        if let Some((archive, inner)) = crate::archive::split_virtual_path(&path) {
+            let bytes = crate::archive::read_archive_file(&archive, &inner, opts)?;
+            let size = bytes.len() as u64;
+            let encoding = encoding.unwrap_or("utf8");
+            let content = match encoding {
+                "utf8" => String::from_utf8(bytes).context("File is not valid UTF-8")?,
+                "base64" => BASE64_ENGINE.encode(&bytes),
+                _ => anyhow::bail!("Unsupported encoding: {}", encoding),
+            };
+            return Ok(ResponseData::FileContent(FileContent {
+                path,
+                content,
+                encoding: encoding.to_string(),
+                size,
+            }));
+        }
         let path_buf = Path::new(&path);
 
         if !path_buf.exists() {
@@ -597,17 +717,25 @@ impl CommandExecutor {
         }))
     }
 
-    fn copy_file(source: &str, destination: &str, recursive: bool) -> Result<ResponseData> {
+    fn copy_file(
+        source: &str,
+        destination: &str,
+        recursive: bool,
+        opts: &ArchiveOptions,
+    ) -> Result<ResponseData> {
         let source = Self::resolve_path(source);
         let destination = Self::resolve_path(destination);
         let source_buf = Path::new(&source);
         let dest_buf = Path::new(&destination);
 
-        if !source_buf.exists() {
-            anyhow::bail!("Source does not exist: {}", source);
+        if crate::archive::split_virtual_path(&destination).is_some() {
+            anyhow::bail!("Cannot copy into an archive; use ARCHIVE_ADD instead");
         }
-
-        if source_buf.is_dir() {
+        if let Some((archive, inner)) = crate::archive::split_virtual_path(&source) {
+            crate::archive::copy_out(&archive, &inner, &destination, recursive, opts)?;
+        } else if !source_buf.exists() {
+            anyhow::bail!("Source does not exist: {}", source);
+        } else if source_buf.is_dir() {
             if !recursive {
                 anyhow::bail!("Cannot copy directory without recursive flag");
             }
@@ -639,8 +767,23 @@ impl CommandExecutor {
         Ok(())
     }
 
-    fn get_file_info(path: &str) -> Result<ResponseData> {
+    fn get_file_info(path: &str, opts: &ArchiveOptions) -> Result<ResponseData> {
         let path = Self::resolve_path(path);
+        if let Some((archive, inner)) = crate::archive::split_virtual_path(&path) {
+            let file_info = if inner.trim_matches('/').is_empty() {
+                // The archive's root: the archive file itself, as a folder.
+                let archive_buf = Path::new(&archive);
+                let name = archive_buf.file_name().unwrap_or_default().to_string_lossy();
+                let mut info = Self::metadata_to_file_info(&name, archive_buf, &fs::metadata(archive_buf)?)?;
+                info.path = path;
+                info.file_type = FileType::Directory;
+                info
+            } else {
+                let entry = crate::archive::archive_entry(&archive, &inner, opts)?;
+                Self::archive_entry_to_file_info(&archive, &entry)
+            };
+            return Ok(ResponseData::FileInfo(file_info));
+        }
         let path_buf = Path::new(&path);
 
         if !path_buf.exists() {
@@ -658,54 +801,203 @@ impl CommandExecutor {
         Ok(ResponseData::FileInfo(file_info))
     }
 
-    fn search_files(path: &str, pattern: &str, recursive: bool) -> Result<ResponseData> {
+    /// Search file contents. Without `emit` every match is collected into
+    /// the response.
+    fn search_content(
+        path: &str,
+        query: &str,
+        options: &ContentSearchOptions,
+        emit: Option<&mut dyn FnMut(ContentMatches) -> bool>,
+    ) -> Result<ResponseData> {
+        let path = Self::resolve_path(path);
+        let mut files = Vec::new();
+        let result = match emit {
+            Some(emit) => crate::search::search_content(&path, query, options, emit)?,
+            None => crate::search::search_content(&path, query, options, &mut |file| {
+                files.push(file);
+                true
+            })?,
+        };
+        Ok(ResponseData::ContentSearchResult(ContentSearchResult { files, ..result }))
+    }
+
+    /// Find files whose names and properties match a query (see
+    /// `search::SearchQuery`), filtering while walking. `ranking` (recent
+    /// paths and a limit) turns on fuzzy ranking.
+    fn search_files(
+        path: &str,
+        pattern: &str,
+        opts: &ArchiveOptions,
+        archives: Option<&ArchiveSearchLimits>,
+        ranking: Option<(&[String], usize)>,
+    ) -> Result<ResponseData> {
         let path = Self::resolve_path(path);
         let path_buf = Path::new(&path);
+        let mut matches = NameMatches::new(SearchQuery::parse_or_literal(pattern), ranking.map(|(recent, _)| recent));
+        let limit = ranking.map_or(usize::MAX, |(_, limit)| limit);
+        let query_depth = matches.query().max_depth();
+        let max_depth = match opts.recursive {
+            true => query_depth.unwrap_or(usize::MAX),
+            false => query_depth.unwrap_or(1).min(1),
+        };
+
+        if let Some((archive, inner)) = crate::archive::split_virtual_path(&path) {
+            let prefix = inner.trim_matches('/');
+            let mut visit = |e: &ArchiveEntry| {
+                let inner_path = e.inner_path.trim_matches('/');
+                let relative = inner_path
+                    .strip_prefix(prefix)
+                    .map_or(inner_path, |rest| rest.trim_start_matches('/'));
+                let candidate = Self::archive_candidate(e, relative, Self::path_depth(relative));
+                let _ = matches.consider(&candidate, || Ok(Self::archive_entry_to_file_info(&archive, e)));
+            };
+            match archives {
+                Some(limits) if opts.recursive => {
+                    crate::archive::walk_archive(&archive, &inner, limits, opts, &mut visit)?
+                }
+                _ => crate::archive::list_archive(&archive, &inner, opts)?
+                    .entries
+                    .iter()
+                    .for_each(visit),
+            }
+            return Ok(ResponseData::SearchResult(matches.finish(path, limit)));
+        }
 
         if !path_buf.exists() {
             anyhow::bail!("Path does not exist: {}", path);
         }
 
-        let pattern_lower = pattern.to_lowercase();
-        let mut matches = Vec::new();
-
-        let walker = if recursive {
-            WalkDir::new(path_buf)
-        } else {
-            WalkDir::new(path_buf).max_depth(1)
-        };
-
+        let walker = WalkDir::new(path_buf).min_depth(1).max_depth(max_depth);
         for entry in walker.into_iter().filter_map(|e| e.ok()) {
             let file_name = entry.file_name().to_string_lossy();
-            if file_name.to_lowercase().contains(&pattern_lower) {
-                if let Ok(metadata) = entry.metadata() {
-                    let file_info =
-                        Self::metadata_to_file_info(&file_name, entry.path(), &metadata)?;
-                    matches.push(file_info);
-                }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let relative = entry
+                .path()
+                .strip_prefix(path_buf)
+                .unwrap_or(entry.path())
+                .to_string_lossy()
+                .replace('\\', "/");
+            let candidate = Candidate::from_metadata(&file_name, &relative, entry.depth(), &metadata);
+            matches.consider(&candidate, || Self::metadata_to_file_info(&file_name, entry.path(), &metadata))?;
+
+            let Some(limits) = archives else {
+                continue;
+            };
+            if metadata.is_file()
+                && query_depth.is_none_or(|depth| entry.depth() < depth)
+                && metadata.len() <= limits.max_size
+                && crate::archive::is_archive_name(&file_name)
+            {
+                let archive = Self::normalize_path(entry.path());
+                // Archives that cannot be read (damaged, encrypted) are skipped.
+                let _ = crate::archive::walk_archive(&archive, "", limits, opts, &mut |e| {
+                    let inner_relative = format!("{}!/{}", relative, e.inner_path.trim_matches('/'));
+                    let depth = entry.depth() + Self::path_depth(&e.inner_path);
+                    let candidate = Self::archive_candidate(e, &inner_relative, depth);
+                    let _ = matches.consider(&candidate, || Ok(Self::archive_entry_to_file_info(&archive, e)));
+                });
             }
         }
 
-        let total_matches = matches.len();
+        Ok(ResponseData::SearchResult(matches.finish(path, limit)))
+    }
 
-        Ok(ResponseData::SearchResult(SearchResult {
-            path,
-            matches,
-            total_matches,
-        }))
+    /// The query view of an archive entry at `relative`, `depth` levels
+    /// below the search root.
+    fn archive_candidate<'a>(entry: &'a ArchiveEntry, relative: &'a str, depth: usize) -> Candidate<'a> {
+        let kind = match entry.entry_type {
+            ArchiveEntryType::Directory => Kind::Directory,
+            ArchiveEntryType::Symlink => Kind::Symlink,
+            ArchiveEntryType::File => Kind::File,
+        };
+        Candidate {
+            name: &entry.name,
+            relative,
+            depth,
+            kind,
+            size: entry.size,
+            modified: entry.modified,
+            mode: entry.mode,
+            uid: None,
+            gid: None,
+            owner: entry.owner.as_deref(),
+            group: entry.group.as_deref(),
+        }
+    }
+
+    fn path_depth(path: &str) -> usize {
+        path.split('/').filter(|s| !s.is_empty()).count()
     }
 
     // -------------------------------------------------------------------------
     // Archive operations
     // -------------------------------------------------------------------------
 
-    fn list_archive(archive_path: &str, inner_path: &str) -> Result<ResponseData> {
-        let listing = crate::archive::list_archive(archive_path, inner_path)?;
+    /// LIST_DIRECTORY for a virtual path inside an archive.
+    fn list_archive_directory(
+        path: &str,
+        archive: &str,
+        inner: &str,
+        show_hidden: bool,
+        opts: &ArchiveOptions,
+    ) -> Result<ResponseData> {
+        let listing = crate::archive::list_archive(archive, inner, opts)?;
+        let mut entries: Vec<FileInfo> = listing
+            .entries
+            .iter()
+            .filter(|e| show_hidden || !e.name.starts_with('.'))
+            .map(|e| Self::archive_entry_to_file_info(archive, e))
+            .collect();
+        Self::sort_entries(&mut entries);
+
+        Ok(ResponseData::DirectoryListing(DirectoryListing {
+            path: path.to_string(),
+            total_size: entries.iter().map(|e| e.size).sum(),
+            entries,
+        }))
+    }
+
+    /// Present an archive entry like a file on disk, addressed by its
+    /// virtual path. Archives only keep a modification time.
+    fn archive_entry_to_file_info(archive: &str, entry: &ArchiveEntry) -> FileInfo {
+        let file_type = match entry.entry_type {
+            ArchiveEntryType::Directory => FileType::Directory,
+            ArchiveEntryType::Symlink => FileType::Symlink,
+            ArchiveEntryType::File => FileType::File,
+        };
+        let permissions = match (entry.mode, &file_type) {
+            (Some(mode), _) => format!("{:o}", mode & 0o777),
+            (None, FileType::Directory) => "755".to_string(),
+            (None, _) => "644".to_string(),
+        };
+        FileInfo {
+            name: entry.name.clone(),
+            path: crate::archive::virtual_path(archive, &entry.inner_path),
+            file_type,
+            size: entry.size,
+            created: entry.modified,
+            modified: entry.modified,
+            accessed: entry.modified,
+            permissions,
+            is_hidden: entry.name.starts_with('.'),
+            archive_entry: Some(Box::new(entry.clone())),
+        }
+    }
+
+    fn list_archive(archive_path: &str, inner_path: &str, opts: &ArchiveOptions) -> Result<ResponseData> {
+        let listing = crate::archive::list_archive(archive_path, inner_path, opts)?;
         Ok(ResponseData::ArchiveListing(listing))
     }
 
-    fn read_archive_file(path: &str, inner: &str, encoding: Option<&str>) -> Result<ResponseData> {
-        let bytes = crate::archive::read_archive_file(path, inner)?;
+    fn read_archive_file(
+        path: &str,
+        inner: &str,
+        encoding: Option<&str>,
+        opts: &ArchiveOptions,
+    ) -> Result<ResponseData> {
+        let bytes = crate::archive::read_archive_file(path, inner, opts)?;
         let (content, enc) = match encoding {
             Some("base64") | None => (BASE64_ENGINE.encode(&bytes), "base64".to_string()),
             Some("utf8") => (String::from_utf8_lossy(&bytes).to_string(), "utf-8".to_string()),
@@ -719,8 +1011,13 @@ impl CommandExecutor {
         }))
     }
 
-    fn extract_archive(path: &str, destination: &str, inner_paths: &[String]) -> Result<ResponseData> {
-        let extracted = crate::archive::extract_archive(path, destination, inner_paths)?;
+    fn extract_archive(
+        path: &str,
+        destination: &str,
+        inner_paths: &[String],
+        opts: &ArchiveOptions,
+    ) -> Result<ResponseData> {
+        let extracted = crate::archive::extract_archive(path, destination, inner_paths, opts)?;
         Ok(ResponseData::OperationResult(OperationResult {
             success: true,
             message: Some(format!("Extracted {} entries", extracted.len())),
@@ -728,6 +1025,156 @@ impl CommandExecutor {
         }))
     }
 
+    fn create_archive(
+        sources: Vec<String>,
+        destination: String,
+        format: &str,
+        options: CreateArchiveOptions,
+    ) -> Result<ResponseData> {
+        let format = crate::archive::ArchiveFormat::from_name(format)
+            .ok_or_else(|| anyhow::anyhow!("Unsupported archive format: {}", format))?;
+        let sources: Vec<String> = sources.iter().map(|s| Self::resolve_path(s)).collect();
+        let destination = Self::resolve_path(&destination);
+
+        if Path::new(&destination).exists() {
+            anyhow::bail!("Destination already exists: {}", destination);
+        }
+
+        let job = crate::jobs::spawn("CREATE_ARCHIVE", move |job| {
+            let created =
+                crate::archive::create_archive(&sources, &destination, format, &options, job)?;
+            let volumes = match created.files.len() {
+                1 => String::new(),
+                n => format!(" in {} volumes", n),
+            };
+            Ok(ResponseData::OperationResult(OperationResult {
+                success: true,
+                message: Some(format!(
+                    "Created {} with {} entries{}",
+                    destination,
+                    created.entries.len(),
+                    volumes
+                )),
+                affected_paths: Some(created.files),
+            }))
+        });
+        Ok(ResponseData::Job(job))
+    }
+
+    fn archive_add(
+        archive_path: String,
+        inner_path: String,
+        sources: Vec<String>,
+        options: CreateArchiveOptions,
+    ) -> Result<ResponseData> {
+        let archive_path = Self::resolve_path(&archive_path);
+        let sources: Vec<String> = sources.iter().map(|s| Self::resolve_path(s)).collect();
+
+        let job = crate::jobs::spawn("ARCHIVE_ADD", move |job| {
+            let added =
+                crate::archive::add_to_archive(&archive_path, &inner_path, &sources, &options, job)?;
+            Ok(ResponseData::OperationResult(OperationResult {
+                success: true,
+                message: Some(format!("Added {} entries to {}", added.len(), archive_path)),
+                affected_paths: Some(added),
+            }))
+        });
+        Ok(ResponseData::Job(job))
+    }
+
+    fn archive_delete(archive_path: String, inner_paths: Vec<String>) -> Result<ResponseData> {
+        let archive_path = Self::resolve_path(&archive_path);
+
+        let job = crate::jobs::spawn("ARCHIVE_DELETE", move |job| {
+            let deleted = crate::archive::delete_from_archive(&archive_path, &inner_paths, job)?;
+            Ok(ResponseData::OperationResult(OperationResult {
+                success: true,
+                message: Some(format!("Deleted {} entries from {}", deleted.len(), archive_path)),
+                affected_paths: Some(deleted),
+            }))
+        });
+        Ok(ResponseData::Job(job))
+    }
+
+    fn test_archive(archive_path: String, opts: ArchiveOptions) -> Result<ResponseData> {
+        let archive_path = Self::resolve_path(&archive_path);
+        if !Path::new(&archive_path).exists() {
+            anyhow::bail!("Archive not found: {}", archive_path);
+        }
+
+        let job = crate::jobs::spawn("TEST_ARCHIVE", move |job| {
+            let report = crate::archive::test_archive(&archive_path, &opts, job)?;
+            Ok(ResponseData::ArchiveTestReport(report))
+        });
+        Ok(ResponseData::Job(job))
+    }
+
+    fn repack_archive(
+        archive_path: String,
+        destination: String,
+        format: &str,
+        opts: ArchiveOptions,
+        options: CreateArchiveOptions,
+    ) -> Result<ResponseData> {
+        let format = crate::archive::ArchiveFormat::from_name(format)
+            .ok_or_else(|| anyhow::anyhow!("Unsupported archive format: {}", format))?;
+        let archive_path = Self::resolve_path(&archive_path);
+        let destination = Self::resolve_path(&destination);
+
+        if !Path::new(&archive_path).exists() {
+            anyhow::bail!("Archive not found: {}", archive_path);
+        }
+        if Path::new(&destination).exists() {
+            anyhow::bail!("Destination already exists: {}", destination);
+        }
+
+        let job = crate::jobs::spawn("REPACK_ARCHIVE", move |job| {
+            let repacked = crate::archive::repack_archive(
+                &archive_path,
+                &destination,
+                format,
+                &opts,
+                &options,
+                job,
+            )?;
+            let skipped = match repacked.skipped.len() {
+                0 => String::new(),
+                n => format!(" ({} symbolic links skipped)", n),
+            };
+            Ok(ResponseData::OperationResult(OperationResult {
+                success: true,
+                message: Some(format!(
+                    "Repacked {} into {} with {} entries{}",
+                    archive_path,
+                    destination,
+                    repacked.entries.len(),
+                    skipped
+                )),
+                affected_paths: Some(repacked.files),
+            }))
+        });
+        Ok(ResponseData::Job(job))
+    }
+
+    fn archive_rename(
+        archive_path: String,
+        inner_path: String,
+        new_inner_path: String,
+    ) -> Result<ResponseData> {
+        let archive_path = Self::resolve_path(&archive_path);
+
+        let job = crate::jobs::spawn("ARCHIVE_RENAME", move |job| {
+            let renamed =
+                crate::archive::rename_in_archive(&archive_path, &inner_path, &new_inner_path, job)?;
+            Ok(ResponseData::OperationResult(OperationResult {
+                success: true,
+                message: Some(format!("Renamed {} to {}", inner_path, new_inner_path)),
+                affected_paths: Some(renamed),
+            }))
+        });
+        Ok(ResponseData::Job(job))
+    }
+
     // -------------------------------------------------------------------------
     // Metadata helpers
     // -------------------------------------------------------------------------
@@ -791,6 +1238,7 @@ impl CommandExecutor {
             accessed,
             permissions,
             is_hidden,
+            archive_entry: None,
         })
     }
 
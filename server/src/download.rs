@@ -0,0 +1,338 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::{Query, RawQuery},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::Response as AxumResponse,
+};
+use serde::Deserialize;
+use std::io::{self, BufWriter, Write};
+use tokio::sync::mpsc;
+
+use crate::archive::{ArchiveError, ArchiveFormat, ArchiveOptions};
+use crate::handlers::ApiError;
+use crate::protocol::CreateArchiveOptions;
+
+/// Size of the chunks handed to the response body.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Chunks buffered between the blocking producer and the client.
+const CHUNKS_IN_FLIGHT: usize = 8;
+
+// =======================
+// Archive entries
+// =======================
+
+#[derive(Debug, Deserialize)]
+pub struct EntryQuery {
+    archive_path: String,
+    /// Path of the file inside the archive; may cross nested archives (`a.jar!/b`).
+    inner_path: String,
+    #[serde(default)]
+    password: Option<String>,
+    #[serde(default)]
+    name_encoding: Option<String>,
+    /// Send as an attachment instead of inline.
+    #[serde(default)]
+    download: bool,
+}
+
+/// `GET /api/archive/entry`: stream one file's decompressed bytes. Stored
+/// zip entries also answer single `Range` requests, so media inside an
+/// archive can be seeked.
+pub async fn archive_entry(
+    Query(query): Query<EntryQuery>,
+    headers: HeaderMap,
+) -> Result<AxumResponse, ApiError> {
+    tracing::info!("Streaming archive entry: {} (inner: {})", query.archive_path, query.inner_path);
+    let opts = ArchiveOptions {
+        password: query.password,
+        name_encoding: query.name_encoding,
+        ..Default::default()
+    };
+    let (archive_path, inner_path) = (query.archive_path, query.inner_path);
+    let source = tokio::task::spawn_blocking(move || {
+        crate::archive::open_entry(&archive_path, &inner_path, &opts)
+    })
+    .await
+    .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .map_err(archive_error)?;
+
+    let name = source.entry.name.clone();
+    let mime = mime_guess::from_path(&name).first_or_octet_stream();
+    let mut response = AxumResponse::builder()
+        .header(header::CONTENT_TYPE, mime.as_ref())
+        .header(header::CONTENT_DISPOSITION, content_disposition(&name, query.download))
+        .header(header::ACCEPT_RANGES, if source.seekable() { "bytes" } else { "none" });
+
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .filter(|_| source.seekable());
+    if let (Some(range), Some(size)) = (range, source.size()) {
+        match parse_range(range, size) {
+            Ok(Some((start, end))) => {
+                let len = end - start + 1;
+                response = response
+                    .status(StatusCode::PARTIAL_CONTENT)
+                    .header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, size))
+                    .header(header::CONTENT_LENGTH, len);
+                let body = stream_body(move |out| source.copy_range(start, len, out).map(drop));
+                return response.body(body).map_err(internal);
+            }
+            Ok(None) => {}
+            Err(()) => {
+                let response = response
+                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header(header::CONTENT_RANGE, format!("bytes */{}", size))
+                    .body(Body::empty())
+                    .map_err(internal)?;
+                return Ok(response);
+            }
+        }
+    }
+
+    if let Some(size) = source.size() {
+        response = response.header(header::CONTENT_LENGTH, size);
+    }
+    let body = stream_body(move |out| source.copy_to(out).map(drop));
+    response.body(body).map_err(internal)
+}
+
+/// Parse a `Range` header against a body of `size` bytes into an inclusive
+/// byte span. `Ok(None)` means the header is ignored (malformed, another
+/// unit, or several ranges) and the whole body is sent; `Err` means the
+/// range lies outside the body.
+fn parse_range(header: &str, size: u64) -> Result<Option<(u64, u64)>, ()> {
+    let Some(spec) = header.trim().strip_prefix("bytes=") else {
+        return Ok(None);
+    };
+    if spec.contains(',') {
+        return Ok(None);
+    }
+    let Some((first, last)) = spec.trim().split_once('-') else {
+        return Ok(None);
+    };
+    let parse = |text: &str| text.trim().parse::<u64>().ok();
+    match (first.trim().is_empty(), last.trim().is_empty()) {
+        // `bytes=-500`: the last 500 bytes.
+        (true, false) => match parse(last) {
+            Some(0) => Err(()),
+            Some(_) if size == 0 => Err(()),
+            Some(suffix) => Ok(Some((size.saturating_sub(suffix), size - 1))),
+            None => Ok(None),
+        },
+        // `bytes=500-`: everything from byte 500.
+        (false, true) => match parse(first) {
+            Some(start) if start >= size => Err(()),
+            Some(start) => Ok(Some((start, size - 1))),
+            None => Ok(None),
+        },
+        (false, false) => match (parse(first), parse(last)) {
+            (Some(start), Some(end)) if start > end => Ok(None),
+            (Some(start), Some(_)) if start >= size => Err(()),
+            (Some(start), Some(end)) => Ok(Some((start, end.min(size - 1)))),
+            _ => Ok(None),
+        },
+        (true, true) => Ok(None),
+    }
+}
+
+// =======================
+// Archives of selections
+// =======================
+
+/// `GET /api/archive/download?path=...&path=...`: stream a zip (or tar,
+/// with `format=tar.gz` etc.) of the given files and folders, built while
+/// it is sent. Optional: `name` of the download without extension,
+/// `level`, repeated `exclude` globs and `store_symlinks`.
+pub async fn archive_download(RawQuery(query): RawQuery) -> Result<AxumResponse, ApiError> {
+    let mut sources = Vec::new();
+    let mut format = ArchiveFormat::Zip;
+    let mut name = None;
+    let mut options = CreateArchiveOptions::default();
+    for (key, value) in form_urlencoded::parse(query.unwrap_or_default().as_bytes()) {
+        let bad = |what: &str| ApiError::new(StatusCode::BAD_REQUEST, format!("Invalid {}: {}", what, value));
+        match key.as_ref() {
+            "path" => sources.push(value.into_owned()),
+            "format" => format = ArchiveFormat::from_name(&value).ok_or_else(|| bad("format"))?,
+            "name" => name = Some(value.into_owned()),
+            "level" => options.compression_level = Some(value.parse().map_err(|_| bad("level"))?),
+            "exclude" => options.exclude.push(value.into_owned()),
+            "store_symlinks" => options.store_symlinks = value.parse().map_err(|_| bad("store_symlinks"))?,
+            _ => {}
+        }
+    }
+    if sources.is_empty() {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, "No paths to archive"));
+    }
+    if matches!(format, ArchiveFormat::SevenZip) {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, "7z archives cannot be streamed"));
+    }
+    for source in &sources {
+        if std::fs::symlink_metadata(source).is_err() {
+            return Err(ApiError::new(StatusCode::NOT_FOUND, format!("Source does not exist: {}", source)));
+        }
+    }
+    tracing::info!("Streaming {} archive of {} paths", format.as_str(), sources.len());
+
+    // A single source names the download; a selection falls back to "download".
+    let stem = name.unwrap_or_else(|| match sources.as_slice() {
+        [single] => std::path::Path::new(single)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "download".to_string()),
+        _ => "download".to_string(),
+    });
+    let file_name = format!("{}.{}", stem, format.as_str());
+    let mime = mime_guess::from_path(&file_name).first_or_octet_stream();
+
+    let body = stream_body(move |out| crate::archive::stream_archive(&sources, format, &options, out));
+    AxumResponse::builder()
+        .header(header::CONTENT_TYPE, mime.as_ref())
+        .header(header::CONTENT_DISPOSITION, content_disposition(&file_name, true))
+        .body(body)
+        .map_err(internal)
+}
+
+// =======================
+// Helpers
+// =======================
+
+/// A response body fed by `produce`, which runs on a blocking thread and
+/// writes into the body as it goes. A failure after the headers were sent
+/// aborts the body so the client sees a truncated transfer, not a short
+/// file.
+pub fn stream_body<F>(produce: F) -> Body
+where
+    F: FnOnce(&mut dyn Write) -> anyhow::Result<()> + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(CHUNKS_IN_FLIGHT);
+    let errors = tx.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut out = BufWriter::with_capacity(CHUNK_SIZE, BodyWriter { tx });
+        let result = produce(&mut out).and_then(|()| Ok(out.flush()?));
+        if let Err(e) = result {
+            if !errors.is_closed() {
+                tracing::error!("Streaming download failed: {}", e);
+                let _ = errors.blocking_send(Err(io::Error::other(e.to_string())));
+            }
+        }
+    });
+    Body::from_stream(futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    }))
+}
+
+/// Forwards everything written to the response body.
+struct BodyWriter {
+    tx: mpsc::Sender<io::Result<Bytes>>,
+}
+
+impl Write for BodyWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.tx
+            .blocking_send(Ok(Bytes::copy_from_slice(buf)))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Client disconnected"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// `Content-Disposition` carrying `name`, with an RFC 5987 UTF-8 form for
+/// names that are not plain ASCII.
+pub fn content_disposition(name: &str, attachment: bool) -> HeaderValue {
+    let kind = if attachment { "attachment" } else { "inline" };
+    let fallback: String = name
+        .chars()
+        .map(|c| if (c.is_ascii_graphic() || c == ' ') && c != '"' && c != '\\' { c } else { '_' })
+        .collect();
+    let encoded: String = name
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect();
+    let value = format!("{}; filename=\"{}\"; filename*=UTF-8''{}", kind, fallback, encoded);
+    HeaderValue::from_str(&value).unwrap_or(HeaderValue::from_static("attachment"))
+}
+
+/// Status for a failure before any bytes were sent.
+fn archive_error(e: anyhow::Error) -> ApiError {
+    let status = match e.downcast_ref::<ArchiveError>() {
+        Some(ArchiveError::PasswordRequired | ArchiveError::WrongPassword) => StatusCode::UNAUTHORIZED,
+        Some(ArchiveError::MissingVolume(_) | ArchiveError::EntryNotFound(_)) => StatusCode::NOT_FOUND,
+        Some(ArchiveError::NotAFile(_)) => StatusCode::BAD_REQUEST,
+        None => match e.downcast_ref::<io::Error>() {
+            Some(io) if io.kind() == io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        },
+    };
+    ApiError::new(status, e.to_string())
+}
+
+fn internal(e: impl std::fmt::Display) -> ApiError {
+    ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Ok(Some((0, 99))));
+        assert_eq!(parse_range(" bytes= 10 - 19 ", 1000), Ok(Some((10, 19))));
+        assert_eq!(parse_range("bytes=900-2000", 1000), Ok(Some((900, 999))));
+        assert_eq!(parse_range("bytes=500-", 1000), Ok(Some((500, 999))));
+        assert_eq!(parse_range("bytes=999-999", 1000), Ok(Some((999, 999))));
+    }
+
+    #[test]
+    fn parses_suffix_ranges() {
+        assert_eq!(parse_range("bytes=-500", 1000), Ok(Some((500, 999))));
+        assert_eq!(parse_range("bytes=-5000", 1000), Ok(Some((0, 999))));
+        assert_eq!(parse_range("bytes=-1", 1), Ok(Some((0, 0))));
+        assert_eq!(parse_range("bytes=-0", 1000), Err(()));
+        assert_eq!(parse_range("bytes=-5", 0), Err(()));
+    }
+
+    #[test]
+    fn rejects_ranges_outside_the_body() {
+        assert_eq!(parse_range("bytes=1000-", 1000), Err(()));
+        assert_eq!(parse_range("bytes=1000-2000", 1000), Err(()));
+        assert_eq!(parse_range("bytes=0-", 0), Err(()));
+        assert_eq!(parse_range("bytes=0-0", 0), Err(()));
+    }
+
+    #[test]
+    fn ignores_headers_it_does_not_handle() {
+        for header in [
+            "items=0-1",
+            "bytes=0-1,5-6",
+            "bytes=abc",
+            "bytes=-",
+            "bytes=x-5",
+            "bytes=5-x",
+            "bytes=-x",
+            "bytes=9-1",
+            "bytes=-1-2",
+            "",
+        ] {
+            assert_eq!(parse_range(header, 1000), Ok(None), "{header}");
+        }
+    }
+
+    #[test]
+    fn maps_entry_errors_to_client_statuses() {
+        use axum::response::IntoResponse;
+        let status = |e: anyhow::Error| archive_error(e).into_response().status();
+        assert_eq!(status(ArchiveError::EntryNotFound("a/b".into()).into()), StatusCode::NOT_FOUND);
+        assert_eq!(status(ArchiveError::NotAFile("a".into()).into()), StatusCode::BAD_REQUEST);
+        assert_eq!(status(ArchiveError::PasswordRequired.into()), StatusCode::UNAUTHORIZED);
+        assert_eq!(status(anyhow::anyhow!("corrupt")), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -9,10 +9,29 @@ use crate::protocol::{Command, Response};
 pub async fn handle_command(
     Json(command): Json<Command>,
 ) -> Result<Json<Response>, ApiError> {
-    tracing::info!("Received command: {:?}", command);
+    log_command(&command);
+
+    // Execute command
+    let response = CommandExecutor::execute(command);
+    
+    // Log response status
+    match &response {
+        Response::Success { command_id, .. } => {
+            tracing::info!("Command {} executed successfully", command_id);
+        }
+        Response::Error { command_id, error, .. } => {
+            tracing::error!("Command {} failed: {} - {}", command_id, error.code, error.message);
+        }
+    }
     
-    // Log command type for better debugging
-    match &command {
+    Ok(Json(response))
+}
+
+/// Log command type for better debugging, over HTTP and WebSocket alike;
+/// the whole command is never logged, as it can carry archive passwords
+/// and file contents.
+pub fn log_command(command: &Command) {
+    match command {
         Command::ListDrives { .. } => tracing::info!("Processing ListDrives command"),
         Command::GetOsInfo { .. } => tracing::info!("Processing GetOsInfo command"),
         Command::ListDirectory { path, .. } => tracing::info!("Processing ListDirectory for path: {}", path),
@@ -24,27 +43,22 @@ pub async fn handle_command(
         Command::CopyFile { source, destination, .. } => tracing::info!("Processing CopyFile from {} to {}", source, destination),
         Command::GetFileInfo { path, .. } => tracing::info!("Processing GetFileInfo for path: {}", path),
         Command::SearchFiles { path, pattern, .. } => tracing::info!("Processing SearchFiles in {} for pattern: {}", path, pattern),
+        Command::SearchContent { path, query, .. } => tracing::info!("Processing SearchContent in {} for: {}", path, query),
+        Command::QueryIndex { query, .. } => tracing::info!("Processing QueryIndex for: {}", query),
+        Command::CompletePath { path, .. } => tracing::info!("Processing CompletePath for: {}", path),
+        Command::Reindex { roots, .. } => tracing::info!("Processing Reindex (roots: {:?})", roots),
         // Archive-related commands
         Command::ListArchive { archive_path, inner_path, .. } => tracing::info!("Processing ListArchive: {} (inner: {})", archive_path, inner_path),
         Command::ReadArchiveFile { archive_path, inner_path, .. } => tracing::info!("Processing ReadArchiveFile: {} (inner: {})", archive_path, inner_path),
         Command::ExtractArchive { archive_path, destination, .. } => tracing::info!("Processing ExtractArchive: {} -> {}", archive_path, destination),
+        Command::ArchiveAdd { archive_path, inner_path, sources, .. } => tracing::info!("Processing ArchiveAdd: {} sources -> {} (inner: {})", sources.len(), archive_path, inner_path),
+        Command::ArchiveDelete { archive_path, inner_paths, .. } => tracing::info!("Processing ArchiveDelete: {} ({} entries)", archive_path, inner_paths.len()),
+        Command::ArchiveRename { archive_path, inner_path, new_inner_path, .. } => tracing::info!("Processing ArchiveRename: {} ({} -> {})", archive_path, inner_path, new_inner_path),
+        Command::TestArchive { archive_path, .. } => tracing::info!("Processing TestArchive: {}", archive_path),
+        Command::RepackArchive { archive_path, destination, format, .. } => tracing::info!("Processing RepackArchive: {} -> {} ({})", archive_path, destination, format),
+        Command::CreateArchive { destination, format, sources, .. } => tracing::info!("Processing CreateArchive: {} ({}, {} sources)", destination, format, sources.len()),
         _ => tracing::info!("Processing command"),
     }
-    
-    // Execute command
-    let response = CommandExecutor::execute(command);
-    
-    // Log response status
-    match &response {
-        Response::Success { command_id, .. } => {
-            tracing::info!("Command {} executed successfully", command_id);
-        }
-        Response::Error { command_id, error, .. } => {
-            tracing::error!("Command {} failed: {} - {}", command_id, error.code, error.message);
-        }
-    }
-    
-    Ok(Json(response))
 }
 
 // Error handling
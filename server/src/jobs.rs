@@ -0,0 +1,216 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::protocol::{JobInfo, JobState, ResponseData};
+
+/// Finished jobs are kept around this long so clients can poll the result.
+const FINISHED_JOB_TTL_SECS: i64 = 60 * 60;
+
+// =======================
+// Registry
+// =======================
+
+struct JobEntry {
+    info: Mutex<JobInfo>,
+    cancelled: AtomicBool,
+}
+
+fn registry() -> &'static Mutex<HashMap<String, Arc<JobEntry>>> {
+    static JOBS: OnceLock<Mutex<HashMap<String, Arc<JobEntry>>>> = OnceLock::new();
+    JOBS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Run `work` on a background thread and return the initial job snapshot.
+/// The closure's result becomes the job's `result` (or `error`) once it ends.
+pub fn spawn<F>(kind: &str, work: F) -> JobInfo
+where
+    F: FnOnce(&JobHandle) -> Result<ResponseData> + Send + 'static,
+{
+    let now = Utc::now().timestamp();
+    let info = new_info(kind, now);
+
+    let entry = Arc::new(JobEntry {
+        info: Mutex::new(info.clone()),
+        cancelled: AtomicBool::new(false),
+    });
+
+    {
+        let mut jobs = registry().lock().unwrap();
+        jobs.retain(|_, job| {
+            let info = job.info.lock().unwrap();
+            info.finished.is_none_or(|t| now - t < FINISHED_JOB_TTL_SECS)
+        });
+        jobs.insert(info.job_id.clone(), entry.clone());
+    }
+
+    let handle = JobHandle { entry };
+    std::thread::spawn(move || {
+        let outcome = work(&handle);
+        let cancelled = handle.is_cancelled();
+        let mut info = handle.entry.info.lock().unwrap();
+        info.finished = Some(Utc::now().timestamp());
+        info.current_item = None;
+        match outcome {
+            Ok(data) => {
+                info.state = JobState::Completed;
+                info.result = Some(Box::new(data));
+            }
+            Err(e) => {
+                info.state = if cancelled {
+                    JobState::Cancelled
+                } else {
+                    JobState::Failed
+                };
+                info.error = Some(e.to_string());
+            }
+        }
+        tracing::info!("Job {} ({}) finished: {:?}", info.job_id, info.kind, info.state);
+    });
+
+    info
+}
+
+fn new_info(kind: &str, now: i64) -> JobInfo {
+    JobInfo {
+        job_id: uuid::Uuid::new_v4().to_string(),
+        kind: kind.to_string(),
+        state: JobState::Running,
+        total_items: 0,
+        processed_items: 0,
+        total_bytes: 0,
+        processed_bytes: 0,
+        current_item: None,
+        started: now,
+        finished: None,
+        error: None,
+        result: None,
+    }
+}
+
+/// Current snapshot of a job.
+pub fn status(job_id: &str) -> Result<JobInfo> {
+    let jobs = registry().lock().unwrap();
+    let job = jobs
+        .get(job_id)
+        .ok_or_else(|| anyhow!("Unknown job: {}", job_id))?;
+    let info = job.info.lock().unwrap().clone();
+    Ok(info)
+}
+
+/// Ask a running job to stop. The job notices at its next progress update.
+pub fn cancel(job_id: &str) -> Result<JobInfo> {
+    let jobs = registry().lock().unwrap();
+    let job = jobs
+        .get(job_id)
+        .ok_or_else(|| anyhow!("Unknown job: {}", job_id))?;
+    job.cancelled.store(true, Ordering::SeqCst);
+    let info = job.info.lock().unwrap().clone();
+    Ok(info)
+}
+
+// =======================
+// Progress reporting
+// =======================
+
+/// Passed to the job closure to report progress and observe cancellation.
+pub struct JobHandle {
+    entry: Arc<JobEntry>,
+}
+
+impl JobHandle {
+    /// A handle for work that runs outside the registry, such as a streamed
+    /// download: nobody can see its progress or cancel it.
+    pub fn detached(kind: &str) -> Self {
+        let entry = JobEntry {
+            info: Mutex::new(new_info(kind, Utc::now().timestamp())),
+            cancelled: AtomicBool::new(false),
+        };
+        Self { entry: Arc::new(entry) }
+    }
+
+    pub fn set_totals(&self, items: u64, bytes: u64) {
+        let mut info = self.entry.info.lock().unwrap();
+        info.total_items = items;
+        info.total_bytes = bytes;
+    }
+
+    /// Mark `name` as the item currently being processed.
+    pub fn begin_item(&self, name: &str) {
+        self.entry.info.lock().unwrap().current_item = Some(name.to_string());
+    }
+
+    pub fn finish_item(&self) {
+        self.entry.info.lock().unwrap().processed_items += 1;
+    }
+
+    pub fn add_bytes(&self, bytes: u64) {
+        self.entry.info.lock().unwrap().processed_bytes += bytes;
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.entry.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Bail out of the job if a client has cancelled it.
+    pub fn check_cancelled(&self) -> Result<()> {
+        if self.is_cancelled() {
+            anyhow::bail!("Job cancelled");
+        }
+        Ok(())
+    }
+}
+
+/// A reader that reports every byte read to a job and aborts on cancellation.
+pub struct ProgressReader<'a, R> {
+    inner: R,
+    job: &'a JobHandle,
+}
+
+impl<'a, R: Read> ProgressReader<'a, R> {
+    pub fn new(inner: R, job: &'a JobHandle) -> Self {
+        Self { inner, job }
+    }
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.job.is_cancelled() {
+            return Err(std::io::Error::other("Job cancelled"));
+        }
+        let n = self.inner.read(buf)?;
+        self.job.add_bytes(n as u64);
+        Ok(n)
+    }
+}
+
+/// A writer that reports every byte written to a job and aborts on
+/// cancellation.
+pub struct ProgressWriter<'a, W> {
+    inner: W,
+    job: &'a JobHandle,
+}
+
+impl<'a, W: Write> ProgressWriter<'a, W> {
+    pub fn new(inner: W, job: &'a JobHandle) -> Self {
+        Self { inner, job }
+    }
+}
+
+impl<W: Write> Write for ProgressWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.job.is_cancelled() {
+            return Err(std::io::Error::other("Job cancelled"));
+        }
+        let n = self.inner.write(buf)?;
+        self.job.add_bytes(n as u64);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
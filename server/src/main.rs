@@ -1,13 +1,15 @@
 mod archive;
 mod commands;
+mod download;
 mod handlers;
+mod jobs;
 mod protocol;
+mod search;
 mod ws;
 
 use std::net::SocketAddr;
 use anyhow::Result;
 use axum::{
-    extract::State,
     routing::{get, post},
     Router,
 };
@@ -22,10 +24,13 @@ async fn main() -> Result<()> {
     tracing_subscriber::fmt::init();
 
     let state = AppState {};
+    search::index::start();
 
     let app = Router::new()
         .route("/ws", get(ws::websocket_handler))
         .route("/api/command", post(handlers::handle_command))
+        .route("/api/archive/entry", get(download::archive_entry))
+        .route("/api/archive/download", get(download::archive_download))
         .route("/health", get(health))
         .layer(CorsLayer::permissive())
         .with_state(state);
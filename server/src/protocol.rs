@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 // ============================================================================
 // OS Info
@@ -49,6 +50,10 @@ pub enum Command {
         path: String,
         #[serde(default)]
         show_hidden: bool,
+        /// Password for an encrypted archive when `path` points inside one
+        /// (`/home/me/x.zip!/docs/readme.md`).
+        #[serde(default)]
+        password: Option<String>,
     },
 
     #[serde(rename = "LIST_DRIVES")]
@@ -70,6 +75,9 @@ pub enum Command {
         path: String,
         #[serde(default)]
         encoding: Option<String>,
+        /// Password when `path` is inside an encrypted archive.
+        #[serde(default)]
+        password: Option<String>,
     },
 
     #[serde(rename = "WRITE_FILE")]
@@ -116,6 +124,9 @@ pub enum Command {
         destination: String,
         #[serde(default)]
         recursive: bool,
+        /// Password when `source` is inside an encrypted archive.
+        #[serde(default)]
+        password: Option<String>,
     },
 
     #[serde(rename = "GET_FILE_INFO")]
@@ -123,6 +134,9 @@ pub enum Command {
         id: String,
         timestamp: i64,
         path: String,
+        /// Password when `path` is inside an encrypted archive.
+        #[serde(default)]
+        password: Option<String>,
     },
 
     #[serde(rename = "SEARCH_FILES")]
@@ -130,9 +144,56 @@ pub enum Command {
         id: String,
         timestamp: i64,
         path: String,
+        /// A query such as `*.rs size>10k -path:target` (see
+        /// `search::SearchQuery`); a plain word matches names containing it.
+        /// Protocol change: a leading `-`, `/re/`, parentheses, `OR`/`NOT`
+        /// and `key:value` words are now operators rather than literal text.
+        /// A pattern that does not parse as a query matches names
+        /// containing it literally, as before.
         pattern: String,
         #[serde(default)]
         recursive: bool,
+        /// Password when `path` is inside an encrypted archive.
+        #[serde(default)]
+        password: Option<String>,
+        /// Also match the names of entries inside archives met on the way.
+        #[serde(default)]
+        include_archives: bool,
+        /// Levels of archives to open: 1 (the default) searches archives
+        /// found on disk, 2 also archives inside those, and so on.
+        #[serde(default)]
+        archive_depth: Option<u32>,
+        /// Archives larger than this many bytes are not opened
+        /// (default 256 MiB).
+        #[serde(default)]
+        max_archive_size: Option<u64>,
+        /// Rank matches: the query's plain words are matched fuzzily
+        /// against names (typos, abbreviations, any word order) and the
+        /// best matches come first, each with a score in `ranks`.
+        #[serde(default)]
+        fuzzy: bool,
+        /// Paths the client opened recently, most recent first; fuzzy
+        /// ranking boosts them and what is below them.
+        #[serde(default)]
+        recent_paths: Vec<String>,
+        /// Most matches to return in fuzzy mode (default 100).
+        #[serde(default)]
+        limit: Option<usize>,
+    },
+
+    /// Search file contents below `path` for a literal string or a regex.
+    /// Over the WebSocket each file's matches arrive as a CONTENT_MATCHES
+    /// message and the response carries only the totals; over HTTP the
+    /// response holds every match.
+    #[serde(rename = "SEARCH_CONTENT")]
+    SearchContent {
+        id: String,
+        timestamp: i64,
+        /// File or directory to search; directories are searched recursively.
+        path: String,
+        query: String,
+        #[serde(default)]
+        options: ContentSearchOptions,
     },
 
     /// List the contents of an archive at `archive_path`, optionally under
@@ -143,9 +204,21 @@ pub enum Command {
         timestamp: i64,
         /// Absolute path to the archive file on disk.
         archive_path: String,
-        /// Path inside the archive to list (empty string = root).
+        /// Path inside the archive to list (empty string = root). Archives
+        /// inside the archive are entered with `!/`, e.g. `lib/inner.jar!/META-INF`.
         #[serde(default)]
         inner_path: String,
+        /// Password for encrypted archives.
+        #[serde(default)]
+        password: Option<String>,
+        /// Return every entry below `inner_path` as a flat list instead of
+        /// only its direct children.
+        #[serde(default)]
+        recursive: bool,
+        /// Encoding of zip entry names stored without the UTF-8 flag
+        /// (`cp437`, `cp866`, `shift_jis`, ...); detected when omitted.
+        #[serde(default)]
+        name_encoding: Option<String>,
     },
 
     /// Extract a single file from an archive and return its contents.
@@ -154,10 +227,15 @@ pub enum Command {
         id: String,
         timestamp: i64,
         archive_path: String,
-        /// Path of the file inside the archive.
+        /// Path of the file inside the archive; may cross nested archives (`a.jar!/b`).
         inner_path: String,
         #[serde(default)]
         encoding: Option<String>,
+        #[serde(default)]
+        password: Option<String>,
+        /// As for LIST_ARCHIVE.
+        #[serde(default)]
+        name_encoding: Option<String>,
     },
 
     /// Extract one or more entries from an archive to a destination directory.
@@ -169,9 +247,225 @@ pub enum Command {
         /// Destination directory on the filesystem.
         destination: String,
         /// Optional list of inner paths to extract; if empty, extract all.
+        /// A path ending in `!/` extracts a whole nested archive.
         #[serde(default)]
         inner_paths: Vec<String>,
+        #[serde(default)]
+        password: Option<String>,
+        /// As for LIST_ARCHIVE.
+        #[serde(default)]
+        name_encoding: Option<String>,
+    },
+
+    /// Pack files and directories into a new archive. Runs as a background
+    /// job; the response carries the job to poll with GET_JOB_STATUS.
+    #[serde(rename = "CREATE_ARCHIVE")]
+    CreateArchive {
+        id: String,
+        timestamp: i64,
+        /// Files and directories to add.
+        sources: Vec<String>,
+        /// Path of the archive to create.
+        destination: String,
+        /// `"zip"`, `"7z"`, `"tar"`, `"tar.gz"`, `"tar.bz2"`, `"tar.xz"` or `"tar.zst"`.
+        format: String,
+        #[serde(default)]
+        options: CreateArchiveOptions,
     },
+
+    /// Add files and directories to an existing zip or tar archive under
+    /// `inner_path`. Runs as a background job.
+    #[serde(rename = "ARCHIVE_ADD")]
+    ArchiveAdd {
+        id: String,
+        timestamp: i64,
+        archive_path: String,
+        /// Directory inside the archive to add to (empty string = root).
+        #[serde(default)]
+        inner_path: String,
+        /// Files and directories on disk to add.
+        sources: Vec<String>,
+        #[serde(default)]
+        options: CreateArchiveOptions,
+    },
+
+    /// Remove entries from a zip or tar archive. Runs as a background job.
+    #[serde(rename = "ARCHIVE_DELETE")]
+    ArchiveDelete {
+        id: String,
+        timestamp: i64,
+        archive_path: String,
+        /// Entries to remove; directories are removed with their contents.
+        inner_paths: Vec<String>,
+    },
+
+    /// Rename or move an entry inside a zip or tar archive. Runs as a
+    /// background job.
+    #[serde(rename = "ARCHIVE_RENAME")]
+    ArchiveRename {
+        id: String,
+        timestamp: i64,
+        archive_path: String,
+        inner_path: String,
+        /// Full new inner path of the entry.
+        new_inner_path: String,
+    },
+
+    /// Decompress every entry and verify its checksums without writing
+    /// anything to disk. Runs as a background job; the result is an
+    /// `ArchiveTestReport`.
+    #[serde(rename = "TEST_ARCHIVE")]
+    TestArchive {
+        id: String,
+        timestamp: i64,
+        archive_path: String,
+        #[serde(default)]
+        password: Option<String>,
+    },
+
+    /// Convert an archive to another format (e.g. 7z to tar.zst), streaming
+    /// every entry across without extracting to disk. Paths, modification
+    /// times and modes are kept. Runs as a background job.
+    #[serde(rename = "REPACK_ARCHIVE")]
+    RepackArchive {
+        id: String,
+        timestamp: i64,
+        archive_path: String,
+        /// Path of the archive to create.
+        destination: String,
+        /// Format to write, as for CREATE_ARCHIVE.
+        format: String,
+        /// Password of the source archive.
+        #[serde(default)]
+        password: Option<String>,
+        /// Compression level, exclusions, password and split size of the
+        /// new archive; `exclude` globs match inner paths.
+        #[serde(default)]
+        options: CreateArchiveOptions,
+    },
+
+    /// Poll the progress and result of a background job.
+    #[serde(rename = "GET_JOB_STATUS")]
+    GetJobStatus {
+        id: String,
+        timestamp: i64,
+        job_id: String,
+    },
+
+    /// Request cancellation of a running background job.
+    #[serde(rename = "CANCEL_JOB")]
+    CancelJob {
+        id: String,
+        timestamp: i64,
+        job_id: String,
+    },
+
+    /// Report the search index's roots, sizes and scan state.
+    #[serde(rename = "INDEX_STATUS")]
+    IndexStatus {
+        id: String,
+        timestamp: i64,
+    },
+
+    /// Rescan the search index in a background job. `roots` and `exclude`
+    /// (globs), when given, replace the configured ones and are saved with
+    /// the index.
+    #[serde(rename = "REINDEX")]
+    Reindex {
+        id: String,
+        timestamp: i64,
+        #[serde(default)]
+        roots: Option<Vec<String>>,
+        #[serde(default)]
+        exclude: Option<Vec<String>>,
+    },
+
+    /// Answer a SEARCH_FILES query from the search index, optionally only
+    /// below `path`.
+    #[serde(rename = "QUERY_INDEX")]
+    QueryIndex {
+        id: String,
+        timestamp: i64,
+        query: String,
+        #[serde(default)]
+        path: Option<String>,
+        /// Most matches to return (default 1000); `totalMatches` counts all.
+        #[serde(default)]
+        limit: Option<usize>,
+    },
+
+    /// Ranked completions for a partially typed path, for the address bar.
+    /// `~` and environment variables are expanded; relative paths are taken
+    /// below `cwd`.
+    #[serde(rename = "COMPLETE_PATH")]
+    CompletePath {
+        id: String,
+        timestamp: i64,
+        path: String,
+        #[serde(default)]
+        cwd: Option<String>,
+        /// Most completions to return (default 20).
+        #[serde(default)]
+        limit: Option<usize>,
+    },
+}
+
+/// Options for CREATE_ARCHIVE, also used by ARCHIVE_ADD and REPACK_ARCHIVE.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CreateArchiveOptions {
+    /// Format-specific compression level; the format's default when absent.
+    #[serde(default)]
+    pub compression_level: Option<u32>,
+    /// Stored names are made relative to this directory. Defaults to the
+    /// parent of each source, so a selected folder keeps its own name.
+    #[serde(default)]
+    pub base_path: Option<String>,
+    /// Glob patterns (e.g. `"*.log"`, `"**/node_modules"`) of entries to skip.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Store symlinks as links instead of archiving what they point to.
+    #[serde(default)]
+    pub store_symlinks: bool,
+    /// Encrypt the archive with this password (7z only: AES-256).
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Split the archive into volumes of this many bytes, written as
+    /// `<destination>.001`, `.002`, ... instead of `destination` itself.
+    #[serde(default)]
+    pub split_size: Option<u64>,
+}
+
+/// Options for SEARCH_CONTENT.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ContentSearchOptions {
+    /// Treat the query as a regular expression instead of a literal string.
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub case_sensitive: bool,
+    /// Only match whole words.
+    #[serde(default)]
+    pub whole_word: bool,
+    /// Files larger than this many bytes are skipped (default 10 MiB).
+    #[serde(default)]
+    pub max_file_size: Option<u64>,
+    /// Files with a NUL byte near the start are skipped unless this is set.
+    #[serde(default)]
+    pub include_binary: bool,
+    /// Glob patterns; when given, only files whose path below the search
+    /// root or whose name matches one are searched.
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Glob patterns (e.g. `"*.min.js"`, `"**/node_modules"`) of files and
+    /// directories to skip.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Lines of context to return before and after each matching line.
+    #[serde(default)]
+    pub context_lines: u32,
+    /// Stop after this many matching lines (default 10000).
+    #[serde(default)]
+    pub max_results: Option<u64>,
 }
 
 impl Command {
@@ -188,9 +482,22 @@ impl Command {
             Command::CopyFile { id, .. } => id,
             Command::GetFileInfo { id, .. } => id,
             Command::SearchFiles { id, .. } => id,
+            Command::SearchContent { id, .. } => id,
             Command::ListArchive { id, .. } => id,
             Command::ReadArchiveFile { id, .. } => id,
             Command::ExtractArchive { id, .. } => id,
+            Command::CreateArchive { id, .. } => id,
+            Command::ArchiveAdd { id, .. } => id,
+            Command::ArchiveDelete { id, .. } => id,
+            Command::ArchiveRename { id, .. } => id,
+            Command::TestArchive { id, .. } => id,
+            Command::RepackArchive { id, .. } => id,
+            Command::GetJobStatus { id, .. } => id,
+            Command::CancelJob { id, .. } => id,
+            Command::IndexStatus { id, .. } => id,
+            Command::Reindex { id, .. } => id,
+            Command::QueryIndex { id, .. } => id,
+            Command::CompletePath { id, .. } => id,
         }
     }
 }
@@ -234,9 +541,14 @@ pub enum ResponseData {
     FileInfo(FileInfo),
     OperationResult(OperationResult),
     SearchResult(SearchResult),
+    ContentSearchResult(ContentSearchResult),
     DrivesList(DrivesList),
     OsInfo(OsInfo),
     ArchiveListing(ArchiveListing),
+    ArchiveTestReport(ArchiveTestReport),
+    Job(JobInfo),
+    IndexStatus(IndexStatus),
+    PathCompletions(PathCompletions),
 }
 
 // ============================================================================
@@ -264,6 +576,10 @@ pub struct FileInfo {
     pub accessed: i64,
     pub permissions: String,
     pub is_hidden: bool,
+    /// Entry details when `path` is inside an archive
+    /// (`/home/me/x.zip!/docs/readme.md`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive_entry: Option<Box<ArchiveEntry>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -299,38 +615,122 @@ pub struct SearchResult {
     pub path: String,
     pub matches: Vec<FileInfo>,
     pub total_matches: usize,
+    /// Fuzzy mode: the rank of each match, in the same order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ranks: Vec<MatchRank>,
+}
+
+/// How well a fuzzy SEARCH_FILES match scored.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchRank {
+    /// Higher is better; about 100 for a name the query matches well,
+    /// plus boosts for shallow, recent and recently used paths.
+    pub score: f64,
+    /// `[start, end)` of the matched parts of the name, in UTF-16 code units.
+    pub ranges: Vec<[usize; 2]>,
+}
+
+/// The matching lines of one file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentMatches {
+    pub path: String,
+    pub matches: Vec<LineMatch>,
+}
+
+/// A matching line. Ranges are `[start, end)` offsets into `line` in UTF-16
+/// code units, as JavaScript strings are indexed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineMatch {
+    /// 1-based
+    pub line_number: u64,
+    pub line: String,
+    pub ranges: Vec<[usize; 2]>,
+    /// Context lines before, nearest last; never repeats a line already sent.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub before: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub after: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentSearchResult {
+    pub path: String,
+    /// Every match over HTTP; empty when the matches were streamed.
+    pub files: Vec<ContentMatches>,
+    pub files_searched: u64,
+    pub files_matched: u64,
+    /// Matching lines
+    pub total_matches: u64,
+    pub skipped_binary: u64,
+    pub skipped_large: u64,
+    /// The search stopped at `max_results`.
+    pub truncated: bool,
 }
 
 // ============================================================================
 // Archive Data Types
 // ============================================================================
 
-/// A single entry inside an archive (file or directory).
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A single entry inside an archive (file, directory or symbolic link).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ArchiveEntry {
     /// File/directory name (last component of `inner_path`).
     pub name: String,
     /// Full path inside the archive, using forward slashes.
     pub inner_path: String,
-    /// `"FILE"` or `"DIRECTORY"`.
+    /// `"FILE"`, `"DIRECTORY"` or `"SYMLINK"`.
     #[serde(rename = "type")]
     pub entry_type: ArchiveEntryType,
-    /// Uncompressed size in bytes (0 for directories).
+    /// Uncompressed size in bytes; for directories, the total of all files below.
     pub size: u64,
-    /// Compressed size in bytes (0 for directories / unsupported formats).
+    /// Compressed size in bytes (directories: total below; 0 for unsupported formats).
     pub compressed_size: u64,
     /// Unix timestamp of the last-modified time (0 if unknown).
     pub modified: i64,
-    /// Compression method string, e.g. `"Deflate"`, `"Stored"`, `"BZip2"`, etc.
+    /// Compression method, e.g. `"Deflate"`, `"Stored"`, `"BZip2"`, `"LZMA2"`.
     pub compression: String,
+    /// Compression level or preset, e.g. `"Maximum"` for a zip entry
+    /// deflated with `-9`, when the format records it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression_level: Option<String>,
+    /// Whether the entry's data is encrypted and needs a password.
+    #[serde(default)]
+    pub encrypted: bool,
+    /// Per-entry comment (zip, 7z).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// Unix mode bits including the file type, e.g. `0o100644`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+    /// Owning user: the stored name, or the numeric uid when only that is known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// Owning group: the stored name, or the numeric gid when only that is known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Target of a symbolic link entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symlink_target: Option<String>,
+    /// CRC32 of the uncompressed data, as stored in the archive.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crc32: Option<u32>,
+    /// System the entry was created on, e.g. `"Unix"`, `"MS-DOS"`, `"NTFS"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_os: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum ArchiveEntryType {
+    #[default]
     File,
     Directory,
+    Symlink,
 }
 
 /// Response for LIST_ARCHIVE.
@@ -341,12 +741,183 @@ pub struct ArchiveListing {
     pub archive_path: String,
     /// The inner path that was listed (empty string = root).
     pub inner_path: String,
-    /// Format detected: `"zip"`, `"tar"`, `"tar.gz"`, `"tar.bz2"`, `"tar.xz"`, `"tar.zst"`, `"gz"`, `"bz2"`, `"xz"`, `"zst"`.
+    /// Format detected from the file's signature: `"zip"`, `"7z"`, `"tar"`,
+    /// `"tar.gz"`, `"tar.bz2"`, `"tar.xz"`, `"tar.zst"`, `"gz"`, `"bz2"`, `"xz"`,
+    /// `"zst"`, `"cab"`, `"rar"`, `"iso"`, `"deb"`, `"rpm"`, `"ar"`, `"cpio"`.
     pub format: String,
-    /// Direct children of `inner_path`.
+    /// Direct children of `inner_path` (every descendant when recursive).
     pub entries: Vec<ArchiveEntry>,
     /// Total uncompressed size of all entries in this listing level.
     pub total_size: u64,
+    /// Archive-level information, for formats that record it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<ArchiveMetadata>,
+}
+
+/// Result of a TEST_ARCHIVE job.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveTestReport {
+    pub archive_path: String,
+    pub format: String,
+    /// True when every entry and the archive as a whole checked out.
+    pub ok: bool,
+    pub passed: u64,
+    pub failed: u64,
+    /// One result per file entry, in archive order.
+    pub entries: Vec<EntryTestResult>,
+    /// A failure not tied to one entry, e.g. a truncated stream or a bad
+    /// checksum in a gzip trailer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Test outcome of one archive entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryTestResult {
+    pub inner_path: String,
+    pub ok: bool,
+    /// Uncompressed size of the entry.
+    pub size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Archive-level information reported alongside a listing.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveMetadata {
+    /// Volume label of a disc image.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume_label: Option<String>,
+    /// Archive comment (zip, 7z, RAR, gzip header).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// Unix timestamp the archive or volume was created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<i64>,
+    /// Encoding used for zip entry names stored without the UTF-8 flag,
+    /// detected or as requested with `name_encoding`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name_encoding: Option<String>,
+    /// Package fields of a .deb or .rpm (`name`, `version`, `depends`, ...).
+    /// Debian control fields keep their lowercased names.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub package: BTreeMap<String, String>,
+}
+
+// ============================================================================
+// Job Data Types
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum JobState {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// Snapshot of a background job, returned when it starts and by GET_JOB_STATUS.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobInfo {
+    pub job_id: String,
+    /// Command type that started the job, e.g. `"CREATE_ARCHIVE"`.
+    pub kind: String,
+    pub state: JobState,
+    pub total_items: u64,
+    pub processed_items: u64,
+    pub total_bytes: u64,
+    pub processed_bytes: u64,
+    /// Item being processed right now, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_item: Option<String>,
+    /// Unix timestamp when the job started.
+    pub started: i64,
+    /// Unix timestamp when the job ended.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Result of a completed job.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Box<ResponseData>>,
+}
+
+// ============================================================================
+// Search Index Types
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum IndexState {
+    /// Configured but not scanned yet.
+    #[default]
+    Pending,
+    Indexing,
+    Ready,
+    /// The first scan failed; see `error`.
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexRootStatus {
+    pub path: String,
+    pub state: IndexState,
+    pub files: u64,
+    pub directories: u64,
+    /// Unix timestamp of the last completed scan.
+    pub last_indexed: Option<i64>,
+    /// Changes are picked up from filesystem events.
+    pub watching: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Response for INDEX_STATUS, and the result of a REINDEX job.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexStatus {
+    pub roots: Vec<IndexRootStatus>,
+    pub exclude: Vec<String>,
+    pub total_files: u64,
+    pub total_directories: u64,
+    /// Where the index is saved.
+    pub index_file: Option<String>,
+    /// Unix timestamp of the last save by this server.
+    pub last_saved: Option<i64>,
+}
+
+/// Response for COMPLETE_PATH.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathCompletions {
+    pub input: String,
+    /// `input` with `~` and environment variables expanded.
+    pub expanded: String,
+    /// Best first.
+    pub completions: Vec<PathCompletion>,
+    /// The time budget ran out before every candidate was looked at.
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathCompletion {
+    /// The completed path; inside archives, a virtual path.
+    pub path: String,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub file_type: FileType,
+    /// An archive that can be completed into by appending `!/`.
+    pub is_archive: bool,
+    pub score: f64,
+    /// Matched ranges of `path`, in UTF-16 code units.
+    pub ranges: Vec<[usize; 2]>,
 }
 
 // ============================================================================
@@ -363,6 +934,11 @@ pub enum WebSocketMessage {
     #[serde(rename = "RESPONSE")]
     Response { payload: Response },
 
+    /// One file's matches for the SEARCH_CONTENT command `command_id`, sent
+    /// before its RESPONSE.
+    #[serde(rename = "CONTENT_MATCHES")]
+    ContentMatches { command_id: String, payload: ContentMatches },
+
     #[serde(rename = "PING")]
     Ping,
 
@@ -0,0 +1,305 @@
+use anyhow::{Context, Result};
+use globset::GlobSet;
+use regex::{Regex, RegexBuilder};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use walkdir::WalkDir;
+
+pub mod complete;
+mod fuzzy;
+pub mod index;
+mod query;
+
+pub use fuzzy::Ranker;
+pub use query::{Candidate, Kind, SearchQuery};
+
+use crate::archive::build_globset;
+use crate::protocol::{
+    ContentMatches, ContentSearchOptions, ContentSearchResult, FileInfo, LineMatch, MatchRank, SearchResult,
+};
+
+const DEFAULT_MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+const DEFAULT_MAX_RESULTS: u64 = 10_000;
+
+/// Bytes looked at for a NUL when deciding whether a file is binary.
+const BINARY_SNIFF_BYTES: usize = 8 * 1024;
+
+/// Longer lines (minified code, data files) are cut to an excerpt of this
+/// many bytes around their first match.
+const MAX_LINE_BYTES: usize = 2000;
+
+// =======================
+// Name search
+// =======================
+
+/// Collects SEARCH_FILES matches; in fuzzy mode it also ranks them.
+pub struct NameMatches<'a> {
+    query: SearchQuery,
+    ranker: Option<Ranker<'a>>,
+    matches: Vec<FileInfo>,
+    ranks: Vec<MatchRank>,
+}
+
+impl<'a> NameMatches<'a> {
+    /// `recent` (paths the client used recently) turns on fuzzy mode.
+    pub fn new(query: SearchQuery, recent: Option<&'a [String]>) -> Self {
+        let (query, ranker) = match recent {
+            Some(recent) => {
+                let (text, filter) = query.split_text();
+                (filter, Some(Ranker::new(&text, recent)))
+            }
+            None => (query, None),
+        };
+        NameMatches {
+            query,
+            ranker,
+            matches: Vec::new(),
+            ranks: Vec::new(),
+        }
+    }
+
+    pub fn query(&self) -> &SearchQuery {
+        &self.query
+    }
+
+    /// Keep `candidate` if it matches; `info` describes it for the response.
+    pub fn consider(&mut self, candidate: &Candidate, info: impl FnOnce() -> Result<FileInfo>) -> Result<()> {
+        if !self.query.matches(candidate) {
+            return Ok(());
+        }
+        let Some(ranker) = &self.ranker else {
+            self.matches.push(info()?);
+            return Ok(());
+        };
+        let Some(text) = ranker.text_score(candidate.name) else {
+            return Ok(());
+        };
+        let info = info()?;
+        self.ranks.push(ranker.rank(text, candidate, &info.path));
+        self.matches.push(info);
+        Ok(())
+    }
+
+    /// The result; ranked matches come best first, at most `limit` of them.
+    pub fn finish(self, path: String, limit: usize) -> SearchResult {
+        let total_matches = self.matches.len();
+        if self.ranker.is_none() {
+            return SearchResult {
+                path,
+                matches: self.matches,
+                total_matches,
+                ranks: Vec::new(),
+            };
+        }
+        let mut ranked: Vec<(FileInfo, MatchRank)> = self.matches.into_iter().zip(self.ranks).collect();
+        ranked.sort_by(|(a_info, a), (b_info, b)| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a_info.name.len().cmp(&b_info.name.len()))
+                .then_with(|| a_info.path.cmp(&b_info.path))
+        });
+        ranked.truncate(limit);
+        let (matches, ranks) = ranked.into_iter().unzip();
+        SearchResult {
+            path,
+            matches,
+            total_matches,
+            ranks,
+        }
+    }
+}
+
+// =======================
+// Content search
+// =======================
+
+/// Search the contents of `root` (a file, or every file below a directory)
+/// for `query`. Each file's matches go to `emit` as soon as the file is
+/// done; the search stops early when `emit` returns false.
+pub fn search_content(
+    root: &str,
+    query: &str,
+    options: &ContentSearchOptions,
+    emit: &mut dyn FnMut(ContentMatches) -> bool,
+) -> Result<ContentSearchResult> {
+    let root_path = Path::new(root);
+    if !root_path.exists() {
+        anyhow::bail!("Path does not exist: {}", root);
+    }
+    if query.is_empty() {
+        anyhow::bail!("Search query is empty");
+    }
+    let pattern = compile(query, options)?;
+    let include = build_globset(&options.include)?;
+    let exclude = build_globset(&options.exclude)?;
+    let max_file_size = options.max_file_size.unwrap_or(DEFAULT_MAX_FILE_SIZE);
+    let max_results = options.max_results.unwrap_or(DEFAULT_MAX_RESULTS);
+
+    let mut result = ContentSearchResult {
+        path: root.to_string(),
+        files: Vec::new(),
+        files_searched: 0,
+        files_matched: 0,
+        total_matches: 0,
+        skipped_binary: 0,
+        skipped_large: 0,
+        truncated: false,
+    };
+
+    let relative = |path: &Path| -> String {
+        path.strip_prefix(root_path)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .replace('\\', "/")
+    };
+    let walker = WalkDir::new(root_path)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !matches_glob(&exclude, &relative(e.path()), e.file_name()));
+
+    // Unreadable directories and files are skipped, as a terminal grep would.
+    for entry in walker.filter_map(|e| e.ok()) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        if !options.include.is_empty() && !matches_glob(&include, &relative(entry.path()), entry.file_name()) {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.len() > max_file_size {
+            result.skipped_large += 1;
+            continue;
+        }
+        let mut bytes = Vec::with_capacity(metadata.len() as usize);
+        let read = File::open(entry.path()).and_then(|f| f.take(max_file_size).read_to_end(&mut bytes));
+        if read.is_err() {
+            continue;
+        }
+        if !options.include_binary && bytes[..bytes.len().min(BINARY_SNIFF_BYTES)].contains(&0) {
+            result.skipped_binary += 1;
+            continue;
+        }
+
+        result.files_searched += 1;
+        let text = String::from_utf8_lossy(&bytes);
+        if !pattern.is_match(&text) {
+            continue;
+        }
+        let budget = max_results - result.total_matches;
+        let matches = match_lines(&text, &pattern, options.context_lines as usize, budget);
+        if matches.is_empty() {
+            continue;
+        }
+
+        result.files_matched += 1;
+        result.total_matches += matches.len() as u64;
+        let file = ContentMatches {
+            path: entry.path().to_string_lossy().into_owned(),
+            matches,
+        };
+        if !emit(file) {
+            break;
+        }
+        if result.total_matches >= max_results {
+            result.truncated = true;
+            break;
+        }
+    }
+    Ok(result)
+}
+
+fn compile(query: &str, options: &ContentSearchOptions) -> Result<Regex> {
+    let pattern = if options.regex {
+        query.to_string()
+    } else {
+        regex::escape(query)
+    };
+    let pattern = if options.whole_word {
+        format!(r"\b(?:{})\b", pattern)
+    } else {
+        pattern
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .build()
+        .with_context(|| format!("Invalid regular expression '{}'", query))
+}
+
+/// Whether a glob matches the path below the search root or the name alone.
+fn matches_glob(globs: &GlobSet, relative: &str, name: &std::ffi::OsStr) -> bool {
+    globs.is_match(relative) || globs.is_match(name)
+}
+
+/// Up to `budget` matching lines of `text`, each with up to `context` lines
+/// around it. Context never repeats a matching line or one already sent
+/// as context of the previous match.
+fn match_lines(text: &str, pattern: &Regex, context: usize, budget: u64) -> Vec<LineMatch> {
+    let lines: Vec<&str> = text
+        .strip_suffix('\n')
+        .unwrap_or(text)
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect();
+    let hits: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| pattern.is_match(line))
+        .map(|(i, _)| i)
+        .take(usize::try_from(budget).unwrap_or(usize::MAX))
+        .collect();
+
+    let mut matches = Vec::with_capacity(hits.len());
+    let mut sent_until = 0;
+    for (n, &i) in hits.iter().enumerate() {
+        let next = hits.get(n + 1).copied().unwrap_or(lines.len());
+        let before_start = i.saturating_sub(context).max(sent_until);
+        let after_end = (i + 1 + context).min(next);
+        sent_until = after_end;
+
+        let (line, ranges) = excerpt(lines[i], pattern);
+        matches.push(LineMatch {
+            line_number: i as u64 + 1,
+            line,
+            ranges,
+            before: lines[before_start..i].iter().map(|l| cut(l).to_string()).collect(),
+            after: lines[i + 1..after_end].iter().map(|l| cut(l).to_string()).collect(),
+        });
+    }
+    matches
+}
+
+/// The line, or an excerpt of a long one starting shortly before its first
+/// match, with the match ranges inside it in UTF-16 code units.
+fn excerpt(line: &str, pattern: &Regex) -> (String, Vec<[usize; 2]>) {
+    let mut start = 0;
+    if line.len() > MAX_LINE_BYTES {
+        let first = pattern.find(line).map_or(0, |m| m.start());
+        start = floor_boundary(line, first.saturating_sub(MAX_LINE_BYTES / 10));
+    }
+    let end = floor_boundary(line, start + MAX_LINE_BYTES);
+    let shown = &line[start..end];
+
+    let utf16 = |byte: usize| shown[..byte].encode_utf16().count();
+    let ranges = pattern
+        .find_iter(shown)
+        .filter(|m| !m.is_empty())
+        .map(|m| [utf16(m.start()), utf16(m.end())])
+        .collect();
+    (shown.to_string(), ranges)
+}
+
+/// A context line, cut to the excerpt length.
+fn cut(line: &str) -> &str {
+    &line[..floor_boundary(line, MAX_LINE_BYTES)]
+}
+
+fn floor_boundary(s: &str, index: usize) -> usize {
+    let mut index = index.min(s.len());
+    while !s.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
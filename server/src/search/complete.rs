@@ -0,0 +1,381 @@
+//! COMPLETE_PATH: ranked completions for a partially typed path.
+//!
+//! The input is expanded first (`~`, `$VAR`, `${VAR}`), then resolved one
+//! segment at a time. A segment that names an existing directory is taken
+//! as is; otherwise it is matched against the directory's children by
+//! prefix and then fuzzily, keeping the best few candidates at every level,
+//! so `/us/lo/bi` completes to `/usr/local/bin`. A segment ending in `!`
+//! opens an archive (`~/dl/src.zip!/lib/ma`), the same as a virtual path.
+//!
+//! Completion runs on every keystroke, so it gives up on whatever is left
+//! once its time budget is spent and answers with what it has.
+
+use anyhow::{bail, Result};
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use super::fuzzy::Ranker;
+use crate::archive::ArchiveOptions;
+use crate::protocol::{ArchiveEntryType, FileType, PathCompletion, PathCompletions};
+
+pub const DEFAULT_COMPLETION_LIMIT: usize = 20;
+
+/// Time after which no more directories or archives are read, except
+/// directories while nothing has been found.
+const TIME_BUDGET: Duration = Duration::from_millis(100);
+/// Candidates kept for each segment before the last one.
+const BEAM_WIDTH: usize = 4;
+/// Children looked at in a single directory.
+const MAX_DIR_ENTRIES: usize = 20_000;
+
+/// Scores of a case-insensitive prefix match start here, above any fuzzy one.
+const PREFIX_SCORE: f64 = 2.0;
+/// Nudges directories ahead of files; the address bar mostly navigates.
+const DIRECTORY_BONUS: f64 = 0.1;
+
+/// A directory the completion has reached.
+#[derive(Debug, Clone)]
+enum Location {
+    Dir(PathBuf),
+    /// An archive on disk and a directory inside it, which may pass through
+    /// nested archives (`lib/inner.jar!/META-INF`).
+    Archive(String, String),
+}
+
+#[derive(Debug)]
+struct Child {
+    name: String,
+    file_type: FileType,
+    is_dir: bool,
+    is_archive: bool,
+}
+
+/// A path completed up to some segment.
+#[derive(Debug, Clone)]
+struct Partial {
+    location: Location,
+    /// The path as shown to the client.
+    text: String,
+    score: f64,
+    /// Matched ranges of `text`, in UTF-16 code units.
+    ranges: Vec<[usize; 2]>,
+}
+
+impl Partial {
+    /// This path extended by `name`, a child scoring `score` with `ranges`
+    /// inside the name.
+    fn extend(&self, location: Location, name: &str, score: f64, ranges: &[[usize; 2]]) -> Partial {
+        let mut text = self.text.clone();
+        if !text.ends_with(['/', '\\']) {
+            text.push('/');
+        }
+        let offset = text.encode_utf16().count();
+        text.push_str(name);
+        let mut all = self.ranges.clone();
+        all.extend(ranges.iter().map(|[start, end]| [start + offset, end + offset]));
+        Partial {
+            location,
+            text,
+            score: self.score + score,
+            ranges: all,
+        }
+    }
+}
+
+/// Complete `input`; relative input is taken below `cwd`.
+pub fn complete_path(input: &str, cwd: Option<&str>, limit: usize) -> Result<PathCompletions> {
+    let deadline = Instant::now() + TIME_BUDGET;
+    let mut expanded = expand(input);
+    if !Path::new(&expanded).has_root() {
+        let Some(cwd) = cwd else {
+            bail!("Cannot complete a relative path without a working directory: {}", input);
+        };
+        expanded = format!("{}/{}", cwd.trim_end_matches(['/', '\\']), expanded);
+    }
+    // `archive.zip!` completes inside the archive, like `archive.zip!/`.
+    let target = match expanded.ends_with('!') {
+        true => format!("{}/", expanded),
+        false => expanded.clone(),
+    };
+
+    let root = Path::new(&target).ancestors().last().unwrap_or(Path::new("/"));
+    let root_text = root.to_string_lossy().into_owned();
+    let mut segments: Vec<&str> = target[root_text.len()..].split(['/', '\\']).collect();
+    let last = segments.pop().unwrap_or_default();
+
+    let mut truncated = false;
+    let mut partials = vec![Partial {
+        location: Location::Dir(root.to_path_buf()),
+        text: root_text,
+        score: 0.0,
+        ranges: Vec::new(),
+    }];
+    for segment in segments.into_iter().filter(|s| !s.is_empty()) {
+        let (name, open_archive) = match segment.strip_suffix('!') {
+            Some(name) => (name, true),
+            None => (segment, false),
+        };
+        // An existing directory beats every fuzzy alternative.
+        let mut next: Vec<Partial> = partials
+            .iter()
+            .filter_map(|partial| exact_child(partial, name, open_archive))
+            .collect();
+        if next.is_empty() {
+            let ranker = Ranker::new(name, &[]);
+            for partial in &partials {
+                if Instant::now() >= deadline && !next.is_empty() {
+                    truncated = true;
+                    break;
+                }
+                if out_of_time(&partial.location, deadline) {
+                    truncated = true;
+                    continue;
+                }
+                for child in children(&partial.location) {
+                    if !(if open_archive { child.is_archive } else { child.is_dir }) {
+                        continue;
+                    }
+                    if let Some((score, ranges)) = segment_score(name, &ranker, &child.name) {
+                        let location = descend(&partial.location, &child.name, open_archive);
+                        next.push(partial.extend(location, &child.name, score, &ranges));
+                    }
+                }
+            }
+        }
+        sort(&mut next);
+        next.truncate(BEAM_WIDTH);
+        if open_archive {
+            for partial in &mut next {
+                partial.text.push('!');
+            }
+        }
+        partials = next;
+    }
+
+    let ranker = Ranker::new(last, &[]);
+    let mut completions = Vec::new();
+    for partial in &partials {
+        if Instant::now() >= deadline && !completions.is_empty() {
+            truncated = true;
+            break;
+        }
+        if out_of_time(&partial.location, deadline) {
+            truncated = true;
+            continue;
+        }
+        for child in children(&partial.location) {
+            let Some((score, ranges)) = segment_score(last, &ranker, &child.name) else {
+                continue;
+            };
+            let bonus = if child.is_dir { DIRECTORY_BONUS } else { 0.0 };
+            let location = descend(&partial.location, &child.name, false);
+            let done = partial.extend(location, &child.name, score + bonus, &ranges);
+            completions.push((done, child));
+        }
+    }
+    completions.sort_by(|(a, _), (b, _)| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.text.len().cmp(&b.text.len()))
+            .then_with(|| a.text.cmp(&b.text))
+    });
+    completions.truncate(limit);
+
+    Ok(PathCompletions {
+        input: input.to_string(),
+        expanded,
+        completions: completions
+            .into_iter()
+            .map(|(partial, child)| PathCompletion {
+                path: partial.text,
+                name: child.name,
+                file_type: child.file_type,
+                is_archive: child.is_archive,
+                score: partial.score,
+                ranges: partial.ranges,
+            })
+            .collect(),
+        truncated,
+    })
+}
+
+/// Expand a leading `~` and `$VAR` / `${VAR}` (and `%VAR%` on Windows).
+/// Unset variables are left as typed.
+fn expand(input: &str) -> String {
+    let mut input = Cow::Borrowed(input);
+    if input == "~" || input.starts_with("~/") || input.starts_with("~\\") {
+        if let Some(home) = dirs::home_dir() {
+            input = Cow::Owned(format!("{}{}", home.to_string_lossy(), &input[1..]));
+        }
+    }
+
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input.as_ref();
+    while let Some(i) = rest.find(if cfg!(windows) { &['$', '%'][..] } else { &['$'][..] }) {
+        out.push_str(&rest[..i]);
+        let after = &rest[i + 1..];
+        let (name, len) = match (&rest[i..i + 1], after.strip_prefix('{')) {
+            ("$", Some(braced)) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 3),
+                None => ("", 0),
+            },
+            ("$", None) => {
+                let end = after
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                (&after[..end], end + 1)
+            }
+            _ => match after.find('%') {
+                Some(end) => (&after[..end], end + 2),
+                None => ("", 0),
+            },
+        };
+        match std::env::var(name) {
+            Ok(value) if !name.is_empty() => {
+                out.push_str(&value);
+                rest = &rest[i + len..];
+            }
+            _ => {
+                out.push_str(&rest[i..i + 1]);
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// `partial` extended by the child called exactly `name`, if it exists.
+fn exact_child(partial: &Partial, name: &str, open_archive: bool) -> Option<Partial> {
+    match &partial.location {
+        Location::Dir(dir) => {
+            let path = dir.join(name);
+            let found = match open_archive {
+                true => path.is_file() && crate::archive::is_archive_name(name),
+                false => path.is_dir(),
+            };
+            found.then(|| partial.extend(descend(&partial.location, name, open_archive), name, 0.0, &[]))
+        }
+        // Listing is the only way to look inside; the fuzzy pass does that.
+        Location::Archive(..) => None,
+    }
+}
+
+fn descend(location: &Location, name: &str, open_archive: bool) -> Location {
+    match (location, open_archive) {
+        (Location::Dir(dir), false) => Location::Dir(dir.join(name)),
+        (Location::Dir(dir), true) => Location::Archive(dir.join(name).to_string_lossy().into_owned(), String::new()),
+        (Location::Archive(archive, inner), false) => Location::Archive(archive.clone(), join_inner(inner, name)),
+        (Location::Archive(archive, inner), true) => {
+            Location::Archive(archive.clone(), format!("{}!/", join_inner(inner, name)))
+        }
+    }
+}
+
+fn join_inner(inner: &str, name: &str) -> String {
+    match inner.is_empty() || inner.ends_with('/') {
+        true => format!("{}{}", inner, name),
+        false => format!("{}/{}", inner, name),
+    }
+}
+
+/// Whether `location` is an archive and the time budget is spent. Even
+/// with nothing found yet, no archive is opened then: listing one that is
+/// not cached can take far longer than the whole budget.
+fn out_of_time(location: &Location, deadline: Instant) -> bool {
+    matches!(location, Location::Archive(..)) && Instant::now() >= deadline
+}
+
+/// The children of a location. Unreadable directories and archives have
+/// none.
+fn children(location: &Location) -> Vec<Child> {
+    match location {
+        Location::Dir(dir) => {
+            let Ok(entries) = fs::read_dir(dir) else {
+                return Vec::new();
+            };
+            entries
+                .filter_map(|e| e.ok())
+                .take(MAX_DIR_ENTRIES)
+                .filter_map(|entry| {
+                    let file_type = entry.file_type().ok()?;
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    // Symlinks count as what they point to.
+                    let (is_dir, is_file) = match file_type.is_symlink() {
+                        true => fs::metadata(entry.path()).map_or((false, false), |m| (m.is_dir(), m.is_file())),
+                        false => (file_type.is_dir(), file_type.is_file()),
+                    };
+                    Some(Child {
+                        file_type: match () {
+                            _ if file_type.is_symlink() => FileType::Symlink,
+                            _ if is_dir => FileType::Directory,
+                            _ => FileType::File,
+                        },
+                        is_dir,
+                        is_archive: is_file && crate::archive::is_archive_name(&name),
+                        name,
+                    })
+                })
+                .collect()
+        }
+        Location::Archive(archive, inner) => {
+            let Ok(listing) = crate::archive::list_archive(archive, inner, &ArchiveOptions::default()) else {
+                return Vec::new();
+            };
+            listing
+                .entries
+                .into_iter()
+                .map(|entry| {
+                    let is_dir = matches!(entry.entry_type, ArchiveEntryType::Directory);
+                    Child {
+                        file_type: match entry.entry_type {
+                            ArchiveEntryType::Directory => FileType::Directory,
+                            ArchiveEntryType::Symlink => FileType::Symlink,
+                            ArchiveEntryType::File => FileType::File,
+                        },
+                        is_dir,
+                        is_archive: !is_dir && crate::archive::is_archive_name(&entry.name),
+                        name: entry.name,
+                    }
+                })
+                .collect()
+        }
+    }
+}
+
+/// How well `name` completes `segment`: a case-insensitive prefix first,
+/// then a fuzzy match. Hidden names only match a segment starting with `.`.
+fn segment_score(segment: &str, ranker: &Ranker, name: &str) -> Option<(f64, Vec<[usize; 2]>)> {
+    if name.starts_with('.') && !segment.starts_with('.') {
+        return None;
+    }
+    if segment.is_empty() {
+        return Some((0.0, Vec::new()));
+    }
+    let mut name_chars = name.chars();
+    let mut exact_case = true;
+    let prefix = segment.chars().all(|s| {
+        name_chars.next().is_some_and(|n| {
+            exact_case &= n == s;
+            n == s || n.to_lowercase().eq(s.to_lowercase())
+        })
+    });
+    if prefix {
+        let typed = segment.chars().count() as f64;
+        let coverage = typed / (typed + name_chars.count() as f64);
+        let score = PREFIX_SCORE + 0.5 * coverage + if exact_case { 0.1 } else { 0.0 };
+        return Some((score, vec![[0, segment.encode_utf16().count()]]));
+    }
+    ranker.text_score(name)
+}
+
+fn sort(partials: &mut [Partial]) {
+    partials.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.text.len().cmp(&b.text.len()))
+            .then_with(|| a.text.cmp(&b.text))
+    });
+}
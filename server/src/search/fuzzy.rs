@@ -0,0 +1,479 @@
+//! Ranked, typo-tolerant name matching for fuzzy SEARCH_FILES.
+//!
+//! Names and queries are split into lowercase tokens at separators, case
+//! changes (`parseHTTPHeader` → parse, http, header) and digit boundaries.
+//! Every query token has to match some name token, in any order: exactly,
+//! as a prefix, inside it, or within a small edit distance. Queries that do
+//! not tokenise cleanly against the name fall back to subsequence matching
+//! (`rdme` → `README.md`). The text score is then boosted for shallow,
+//! recently modified and recently used paths.
+
+use chrono::Utc;
+
+use super::query::Candidate;
+use crate::protocol::MatchRank;
+
+/// Weight of the text match against the boosts below.
+const TEXT_WEIGHT: f64 = 100.0;
+/// Bonus for an entry directly below the search root, lost per level.
+const SHALLOW_BONUS: f64 = 10.0;
+/// Bonus for a path the client used recently, or for something below one.
+const RECENT_PATH_BONUS: f64 = 20.0;
+const RECENT_PARENT_BONUS: f64 = 8.0;
+
+pub struct Ranker<'a> {
+    query: Option<Text>,
+    recent: &'a [String],
+    now: i64,
+}
+
+impl<'a> Ranker<'a> {
+    /// A ranker for the free text of a query; `recent` lists paths the
+    /// client opened recently, most recent first.
+    pub fn new(text: &str, recent: &'a [String]) -> Self {
+        let query = Text::new(text);
+        Ranker {
+            query: (!query.tokens.is_empty()).then_some(query),
+            recent,
+            now: Utc::now().timestamp(),
+        }
+    }
+
+    /// How well `name` matches the text, from 0 to about 1, with the
+    /// matched ranges of `name` in UTF-16 code units. `None` if it does not
+    /// match at all; a query without text matches everything with 0.
+    pub fn text_score(&self, name: &str) -> Option<(f64, Vec<[usize; 2]>)> {
+        match &self.query {
+            Some(query) => query.score(name),
+            None => Some((0.0, Vec::new())),
+        }
+    }
+
+    /// The final score of a match at `path`.
+    pub fn rank(&self, text: (f64, Vec<[usize; 2]>), candidate: &Candidate, path: &str) -> MatchRank {
+        let (score, ranges) = text;
+        let shallow = (SHALLOW_BONUS - candidate.depth.saturating_sub(1) as f64).max(0.0);
+        let age_days = (self.now - candidate.modified) as f64 / 86_400.0;
+        let recency = match candidate.modified {
+            0 => 0.0,
+            _ if age_days < 1.0 => 8.0,
+            _ if age_days < 7.0 => 5.0,
+            _ if age_days < 30.0 => 2.0,
+            _ => 0.0,
+        };
+        let used = self
+            .recent
+            .iter()
+            .enumerate()
+            .filter_map(|(i, recent)| {
+                // Earlier entries in the list count a little more.
+                let fade = 1.0 - (i.min(10) as f64) / 20.0;
+                let recent = recent.trim_end_matches(['/', '\\']);
+                // A root (`/`, `C:\`) is the parent of everything.
+                if recent.is_empty() || recent.ends_with(':') {
+                    return None;
+                }
+                if path == recent {
+                    Some(RECENT_PATH_BONUS * fade)
+                } else if path.starts_with(recent) && path[recent.len()..].starts_with(['/', '\\']) {
+                    Some(RECENT_PARENT_BONUS * fade)
+                } else {
+                    None
+                }
+            })
+            .fold(0.0, f64::max);
+        MatchRank {
+            score: ((score * TEXT_WEIGHT + shallow + recency + used) * 100.0).round() / 100.0,
+            ranges,
+        }
+    }
+}
+
+// =======================
+// Text matching
+// =======================
+
+/// Lowercased characters of a string, one per original character, with
+/// token spans as character indexes.
+struct Text {
+    chars: Vec<char>,
+    tokens: Vec<(usize, usize)>,
+}
+
+impl Text {
+    fn new(text: &str) -> Self {
+        let original: Vec<char> = text.chars().collect();
+        // One lowercase char per char keeps indexes aligned with `original`.
+        let chars: Vec<char> = original
+            .iter()
+            .map(|c| c.to_lowercase().next().unwrap_or(*c))
+            .collect();
+        Text {
+            tokens: tokenize(&original),
+            chars,
+        }
+    }
+
+    fn token(&self, (start, end): (usize, usize)) -> &[char] {
+        &self.chars[start..end]
+    }
+
+    fn score(&self, name: &str) -> Option<(f64, Vec<[usize; 2]>)> {
+        let name = Text::new(name);
+        if name.chars.is_empty() {
+            return None;
+        }
+
+        // The whole name, or the name without its extension.
+        let compact: Vec<char> = self.tokens.iter().flat_map(|&t| self.token(t).iter().copied()).collect();
+        let stem_end = name.chars.iter().rposition(|&c| c == '.').filter(|&i| i > 0).unwrap_or(name.chars.len());
+        let query_chars: Vec<char> = self.chars.iter().copied().filter(|c| !c.is_whitespace()).collect();
+        if query_chars == name.chars || query_chars == name.chars[..stem_end] {
+            return Some((1.2, name.ranges(&[(0, query_chars.len())])));
+        }
+
+        if let Some((score, spans)) = self.match_tokens(&name) {
+            return Some((score, name.ranges(&spans)));
+        }
+
+        // `rdme`, `frmgr`: the query's letters in order, anywhere.
+        let positions = subsequence(&compact, &name)?;
+        let spans = runs(&positions);
+        let boundaries = positions
+            .iter()
+            .filter(|&&p| name.tokens.iter().any(|&(start, _)| start == p))
+            .count();
+        let tightness = compact.len() as f64 / (positions[positions.len() - 1] - positions[0] + 1) as f64;
+        let score = 0.15 + 0.2 * tightness + 0.15 * (boundaries as f64 / compact.len() as f64);
+        Some((score, name.ranges(&spans)))
+    }
+
+    /// Match every query token against some name token.
+    fn match_tokens(&self, name: &Text) -> Option<(f64, Vec<(usize, usize)>)> {
+        let mut total = 0.0;
+        let mut spans = Vec::new();
+        let mut used = Vec::new();
+        let mut in_order = true;
+        let mut last = None;
+        for &query_token in &self.tokens {
+            let query = self.token(query_token);
+            let (score, span, token) = name
+                .tokens
+                .iter()
+                .enumerate()
+                .filter_map(|(i, &(start, end))| {
+                    token_score(query, &name.chars[start..end])
+                        .map(|(score, from, to)| (score, (start + from, start + to), i))
+                })
+                .filter(|(_, _, i)| !used.contains(i))
+                .max_by(|a, b| a.0.total_cmp(&b.0))
+                .or_else(|| {
+                    // Inside the name across token boundaries, e.g. `ead` in `README`.
+                    let at = find(&name.chars, query)?;
+                    Some((0.45, (at, at + query.len()), usize::MAX))
+                })?;
+            if token != usize::MAX {
+                used.push(token);
+                in_order &= last.is_none_or(|last| token > last);
+                last = Some(token);
+            }
+            total += score;
+            spans.push(span);
+        }
+
+        let mut score = total / self.tokens.len() as f64;
+        if in_order {
+            score += 0.05;
+        }
+        // Prefer names the query covers more of.
+        let covered: usize = spans.iter().map(|(start, end)| end - start).sum();
+        let letters = name.chars.iter().filter(|c| c.is_alphanumeric()).count().max(1);
+        score += 0.1 * (covered.min(letters) as f64 / letters as f64);
+        spans.sort_unstable();
+        Some((score, spans))
+    }
+
+    /// Character spans as UTF-16 ranges, merging touching spans.
+    fn ranges(&self, spans: &[(usize, usize)]) -> Vec<[usize; 2]> {
+        let mut offsets = Vec::with_capacity(self.chars.len() + 1);
+        let mut at = 0;
+        offsets.push(0);
+        for c in &self.chars {
+            at += c.len_utf16();
+            offsets.push(at);
+        }
+        let mut ranges: Vec<[usize; 2]> = Vec::new();
+        for &(start, end) in spans {
+            let range = [offsets[start], offsets[end]];
+            match ranges.last_mut() {
+                Some(last) if last[1] >= range[0] => last[1] = last[1].max(range[1]),
+                _ => ranges.push(range),
+            }
+        }
+        ranges
+    }
+}
+
+/// How well one query token matches one name token, and the span of the
+/// name token it covers.
+fn token_score(query: &[char], token: &[char]) -> Option<(f64, usize, usize)> {
+    if query == token {
+        return Some((1.0, 0, token.len()));
+    }
+    if token.starts_with(query) {
+        return Some((0.8 + 0.15 * query.len() as f64 / token.len() as f64, 0, query.len()));
+    }
+    if let Some(at) = find(token, query) {
+        return Some((0.55, at, at + query.len()));
+    }
+    if query.len() < 3 {
+        return None;
+    }
+    // Typos: against the whole token, or the part of it the query spans.
+    let allowed = if query.len() <= 4 { 1 } else { 2 };
+    let whole = edit_distance(query, token, allowed);
+    let prefix = (token.len() > query.len())
+        .then(|| edit_distance(query, &token[..query.len()], allowed))
+        .flatten();
+    match (whole, prefix) {
+        (Some(d), _) => Some((0.7 - 0.15 * d as f64, 0, token.len())),
+        (None, Some(d)) => Some((0.6 - 0.15 * d as f64, 0, query.len())),
+        (None, None) => None,
+    }
+}
+
+/// Split into tokens at non-alphanumerics, lower→upper case changes, the
+/// end of an acronym (`HTMLParser` → HTML, Parser) and letter/digit changes.
+fn tokenize(chars: &[char]) -> Vec<(usize, usize)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for i in 0..chars.len() {
+        let c = chars[i];
+        if !c.is_alphanumeric() {
+            if let Some(s) = start.take() {
+                tokens.push((s, i));
+            }
+            continue;
+        }
+        if let Some(s) = start {
+            let prev = chars[i - 1];
+            let next = chars.get(i + 1).copied();
+            let boundary = (prev.is_lowercase() && c.is_uppercase())
+                || (prev.is_uppercase() && c.is_uppercase() && next.is_some_and(|n| n.is_lowercase()))
+                || (prev.is_numeric() != c.is_numeric());
+            if boundary {
+                tokens.push((s, i));
+                start = Some(i);
+            }
+        } else {
+            start = Some(i);
+        }
+    }
+    if let Some(s) = start {
+        tokens.push((s, chars.len()));
+    }
+    tokens
+}
+
+fn find(haystack: &[char], needle: &[char]) -> Option<usize> {
+    if needle.is_empty() || needle.len() > haystack.len() {
+        return None;
+    }
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Positions of `query`'s characters in `name`, in order, preferring the
+/// start of tokens: a greedy forward pass, then a backward pass that pulls
+/// the match as tight as it goes.
+fn subsequence(query: &[char], name: &Text) -> Option<Vec<usize>> {
+    if query.is_empty() {
+        return None;
+    }
+    let chars = &name.chars;
+    let mut end = 0;
+    let mut q = 0;
+    for (i, c) in chars.iter().enumerate() {
+        if *c == query[q] {
+            q += 1;
+            if q == query.len() {
+                end = i;
+                break;
+            }
+        }
+    }
+    if q < query.len() {
+        return None;
+    }
+    let mut positions = vec![0; query.len()];
+    let mut i = end + 1;
+    for q in (0..query.len()).rev() {
+        i = (0..i).rev().find(|&j| chars[j] == query[q])?;
+        positions[q] = i;
+    }
+    Some(positions)
+}
+
+/// Consecutive positions as spans.
+fn runs(positions: &[usize]) -> Vec<(usize, usize)> {
+    let mut spans: Vec<(usize, usize)> = Vec::new();
+    for &p in positions {
+        match spans.last_mut() {
+            Some(last) if last.1 == p => last.1 = p + 1,
+            _ => spans.push((p, p + 1)),
+        }
+    }
+    spans
+}
+
+/// Optimal string alignment distance (Levenshtein plus adjacent
+/// transpositions), or `None` once it exceeds `max`.
+fn edit_distance(a: &[char], b: &[char], max: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+    // Rows i-2, i-1 and i of the distance table.
+    let mut before = vec![0; b.len() + 1];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        current[0] = i;
+        let mut best = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut d = (previous[j] + 1).min(current[j - 1] + 1).min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d = d.min(before[j - 2] + 1);
+            }
+            current[j] = d;
+            best = best.min(d);
+        }
+        if best > max {
+            return None;
+        }
+        std::mem::swap(&mut before, &mut previous);
+        std::mem::swap(&mut previous, &mut current);
+    }
+    let d = previous[b.len()];
+    (d <= max).then_some(d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::Kind;
+
+    fn chars(text: &str) -> Vec<char> {
+        text.chars().collect()
+    }
+
+    fn tokens(text: &str) -> Vec<String> {
+        let chars = chars(text);
+        tokenize(&chars).into_iter().map(|(start, end)| chars[start..end].iter().collect()).collect()
+    }
+
+    fn distance(a: &str, b: &str, max: usize) -> Option<usize> {
+        edit_distance(&chars(a), &chars(b), max)
+    }
+
+    fn score(query: &str, name: &str) -> Option<(f64, Vec<[usize; 2]>)> {
+        Ranker::new(query, &[]).text_score(name)
+    }
+
+    #[test]
+    fn measures_edit_distance() {
+        assert_eq!(distance("kitten", "sitting", 3), Some(3));
+        assert_eq!(distance("kitten", "sitting", 2), None);
+        assert_eq!(distance("form", "from", 1), Some(1));
+        assert_eq!(distance("same", "same", 0), Some(0));
+        assert_eq!(distance("", "abc", 3), Some(3));
+        assert_eq!(distance("a", "abcd", 2), None);
+    }
+
+    #[test]
+    fn splits_case_changes_acronyms_and_digits() {
+        assert_eq!(tokens("parseHTTPHeader"), ["parse", "HTTP", "Header"]);
+        assert_eq!(tokens("HTMLParser"), ["HTML", "Parser"]);
+        assert_eq!(tokens("file_v2.tar.gz"), ["file", "v", "2", "tar", "gz"]);
+        assert_eq!(tokens("--README--"), ["README"]);
+        assert_eq!(tokens("ÉtéFichier"), ["Été", "Fichier"]);
+        assert!(tokens("._-").is_empty());
+    }
+
+    #[test]
+    fn scores_tokens() {
+        let score = |query: &str, token: &str| token_score(&chars(query), &chars(token));
+        assert_eq!(score("main", "main"), Some((1.0, 0, 4)));
+        assert_eq!(score("ma", "main"), Some((0.8 + 0.15 * 0.5, 0, 2)));
+        assert_eq!(score("ai", "main"), Some((0.55, 1, 3)));
+        assert_eq!(score("xy", "main"), None);
+        // One transposition against the whole token.
+        assert_eq!(score("raedme", "readme"), Some((0.7 - 0.15, 0, 6)));
+        // A typo in the part of a longer token the query spans.
+        assert_eq!(score("confgi", "configuration"), Some((0.6 - 0.15, 0, 6)));
+        assert_eq!(score("abcd", "wxyz"), None);
+    }
+
+    #[test]
+    fn ranks_exact_names_first() {
+        let exact = score("readme", "README.md").unwrap();
+        assert_eq!(exact, (1.2, vec![[0, 6]]));
+        let prefix = score("read", "README.md").unwrap();
+        let typo = score("raedme", "README.md").unwrap();
+        assert!(exact.0 > prefix.0 && prefix.0 > typo.0);
+        assert!(score("zzz", "README.md").is_none());
+        assert!(score("", "README.md").is_some_and(|(score, _)| score == 0.0));
+    }
+
+    #[test]
+    fn matches_tokens_in_any_order() {
+        let (in_order, ranges) = score("file manager", "FileManager.ts").unwrap();
+        assert_eq!(ranges, [[0, 11]]);
+        let (reversed, _) = score("manager file", "FileManager.ts").unwrap();
+        assert!(in_order > reversed);
+        assert!(score("file missing", "FileManager.ts").is_none());
+    }
+
+    #[test]
+    fn falls_back_to_subsequences() {
+        let (score, ranges) = score("rdme", "README.md").unwrap();
+        assert!(score > 0.0 && score < 0.55);
+        assert_eq!(ranges, [[0, 1], [3, 6]]);
+    }
+
+    #[test]
+    fn reports_utf16_ranges() {
+        let (_, ranges) = score("notes", "🎵 notes.txt").unwrap();
+        assert_eq!(ranges, [[3, 8]]);
+    }
+
+    #[test]
+    fn boosts_recent_paths_but_not_roots() {
+        let candidate = Candidate {
+            name: "b.txt",
+            relative: "a/b.txt",
+            depth: 2,
+            kind: Kind::File,
+            size: 0,
+            modified: 0,
+            mode: None,
+            uid: None,
+            gid: None,
+            owner: None,
+            group: None,
+        };
+        let rank = |recent: &[&str], path: &str| {
+            let recent: Vec<String> = recent.iter().map(|r| r.to_string()).collect();
+            Ranker::new("", &recent).rank((0.0, Vec::new()), &candidate, path).score
+        };
+        let base = rank(&[], "/home/a/b.txt");
+        assert_eq!(base, SHALLOW_BONUS - 1.0);
+        assert_eq!(rank(&["/home/a/b.txt"], "/home/a/b.txt"), base + RECENT_PATH_BONUS);
+        assert_eq!(rank(&["/home/a/"], "/home/a/b.txt"), base + RECENT_PARENT_BONUS);
+        assert_eq!(rank(&["/home/ab"], "/home/ab.txt"), base);
+        assert_eq!(rank(&["/", "C:\\", "D:"], "/home/a/b.txt"), base);
+        assert_eq!(rank(&["C:\\"], "C:\\a\\b.txt"), base);
+        assert_eq!(rank(&["C:\\a"], "C:\\a\\b.txt"), base + RECENT_PARENT_BONUS);
+        // Later entries in the list fade.
+        assert!(rank(&["/x", "/home/a/b.txt"], "/home/a/b.txt") < base + RECENT_PATH_BONUS);
+    }
+}
@@ -0,0 +1,967 @@
+//! A persistent index of the names and metadata of everything below the
+//! configured roots, answering QUERY_INDEX without touching the disk.
+//!
+//! Roots come from REINDEX (or `FILE_MANAGER_INDEX_ROOTS` at startup) and
+//! are saved with the index. A full scan builds each root's tree; after
+//! that, filesystem events update it in place and the index is written back
+//! to disk every so often. Events that arrive while a root is being scanned
+//! are held back and replayed on the new tree.
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::Utc;
+use globset::GlobSet;
+use notify::{RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use walkdir::WalkDir;
+
+use super::query::{Candidate, Kind, SearchQuery};
+use crate::archive::build_globset;
+use crate::jobs::JobHandle;
+use crate::protocol::{
+    FileInfo, FileType, IndexRootStatus, IndexState, IndexStatus, JobInfo, ResponseData, SearchResult,
+};
+
+/// Bumped whenever the on-disk layout changes; older files are ignored.
+const FORMAT_VERSION: u32 = 1;
+
+/// Roots to index when none are saved, separated like `PATH`.
+const ROOTS_ENV: &str = "FILE_MANAGER_INDEX_ROOTS";
+/// Overrides where the index is saved.
+const INDEX_FILE_ENV: &str = "FILE_MANAGER_INDEX_FILE";
+
+/// Events are collected this long before they are applied together.
+const EVENT_BATCH_DELAY: Duration = Duration::from_millis(250);
+/// A changed index is written back at most this often.
+const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+pub const DEFAULT_QUERY_LIMIT: usize = 1000;
+
+/// Parent of a root's top node.
+const NO_PARENT: u32 = u32::MAX;
+
+// =======================
+// Trees
+// =======================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Node {
+    /// File name; the full path for a root's top node.
+    name: String,
+    parent: u32,
+    /// Levels below the root.
+    depth: u32,
+    kind: Kind,
+    size: u64,
+    modified: i64,
+    mode: Option<u32>,
+    uid: Option<u32>,
+    gid: Option<u32>,
+    /// Live children of a directory.
+    children: Vec<u32>,
+    /// Cleared when the node is removed; its slot is reused.
+    live: bool,
+}
+
+impl Node {
+    fn new(name: String, parent: u32, depth: u32, metadata: &fs::Metadata) -> Self {
+        let c = Candidate::from_metadata("", "", 0, metadata);
+        Node {
+            name,
+            parent,
+            depth,
+            kind: c.kind,
+            size: if c.kind == Kind::Directory { 0 } else { c.size },
+            modified: c.modified,
+            mode: c.mode,
+            uid: c.uid,
+            gid: c.gid,
+            children: Vec::new(),
+            live: true,
+        }
+    }
+}
+
+/// The tree below one root. Node 0 is the root directory itself.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Tree {
+    nodes: Vec<Node>,
+    free: Vec<u32>,
+    files: u64,
+    directories: u64,
+}
+
+impl Tree {
+    fn insert(&mut self, node: Node) -> u32 {
+        match node.kind {
+            Kind::Directory => self.directories += 1,
+            _ => self.files += 1,
+        }
+        let parent = node.parent;
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.nodes[id as usize] = node;
+                id
+            }
+            None => {
+                self.nodes.push(node);
+                (self.nodes.len() - 1) as u32
+            }
+        };
+        if parent != NO_PARENT {
+            self.nodes[parent as usize].children.push(id);
+        }
+        id
+    }
+
+    /// Remove a node and everything below it.
+    fn remove(&mut self, id: u32) {
+        let parent = self.nodes[id as usize].parent;
+        if parent != NO_PARENT {
+            self.nodes[parent as usize].children.retain(|&c| c != id);
+        }
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            let node = &mut self.nodes[id as usize];
+            node.live = false;
+            match node.kind {
+                Kind::Directory => self.directories -= 1,
+                _ => self.files -= 1,
+            }
+            stack.append(&mut node.children);
+            node.name = String::new();
+            self.free.push(id);
+        }
+    }
+
+    fn child(&self, parent: u32, name: &str) -> Option<u32> {
+        self.nodes[parent as usize]
+            .children
+            .iter()
+            .copied()
+            .find(|&c| self.nodes[c as usize].name == name)
+    }
+
+    /// The node at `components` below the root.
+    fn find(&self, components: &[String]) -> Option<u32> {
+        components.iter().try_fold(0, |id, name| self.child(id, name))
+    }
+
+    fn path(&self, id: u32) -> PathBuf {
+        let mut names = Vec::new();
+        let mut id = id;
+        while id != NO_PARENT {
+            let node = &self.nodes[id as usize];
+            names.push(node.name.as_str());
+            id = node.parent;
+        }
+        names.iter().rev().collect()
+    }
+
+    /// `/`-separated path of `id` below `base`.
+    fn relative(&self, id: u32, base: u32) -> String {
+        let mut names = Vec::new();
+        let mut id = id;
+        while id != base && id != NO_PARENT {
+            let node = &self.nodes[id as usize];
+            names.push(node.name.as_str());
+            id = node.parent;
+        }
+        names.reverse();
+        names.join("/")
+    }
+
+    /// Index `dir` (already present as `id`) and everything below it.
+    fn scan_below(&mut self, id: u32, dir: &Path, exclude: &GlobSet, job: Option<&JobHandle>) -> Result<()> {
+        let base_depth = self.nodes[id as usize].depth;
+        // stack[d] is the directory at walk depth d.
+        let mut stack = vec![id];
+        let walker = WalkDir::new(dir)
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(|e| !is_excluded(exclude, e.path()));
+        for entry in walker {
+            let Ok(entry) = entry else {
+                continue;
+            };
+            if let Some(job) = job {
+                job.check_cancelled()?;
+                job.finish_item();
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            stack.truncate(entry.depth());
+            let parent = stack[entry.depth() - 1];
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let node = Node::new(name, parent, base_depth + entry.depth() as u32, &metadata);
+            let is_dir = node.kind == Kind::Directory;
+            let child = self.insert(node);
+            if is_dir {
+                stack.push(child);
+            }
+        }
+        Ok(())
+    }
+
+    /// Merge everything below `from`'s root into the directory `id`. Entries
+    /// already present (added by events since `from` was scanned) are kept
+    /// and merged into.
+    fn graft(&mut self, id: u32, from: &Tree) {
+        let mut stack = vec![(0, id)];
+        while let Some((source, target)) = stack.pop() {
+            for &child in &from.nodes[source as usize].children {
+                let node = &from.nodes[child as usize];
+                if let Some(existing) = self.child(target, &node.name) {
+                    if self.nodes[existing as usize].kind == Kind::Directory && node.kind == Kind::Directory {
+                        stack.push((child, existing));
+                    }
+                    continue;
+                }
+                let depth = self.nodes[target as usize].depth + 1;
+                let copy = Node {
+                    parent: target,
+                    depth,
+                    children: Vec::new(),
+                    ..node.clone()
+                };
+                let inserted = self.insert(copy);
+                if node.kind == Kind::Directory {
+                    stack.push((child, inserted));
+                }
+            }
+        }
+    }
+}
+
+// =======================
+// Index state
+// =======================
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Root {
+    path: String,
+    /// Shared with running queries, which read it without the lock; changes
+    /// copy it when a query still holds it.
+    tree: Arc<Tree>,
+    last_indexed: Option<i64>,
+    #[serde(skip)]
+    state: IndexState,
+    /// Why the last scan failed.
+    #[serde(skip)]
+    error: Option<String>,
+    #[serde(skip)]
+    watching: bool,
+    /// Why the root is not watched for changes.
+    #[serde(skip)]
+    watch_error: Option<String>,
+    /// Paths changed while a scan was running, replayed on the new tree.
+    #[serde(skip)]
+    pending: Option<Vec<PathBuf>>,
+}
+
+impl Root {
+    fn new(path: String) -> Self {
+        Root {
+            path,
+            tree: Arc::default(),
+            last_indexed: None,
+            state: IndexState::Pending,
+            error: None,
+            watching: false,
+            watch_error: None,
+            pending: None,
+        }
+    }
+
+    /// Bring the node for `path` in line with the disk. Directories that
+    /// are new here go to `unscanned`; the caller fills them in with
+    /// `scan_new` once it has let go of the index.
+    fn refresh(&mut self, path: &Path, exclude: &GlobSet, unscanned: &mut Vec<PathBuf>) {
+        let Some(components) = self.components(path) else {
+            return;
+        };
+        if self.tree.nodes.is_empty() || components.is_empty() {
+            return;
+        }
+        let tree = Arc::make_mut(&mut self.tree);
+        let existing = tree.find(&components);
+        let metadata = if is_excluded(exclude, path) {
+            None
+        } else {
+            fs::symlink_metadata(path).ok()
+        };
+        let Some(metadata) = metadata else {
+            if let Some(id) = existing {
+                tree.remove(id);
+            }
+            return;
+        };
+
+        if let Some(id) = existing {
+            let node = &mut tree.nodes[id as usize];
+            let updated = Node::new(node.name.clone(), node.parent, node.depth, &metadata);
+            if updated.kind == node.kind {
+                node.size = updated.size;
+                node.modified = updated.modified;
+                node.mode = updated.mode;
+                node.uid = updated.uid;
+                node.gid = updated.gid;
+                return;
+            }
+            tree.remove(id);
+        }
+
+        // New here: make sure the parent is indexed, then add the node and,
+        // for a directory moved in from elsewhere, everything below it.
+        let (name, parents) = components.split_last().expect("components is not empty");
+        if tree.find(parents).is_none() {
+            let Some(dir) = path.parent() else {
+                return;
+            };
+            self.refresh(dir, exclude, unscanned);
+        }
+        let tree = Arc::make_mut(&mut self.tree);
+        let Some(parent) = tree.find(parents) else {
+            return;
+        };
+        let depth = tree.nodes[parent as usize].depth + 1;
+        tree.insert(Node::new(name.clone(), parent, depth, &metadata));
+        if metadata.is_dir() {
+            unscanned.push(path.to_path_buf());
+        }
+    }
+
+    /// Names leading from the root to `path`.
+    fn components(&self, path: &Path) -> Option<Vec<String>> {
+        let relative = path.strip_prefix(&self.path).ok()?;
+        Some(
+            relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect(),
+        )
+    }
+
+    fn status(&self) -> IndexRootStatus {
+        IndexRootStatus {
+            path: self.path.clone(),
+            state: self.state,
+            files: self.tree.files,
+            directories: self.tree.directories,
+            last_indexed: self.last_indexed,
+            watching: self.watching,
+            error: self.error.clone().or_else(|| self.watch_error.clone()),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Index {
+    roots: Vec<Root>,
+    exclude: Vec<String>,
+    excluded: GlobSet,
+    watcher: Option<notify::RecommendedWatcher>,
+    dirty: bool,
+    last_saved: Option<i64>,
+}
+
+impl Index {
+    fn root_of(&mut self, path: &Path) -> Option<&mut Root> {
+        self.roots
+            .iter_mut()
+            .filter(|root| path.starts_with(&root.path))
+            .max_by_key(|root| root.path.len())
+    }
+}
+
+/// What is written to disk.
+#[derive(Serialize, Deserialize)]
+struct Saved {
+    version: u32,
+    exclude: Vec<String>,
+    roots: Vec<Root>,
+}
+
+fn index() -> &'static Mutex<Index> {
+    static INDEX: OnceLock<Mutex<Index>> = OnceLock::new();
+    INDEX.get_or_init(|| Mutex::new(Index::default()))
+}
+
+/// Where filesystem watchers send their events.
+fn events() -> &'static Mutex<Option<Sender<notify::Result<notify::Event>>>> {
+    static EVENTS: OnceLock<Mutex<Option<Sender<notify::Result<notify::Event>>>>> = OnceLock::new();
+    EVENTS.get_or_init(|| Mutex::new(None))
+}
+
+fn index_file() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os(INDEX_FILE_ENV) {
+        return Some(PathBuf::from(path));
+    }
+    dirs::data_local_dir().map(|dir| dir.join("file-manager").join("search-index.bin"))
+}
+
+fn is_excluded(exclude: &GlobSet, path: &Path) -> bool {
+    !exclude.is_empty()
+        && (exclude.is_match(path) || path.file_name().is_some_and(|name| exclude.is_match(name)))
+}
+
+// =======================
+// Lifecycle
+// =======================
+
+/// Load the saved index, start the event thread and bring every root up
+/// to date in the background. Queries are answered from the saved index
+/// meanwhile.
+pub fn start() {
+    let saved = match load() {
+        Ok(saved) => saved,
+        Err(e) => {
+            tracing::warn!("Ignoring saved search index: {:#}", e);
+            None
+        }
+    };
+    let (tx, rx) = mpsc::channel();
+    *events().lock().unwrap() = Some(tx);
+    std::thread::spawn(move || watch_events(rx));
+
+    let mut roots: Vec<String> = Vec::new();
+    let mut exclude = Vec::new();
+    if let Some(saved) = saved {
+        let mut index = index().lock().unwrap();
+        index.excluded = build_globset(&saved.exclude).unwrap_or_default();
+        index.exclude = saved.exclude.clone();
+        exclude = saved.exclude;
+        roots = saved.roots.iter().map(|root| root.path.clone()).collect();
+        index.roots = saved.roots;
+        for root in &mut index.roots {
+            root.state = IndexState::Ready;
+        }
+    }
+    if roots.is_empty() {
+        if let Some(paths) = std::env::var_os(ROOTS_ENV) {
+            roots = std::env::split_paths(&paths)
+                .map(|p| p.to_string_lossy().into_owned())
+                .collect();
+        }
+    }
+    if roots.is_empty() {
+        return;
+    }
+    match reindex(Some(roots), Some(exclude)) {
+        Ok(job) => tracing::info!("Refreshing search index (job {})", job.job_id),
+        Err(e) => tracing::warn!("Search index not started: {:#}", e),
+    }
+}
+
+fn load() -> Result<Option<Saved>> {
+    let Some(path) = index_file() else {
+        return Ok(None);
+    };
+    let file = match fs::File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Cannot open {}", path.display())),
+    };
+    let saved: Saved = bincode::deserialize_from(BufReader::new(file))
+        .with_context(|| format!("Cannot read {}", path.display()))?;
+    if saved.version != FORMAT_VERSION {
+        bail!("{} has format version {}", path.display(), saved.version);
+    }
+    tracing::info!("Loaded search index from {}", path.display());
+    Ok(Some(saved))
+}
+
+/// Write the index to disk, replacing the previous file in one step. The
+/// lock is only held to take a snapshot, whose trees are shared, so queries
+/// and events are not held up while it is written out.
+fn save() -> Result<()> {
+    let Some(path) = index_file() else {
+        return Ok(());
+    };
+    // One save at a time, so an older snapshot never replaces a newer one.
+    static SAVING: Mutex<()> = Mutex::new(());
+    let _saving = SAVING.lock().unwrap();
+    let saved = {
+        let mut index = index().lock().unwrap();
+        // Cleared up front: changes made while writing mark it again.
+        index.dirty = false;
+        Snapshot {
+            version: FORMAT_VERSION,
+            exclude: index.exclude.clone(),
+            roots: index.roots.iter().map(SavedRoot::of).collect(),
+        }
+    };
+    let written = write_index(&path, &saved);
+    let mut index = index().lock().unwrap();
+    match written {
+        Ok(()) => index.last_saved = Some(Utc::now().timestamp()),
+        Err(_) => index.dirty = true,
+    }
+    written
+}
+
+fn write_index(path: &Path, saved: &Snapshot) -> Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(dir).with_context(|| format!("Cannot create {}", dir.display()))?;
+    let staging = tempfile::NamedTempFile::new_in(dir)?;
+    let mut writer = BufWriter::new(staging.as_file());
+    bincode::serialize_into(&mut writer, saved)?;
+    // Flush explicitly: a failed write must not install a truncated index.
+    writer.flush()?;
+    drop(writer);
+    staging.as_file().sync_all()?;
+    staging.persist(path)?;
+    Ok(())
+}
+
+/// `Saved`, as taken by `save` for writing.
+#[derive(Serialize)]
+struct Snapshot {
+    version: u32,
+    exclude: Vec<String>,
+    roots: Vec<SavedRoot>,
+}
+
+/// The saved fields of a `Root`, in the same order, so it reads back as one.
+#[derive(Serialize)]
+struct SavedRoot {
+    path: String,
+    tree: Arc<Tree>,
+    last_indexed: Option<i64>,
+}
+
+impl SavedRoot {
+    fn of(root: &Root) -> Self {
+        SavedRoot {
+            path: root.path.clone(),
+            tree: Arc::clone(&root.tree),
+            last_indexed: root.last_indexed,
+        }
+    }
+}
+
+/// Index what is below the directories `refresh` added. Each is walked
+/// without the lock, which is only taken to merge the result in.
+fn scan_new(mut unscanned: Vec<PathBuf>) {
+    unscanned.sort();
+    unscanned.dedup_by(|dir, parent| dir.starts_with(parent));
+    for dir in unscanned {
+        let excluded = index().lock().unwrap().excluded.clone();
+        let Ok(metadata) = fs::metadata(&dir) else {
+            continue;
+        };
+        let mut scanned = Tree::default();
+        scanned.insert(Node::new(String::new(), NO_PARENT, 0, &metadata));
+        if scanned.scan_below(0, &dir, &excluded, None).is_err() {
+            continue;
+        }
+
+        let mut index = index().lock().unwrap();
+        let Some(root) = index.root_of(&dir) else {
+            continue;
+        };
+        let Some(components) = root.components(&dir) else {
+            continue;
+        };
+        let Some(id) = root.tree.find(&components) else {
+            continue;
+        };
+        if root.tree.nodes[id as usize].kind == Kind::Directory {
+            Arc::make_mut(&mut root.tree).graft(id, &scanned);
+            index.dirty = true;
+        }
+    }
+}
+
+/// Apply filesystem events in batches and save the index now and then.
+fn watch_events(rx: Receiver<notify::Result<notify::Event>>) {
+    let mut last_save = Instant::now();
+    loop {
+        let mut changed = BTreeSet::new();
+        let mut rescan = BTreeSet::new();
+        match rx.recv_timeout(SAVE_INTERVAL) {
+            Ok(event) => {
+                collect_event(event, &mut changed, &mut rescan);
+                let deadline = Instant::now() + EVENT_BATCH_DELAY;
+                while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+                    match rx.recv_timeout(left) {
+                        Ok(event) => collect_event(event, &mut changed, &mut rescan),
+                        Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+
+        let mut index = index().lock().unwrap();
+        let mut unscanned = Vec::new();
+        if !changed.is_empty() {
+            let excluded = index.excluded.clone();
+            for path in &changed {
+                let Some(root) = index.root_of(path) else {
+                    continue;
+                };
+                match &mut root.pending {
+                    Some(pending) => pending.push(path.clone()),
+                    None => root.refresh(path, &excluded, &mut unscanned),
+                }
+            }
+            index.dirty = true;
+        }
+        if !unscanned.is_empty() {
+            drop(index);
+            scan_new(unscanned);
+            index = self::index().lock().unwrap();
+        }
+        let save_due = index.dirty && last_save.elapsed() >= SAVE_INTERVAL;
+        let roots: Vec<String> = index
+            .roots
+            .iter()
+            .filter(|root| rescan.iter().any(|path: &PathBuf| path.starts_with(&root.path)))
+            .map(|root| root.path.clone())
+            .collect();
+        drop(index);
+        if save_due {
+            last_save = Instant::now();
+            if let Err(e) = save() {
+                tracing::error!("Failed to save search index: {:#}", e);
+            }
+        }
+        if !roots.is_empty() {
+            // The watcher lost track (queue overflow); only a scan can tell.
+            tracing::warn!("Search index lost events; rescanning {}", roots.join(", "));
+            let _ = crate::jobs::spawn("REINDEX", move |job| scan_roots(&roots, job));
+        }
+    }
+}
+
+fn collect_event(
+    event: notify::Result<notify::Event>,
+    changed: &mut BTreeSet<PathBuf>,
+    rescan: &mut BTreeSet<PathBuf>,
+) {
+    match event {
+        Ok(event) if event.need_rescan() => rescan.extend(event.paths),
+        Ok(event) => match event.kind {
+            notify::EventKind::Access(_) => {}
+            _ => changed.extend(event.paths),
+        },
+        Err(e) => {
+            tracing::warn!("Filesystem watch error: {}", e);
+            rescan.extend(e.paths);
+        }
+    }
+}
+
+// =======================
+// Commands
+// =======================
+
+pub fn status() -> IndexStatus {
+    let index = index().lock().unwrap();
+    IndexStatus {
+        roots: index.roots.iter().map(Root::status).collect(),
+        exclude: index.exclude.clone(),
+        total_files: index.roots.iter().map(|root| root.tree.files).sum(),
+        total_directories: index.roots.iter().map(|root| root.tree.directories).sum(),
+        index_file: index_file().map(|path| path.to_string_lossy().into_owned()),
+        last_saved: index.last_saved,
+    }
+}
+
+/// Rescan the index in a background job. `roots` and `exclude`, when
+/// given, replace the configured ones; roots no longer listed are dropped.
+pub fn reindex(roots: Option<Vec<String>>, exclude: Option<Vec<String>>) -> Result<JobInfo> {
+    let roots = {
+        let mut index = index().lock().unwrap();
+        if let Some(exclude) = exclude {
+            index.excluded = build_globset(&exclude)?;
+            index.exclude = exclude;
+        }
+        if let Some(roots) = roots {
+            let mut paths = Vec::new();
+            for root in roots {
+                let path = Path::new(&root);
+                if !path.is_dir() {
+                    bail!("Index root is not a directory: {}", root);
+                }
+                let path = path.to_string_lossy().trim_end_matches(['/', '\\']).to_string();
+                let path = if path.is_empty() { root.clone() } else { path };
+                if !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+            let mut kept = std::mem::take(&mut index.roots);
+            kept.retain(|root| paths.contains(&root.path));
+            index.roots = paths
+                .into_iter()
+                .map(|path| match kept.iter().position(|root| root.path == path) {
+                    Some(at) => kept.swap_remove(at),
+                    None => Root::new(path),
+                })
+                .collect();
+            index.dirty = true;
+        }
+        index.roots.iter().map(|root| root.path.clone()).collect::<Vec<_>>()
+    };
+    Ok(crate::jobs::spawn("REINDEX", move |job| {
+        watch(&roots);
+        scan_roots(&roots, job)
+    }))
+}
+
+/// Replace the watcher with one covering `roots`.
+fn watch(roots: &[String]) {
+    let Some(tx) = events().lock().unwrap().clone() else {
+        return;
+    };
+    index().lock().unwrap().watcher = None;
+    let mut watcher = match notify::recommended_watcher(move |event| {
+        let _ = tx.send(event);
+    }) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            tracing::warn!("Filesystem watching unavailable: {}", e);
+            return;
+        }
+    };
+    // Watching a large tree can take a while; do it outside the lock.
+    let results: Vec<(String, notify::Result<()>)> = roots
+        .iter()
+        .map(|root| (root.clone(), watcher.watch(Path::new(root), RecursiveMode::Recursive)))
+        .collect();
+    let mut index = index().lock().unwrap();
+    for (path, result) in results {
+        if let Some(root) = index.roots.iter_mut().find(|root| root.path == path) {
+            root.watching = result.is_ok();
+            root.watch_error = result.err().map(|e| format!("Not watching for changes: {}", e));
+        }
+    }
+    index.watcher = Some(watcher);
+}
+
+fn scan_roots(roots: &[String], job: &JobHandle) -> Result<ResponseData> {
+    let excluded = index().lock().unwrap().excluded.clone();
+    for path in roots {
+        job.begin_item(path);
+        {
+            let mut index = index().lock().unwrap();
+            let Some(root) = index.roots.iter_mut().find(|root| &root.path == path) else {
+                continue;
+            };
+            root.state = IndexState::Indexing;
+            root.pending.get_or_insert_with(Vec::new);
+        }
+
+        let started = Instant::now();
+        let scanned = scan_tree(path, &excluded, job);
+
+        let mut index = index().lock().unwrap();
+        let Some(root) = index.roots.iter_mut().find(|root| &root.path == path) else {
+            continue;
+        };
+        let pending = root.pending.take().unwrap_or_default();
+        let mut unscanned = Vec::new();
+        match scanned {
+            Ok(tree) => {
+                root.tree = Arc::new(tree);
+                root.state = IndexState::Ready;
+                root.error = None;
+                root.last_indexed = Some(Utc::now().timestamp());
+                for changed in pending {
+                    root.refresh(&changed, &excluded, &mut unscanned);
+                }
+                tracing::info!(
+                    "Indexed {} ({} files, {} directories) in {:?}",
+                    path,
+                    root.tree.files,
+                    root.tree.directories,
+                    started.elapsed()
+                );
+            }
+            Err(e) => {
+                root.state = if root.tree.nodes.is_empty() { IndexState::Failed } else { IndexState::Ready };
+                root.error = Some(e.to_string());
+                if job.is_cancelled() {
+                    return Err(e);
+                }
+            }
+        }
+        index.dirty = true;
+        drop(index);
+        scan_new(unscanned);
+    }
+
+    save().context("Failed to save search index")?;
+    Ok(ResponseData::IndexStatus(status()))
+}
+
+fn scan_tree(path: &str, excluded: &GlobSet, job: &JobHandle) -> Result<Tree> {
+    let metadata = fs::metadata(path).with_context(|| format!("Cannot read {}", path))?;
+    let mut tree = Tree::default();
+    tree.insert(Node::new(path.to_string(), NO_PARENT, 0, &metadata));
+    // The root itself is not counted.
+    tree.directories = 0;
+    tree.scan_below(0, Path::new(path), excluded, Some(job))?;
+    Ok(tree)
+}
+
+/// Entries matching a SEARCH_FILES query, below `path` when given. At most
+/// `limit` are returned; `total_matches` counts them all.
+pub fn query(query: &str, path: Option<&str>, limit: usize) -> Result<SearchResult> {
+    let query = SearchQuery::parse(query)?;
+    let needs_path = query.needs_path();
+    // Work on a snapshot so events and scans are not held up meanwhile.
+    let roots: Vec<(String, Arc<Tree>)> = {
+        let index = index().lock().unwrap();
+        if index.roots.is_empty() {
+            bail!("No index roots are configured; send REINDEX with roots first");
+        }
+        index.roots.iter().map(|root| (root.path.clone(), Arc::clone(&root.tree))).collect()
+    };
+
+    let mut matches = Vec::new();
+    let mut total = 0;
+    let mut consider = |tree: &Tree, id: u32, base: u32| {
+        let node = &tree.nodes[id as usize];
+        let relative = if needs_path { tree.relative(id, base) } else { String::new() };
+        let base_depth = tree.nodes[base as usize].depth;
+        let candidate = Candidate {
+            name: &node.name,
+            relative: &relative,
+            depth: (node.depth - base_depth) as usize,
+            kind: node.kind,
+            size: node.size,
+            modified: node.modified,
+            mode: node.mode,
+            uid: node.uid,
+            gid: node.gid,
+            owner: None,
+            group: None,
+        };
+        if query.matches(&candidate) {
+            total += 1;
+            if matches.len() < limit {
+                matches.push(file_info(tree, id));
+            }
+        }
+    };
+
+    match path {
+        None => {
+            for (_, tree) in &roots {
+                let nodes = tree.nodes.iter().enumerate().skip(1);
+                for (id, _) in nodes.filter(|(_, node)| node.live) {
+                    consider(tree, id as u32, 0);
+                }
+            }
+        }
+        Some(path) => {
+            let path = Path::new(path);
+            let mut found = false;
+            for (root, tree) in &roots {
+                let base = if path.starts_with(root) {
+                    let components: Vec<String> = path
+                        .strip_prefix(root)
+                        .unwrap_or(path)
+                        .components()
+                        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                        .collect();
+                    if tree.nodes.is_empty() {
+                        None
+                    } else {
+                        tree.find(&components)
+                    }
+                } else if Path::new(root).starts_with(path) {
+                    // The whole root lies below `path`.
+                    (!tree.nodes.is_empty()).then_some(0)
+                } else {
+                    None
+                };
+                let Some(base) = base else {
+                    continue;
+                };
+                found = true;
+                let mut stack: Vec<u32> = tree.nodes[base as usize].children.iter().rev().copied().collect();
+                while let Some(id) = stack.pop() {
+                    consider(tree, id, base);
+                    stack.extend(tree.nodes[id as usize].children.iter().rev());
+                }
+            }
+            if !found {
+                return Err(anyhow!("Path is not indexed: {}", path.display()));
+            }
+        }
+    }
+
+    Ok(SearchResult {
+        path: path.unwrap_or_default().to_string(),
+        matches,
+        total_matches: total,
+        ranks: Vec::new(),
+    })
+}
+
+fn file_info(tree: &Tree, id: u32) -> FileInfo {
+    let node = &tree.nodes[id as usize];
+    let file_type = match node.kind {
+        Kind::Directory => FileType::Directory,
+        Kind::Symlink => FileType::Symlink,
+        Kind::File => FileType::File,
+    };
+    let permissions = match node.mode {
+        Some(mode) if cfg!(unix) => format!("{:o}", mode & 0o777),
+        Some(mode) if mode & 0o200 == 0 => "r--".to_string(),
+        _ => "rw-".to_string(),
+    };
+    FileInfo {
+        name: node.name.clone(),
+        path: tree.path(id).to_string_lossy().into_owned(),
+        file_type,
+        size: node.size,
+        created: 0,
+        modified: node.modified,
+        accessed: 0,
+        permissions,
+        is_hidden: node.name.starts_with('.'),
+        archive_entry: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_reads_back_as_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "a").unwrap();
+        let mut root = Root::new(dir.path().to_string_lossy().into_owned());
+        root.tree = Arc::new(scan_tree(&root.path, &GlobSet::empty(), &JobHandle::detached("test")).unwrap());
+        root.last_indexed = Some(1_700_000_000);
+        root.error = Some("not saved".to_string());
+        let snapshot = Snapshot {
+            version: FORMAT_VERSION,
+            exclude: vec!["*.tmp".to_string()],
+            roots: vec![SavedRoot::of(&root)],
+        };
+
+        let saved: Saved = bincode::deserialize(&bincode::serialize(&snapshot).unwrap()).unwrap();
+        assert_eq!(saved.version, FORMAT_VERSION);
+        assert_eq!(saved.exclude, ["*.tmp"]);
+        assert_eq!(saved.roots.len(), 1);
+        assert_eq!(saved.roots[0].path, root.path);
+        assert_eq!(saved.roots[0].last_indexed, root.last_indexed);
+        assert_eq!(saved.roots[0].tree.nodes.len(), 2);
+        assert_eq!(saved.roots[0].error, None);
+    }
+}
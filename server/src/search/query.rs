@@ -0,0 +1,944 @@
+//! The SEARCH_FILES query language.
+//!
+//! A query is a list of terms, all of which must match. Terms are joined
+//! with `OR`, negated with `NOT` or a leading `-`, and grouped with
+//! parentheses:
+//!
+//! ```text
+//! report                    name contains "report" (any case)
+//! "annual report"           the same, with spaces
+//! *.rs  src/**/mod.rs       glob on the name, or on the path below the
+//!                           search root when it contains `/`
+//! /^v\d+\.log$/             regular expression on the name
+//! name:foo*  path:docs/*    explicit name / path match
+//! type:file|dir|symlink
+//! ext:rs,ts
+//! size>10M  size<=512k  size:0
+//! modified<7d               changed within the last 7 days
+//! modified>2024-01-31       changed after that day
+//! depth<=2                  at most two levels below the root
+//! owner:root  group:staff   user / group name or numeric id
+//! perm:644  perm:+111  perm:rwxr-xr-x
+//! ```
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{Local, NaiveDate, Utc};
+use globset::{GlobBuilder, GlobMatcher};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+/// What kind of filesystem object a candidate is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Kind {
+    File,
+    Directory,
+    Symlink,
+}
+
+/// The properties of a file or archive entry a query is evaluated against.
+#[derive(Debug, Clone)]
+pub struct Candidate<'a> {
+    pub name: &'a str,
+    /// Path below the search root, `/`-separated.
+    pub relative: &'a str,
+    /// 1 for the root's direct children.
+    pub depth: usize,
+    pub kind: Kind,
+    pub size: u64,
+    /// Unix timestamp; 0 if unknown.
+    pub modified: i64,
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// Owner and group names, as recorded in archives.
+    pub owner: Option<&'a str>,
+    pub group: Option<&'a str>,
+}
+
+impl<'a> Candidate<'a> {
+    pub fn from_metadata(name: &'a str, relative: &'a str, depth: usize, metadata: &fs::Metadata) -> Self {
+        let kind = if metadata.is_dir() {
+            Kind::Directory
+        } else if metadata.file_type().is_symlink() {
+            Kind::Symlink
+        } else {
+            Kind::File
+        };
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+
+        #[cfg(unix)]
+        let (mode, uid, gid) = {
+            use std::os::unix::fs::MetadataExt;
+            (Some(metadata.mode() & 0o7777), Some(metadata.uid()), Some(metadata.gid()))
+        };
+        #[cfg(not(unix))]
+        let (mode, uid, gid) = {
+            let mode = if metadata.permissions().readonly() { 0o444 } else { 0o666 };
+            (Some(mode), None, None)
+        };
+
+        Candidate {
+            name,
+            relative,
+            depth,
+            kind,
+            size: metadata.len(),
+            modified,
+            mode,
+            uid,
+            gid,
+            owner: None,
+            group: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cmp {
+    Lt,
+    Le,
+    Eq,
+    Ge,
+    Gt,
+}
+
+impl Cmp {
+    fn test<T: PartialOrd>(self, value: T, bound: T) -> bool {
+        match self {
+            Cmp::Lt => value < bound,
+            Cmp::Le => value <= bound,
+            Cmp::Eq => value == bound,
+            Cmp::Ge => value >= bound,
+            Cmp::Gt => value > bound,
+        }
+    }
+
+    /// The comparison with its operands swapped.
+    fn flip(self) -> Self {
+        match self {
+            Cmp::Lt => Cmp::Gt,
+            Cmp::Le => Cmp::Ge,
+            Cmp::Eq => Cmp::Eq,
+            Cmp::Ge => Cmp::Le,
+            Cmp::Gt => Cmp::Lt,
+        }
+    }
+}
+
+/// A user or group, by name and, when known on this host, by id.
+#[derive(Debug, Clone)]
+struct Principal {
+    name: String,
+    id: Option<u32>,
+}
+
+impl Principal {
+    fn matches(&self, id: Option<u32>, name: Option<&str>) -> bool {
+        matches!((self.id, id), (Some(a), Some(b)) if a == b) || name == Some(self.name.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Term {
+    /// Substring of the name, in any case.
+    Contains { text: String, lower: String },
+    /// Lowercased substring of the relative path.
+    PathContains(String),
+    NameGlob(GlobMatcher),
+    PathGlob(GlobMatcher),
+    Regex(Regex),
+    Kind(Vec<Kind>),
+    /// Lowercased extensions without the dot.
+    Ext(Vec<String>),
+    Size(Cmp, u64),
+    Modified(Cmp, i64),
+    Depth(Cmp, usize),
+    Owner(Principal),
+    Group(Principal),
+    /// Permission bits exactly equal to the mode.
+    PermExact(u32),
+    /// All of these permission bits set.
+    PermAll(u32),
+}
+
+impl Term {
+    fn matches(&self, c: &Candidate) -> bool {
+        match self {
+            Term::Contains { lower, .. } => contains_lowercase(c.name, lower),
+            Term::PathContains(text) => contains_lowercase(c.relative, text),
+            Term::NameGlob(glob) => glob.is_match(c.name),
+            Term::PathGlob(glob) => glob.is_match(c.relative),
+            Term::Regex(regex) => regex.is_match(c.name),
+            Term::Kind(kinds) => kinds.contains(&c.kind),
+            Term::Ext(exts) => {
+                let name = c.name.to_lowercase();
+                exts.iter().any(|ext| {
+                    name.len() > ext.len() + 1
+                        && name.ends_with(ext.as_str())
+                        && name.as_bytes()[name.len() - ext.len() - 1] == b'.'
+                })
+            }
+            Term::Size(cmp, bound) => cmp.test(c.size, *bound),
+            Term::Modified(cmp, bound) => c.modified != 0 && cmp.test(c.modified, *bound),
+            Term::Depth(cmp, bound) => cmp.test(c.depth, *bound),
+            Term::Owner(owner) => owner.matches(c.uid, c.owner),
+            Term::Group(group) => group.matches(c.gid, c.group),
+            Term::PermExact(bits) => c.mode.is_some_and(|mode| mode & 0o7777 == *bits),
+            Term::PermAll(bits) => c.mode.is_some_and(|mode| mode & bits == *bits),
+        }
+    }
+}
+
+/// Whether `haystack` contains the lowercased `needle`, in any case. ASCII
+/// names, the common case, are compared without allocating.
+fn contains_lowercase(haystack: &str, needle: &str) -> bool {
+    if haystack.is_ascii() && needle.is_ascii() {
+        let (haystack, needle) = (haystack.as_bytes(), needle.as_bytes());
+        return needle.is_empty()
+            || haystack
+                .windows(needle.len())
+                .any(|window| window.eq_ignore_ascii_case(needle));
+    }
+    haystack.to_lowercase().contains(needle)
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    All,
+    Term(Term),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+}
+
+impl Expr {
+    fn matches(&self, c: &Candidate) -> bool {
+        match self {
+            Expr::All => true,
+            Expr::Term(term) => term.matches(c),
+            Expr::Not(expr) => !expr.matches(c),
+            Expr::And(exprs) => exprs.iter().all(|e| e.matches(c)),
+            Expr::Or(exprs) => exprs.iter().any(|e| e.matches(c)),
+        }
+    }
+
+    fn uses_path(&self) -> bool {
+        match self {
+            Expr::Term(Term::PathContains(_) | Term::PathGlob(_)) => true,
+            Expr::Not(expr) => expr.uses_path(),
+            Expr::And(exprs) | Expr::Or(exprs) => exprs.iter().any(Expr::uses_path),
+            _ => false,
+        }
+    }
+
+    /// The deepest level any match can be at, if the query bounds it.
+    fn max_depth(&self) -> Option<usize> {
+        match self {
+            Expr::Term(Term::Depth(Cmp::Lt, n)) => Some(n.saturating_sub(1)),
+            Expr::Term(Term::Depth(Cmp::Le | Cmp::Eq, n)) => Some(*n),
+            Expr::And(exprs) => exprs.iter().filter_map(Expr::max_depth).min(),
+            Expr::Or(exprs) => exprs.iter().map(Expr::max_depth).try_fold(0, |max, d| Some(max.max(d?))),
+            _ => None,
+        }
+    }
+}
+
+/// A parsed SEARCH_FILES query.
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    expr: Expr,
+}
+
+impl SearchQuery {
+    pub fn parse(query: &str) -> Result<Self> {
+        let tokens = tokenize(query)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = if parser.tokens.is_empty() {
+            Expr::All
+        } else {
+            parser.or()?
+        };
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            bail!("Invalid search query: unexpected '{}'", token.text());
+        }
+        Ok(SearchQuery { expr })
+    }
+
+    /// Like `parse`, but a pattern that is not a valid query matches names
+    /// containing it literally, as SEARCH_FILES patterns did before the
+    /// query language existed.
+    pub fn parse_or_literal(query: &str) -> Self {
+        SearchQuery::parse(query).unwrap_or_else(|_| SearchQuery {
+            expr: Expr::Term(contains(query)),
+        })
+    }
+
+    pub fn matches(&self, candidate: &Candidate) -> bool {
+        self.expr.matches(candidate)
+    }
+
+    /// Split off the plain words (`report`, `"annual report"`) that are
+    /// ANDed into the query, for fuzzy mode to score itself. The rest of
+    /// the query still filters.
+    pub fn split_text(self) -> (String, SearchQuery) {
+        let is_text = |e: &Expr| matches!(e, Expr::Term(Term::Contains { .. }));
+        let text_of = |e: Expr| match e {
+            Expr::Term(Term::Contains { text, .. }) => text,
+            _ => unreachable!("filtered by is_text"),
+        };
+        let (text, rest) = match self.expr {
+            expr if is_text(&expr) => (vec![text_of(expr)], Expr::All),
+            Expr::And(exprs) => {
+                let (text, rest): (Vec<Expr>, Vec<Expr>) = exprs.into_iter().partition(is_text);
+                let rest = match rest.len() {
+                    0 => Expr::All,
+                    1 => rest.into_iter().next().expect("one expression"),
+                    _ => Expr::And(rest),
+                };
+                (text.into_iter().map(text_of).collect(), rest)
+            }
+            expr => (Vec::new(), expr),
+        };
+        (text.join(" "), SearchQuery { expr: rest })
+    }
+
+    /// Whether matching looks at `Candidate::relative` at all, so callers
+    /// can skip building it.
+    pub fn needs_path(&self) -> bool {
+        self.expr.uses_path()
+    }
+
+    /// How deep below the root a walk has to go; `None` when unbounded.
+    pub fn max_depth(&self) -> Option<usize> {
+        self.expr.max_depth()
+    }
+}
+
+// =======================
+// Tokens
+// =======================
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Regex(String),
+    Open,
+    Close,
+}
+
+impl Token {
+    fn text(&self) -> String {
+        match self {
+            Token::Word(w) => w.clone(),
+            Token::Quoted(q) => format!("\"{}\"", q),
+            Token::Regex(r) => format!("/{}/", r),
+            Token::Open => "(".to_string(),
+            Token::Close => ")".to_string(),
+        }
+    }
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '"' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => text.extend(chars.next()),
+                        Some(c) => text.push(c),
+                        None => bail!("Invalid search query: unterminated quote"),
+                    }
+                }
+                tokens.push(Token::Quoted(text));
+            }
+            '/' => {
+                chars.next();
+                let mut pattern = String::new();
+                loop {
+                    match chars.next() {
+                        Some('/') => break,
+                        // Keep the escape for the regex, except for an escaped slash.
+                        Some('\\') => match chars.next() {
+                            Some('/') => pattern.push('/'),
+                            Some(c) => {
+                                pattern.push('\\');
+                                pattern.push(c);
+                            }
+                            None => pattern.push('\\'),
+                        },
+                        Some(c) => pattern.push(c),
+                        None => bail!("Invalid search query: unterminated /regex/"),
+                    }
+                }
+                tokens.push(Token::Regex(pattern));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                    // `name:"two words"` keeps its quoted value in one token.
+                    if word.ends_with(":\"") {
+                        word.pop();
+                        loop {
+                            match chars.next() {
+                                Some('"') => break,
+                                Some(c) => word.push(c),
+                                None => bail!("Invalid search query: unterminated quote"),
+                            }
+                        }
+                    }
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// =======================
+// Parser
+// =======================
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w == keyword)
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut alternatives = vec![self.and()?];
+        while self.keyword("OR") {
+            self.pos += 1;
+            alternatives.push(self.and()?);
+        }
+        Ok(if alternatives.len() == 1 {
+            alternatives.remove(0)
+        } else {
+            Expr::Or(alternatives)
+        })
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut terms = vec![self.unary()?];
+        loop {
+            match self.peek() {
+                None | Some(Token::Close) => break,
+                Some(Token::Word(w)) if w == "OR" => break,
+                Some(Token::Word(w)) if w == "AND" => self.pos += 1,
+                _ => {}
+            }
+            terms.push(self.unary()?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            Expr::And(terms)
+        })
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| anyhow!("Invalid search query: missing term at the end"))?;
+        self.pos += 1;
+        match token {
+            Token::Word(w) if w == "NOT" => Ok(Expr::Not(Box::new(self.unary()?))),
+            Token::Word(w) if w == "AND" || w == "OR" => {
+                bail!("Invalid search query: '{}' needs a term before it", w)
+            }
+            Token::Word(w) if w.len() > 1 && w.starts_with('-') => {
+                Ok(Expr::Not(Box::new(word_term(&w[1..])?)))
+            }
+            Token::Word(w) => word_term(&w),
+            Token::Quoted(text) => Ok(Expr::Term(contains(&text))),
+            Token::Regex(pattern) => Ok(Expr::Term(Term::Regex(regex(&pattern)?))),
+            Token::Open => {
+                let expr = self.or()?;
+                if self.peek() != Some(&Token::Close) {
+                    bail!("Invalid search query: missing ')'");
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Token::Close => bail!("Invalid search query: unexpected ')'"),
+        }
+    }
+}
+
+/// A bare word: a `key:value` / `key>value` predicate, a glob or a substring.
+fn word_term(word: &str) -> Result<Expr> {
+    if let Some((key, cmp, value)) = split_predicate(word) {
+        return predicate(key, cmp, value);
+    }
+    Ok(Expr::Term(name_term(word)?))
+}
+
+const KEYS: [&str; 12] = [
+    "name", "path", "regex", "type", "ext", "size", "modified", "depth", "owner", "group", "perm", "re",
+];
+
+fn split_predicate(word: &str) -> Option<(&str, Cmp, &str)> {
+    let at = word.find([':', '<', '>', '='])?;
+    let key = &word[..at];
+    if !KEYS.contains(&key.to_ascii_lowercase().as_str()) {
+        return None;
+    }
+    let rest = &word[at..];
+    let (cmp, value) = [
+        ("<=", Cmp::Le),
+        (">=", Cmp::Ge),
+        ("<", Cmp::Lt),
+        (">", Cmp::Gt),
+        ("=", Cmp::Eq),
+        (":", Cmp::Eq),
+    ]
+    .iter()
+    .find_map(|(op, cmp)| rest.strip_prefix(op).map(|value| (*cmp, value)))?;
+    Some((key, cmp, value))
+}
+
+fn predicate(key: &str, cmp: Cmp, value: &str) -> Result<Expr> {
+    let key = key.to_ascii_lowercase();
+    if value.is_empty() {
+        bail!("Invalid search query: '{}' needs a value", key);
+    }
+    let ordered = matches!(key.as_str(), "size" | "modified" | "depth");
+    if cmp != Cmp::Eq && !ordered {
+        bail!("Invalid search query: '{}' cannot be compared with < or >", key);
+    }
+    let term = match key.as_str() {
+        "name" => name_term(value)?,
+        "path" if is_glob(value) => Term::PathGlob(glob(value)?),
+        "path" => Term::PathContains(value.to_lowercase()),
+        "regex" | "re" => Term::Regex(regex(value)?),
+        "type" => Term::Kind(
+            value
+                .split(['|', ','])
+                .map(|kind| match kind.to_ascii_lowercase().as_str() {
+                    "file" | "f" => Ok(Kind::File),
+                    "dir" | "directory" | "folder" | "d" => Ok(Kind::Directory),
+                    "symlink" | "link" | "l" => Ok(Kind::Symlink),
+                    other => Err(anyhow!("Invalid search query: unknown type '{}'", other)),
+                })
+                .collect::<Result<_>>()?,
+        ),
+        "ext" => Term::Ext(
+            value
+                .split(['|', ','])
+                .map(|ext| ext.trim_start_matches('.').to_lowercase())
+                .filter(|ext| !ext.is_empty())
+                .collect(),
+        ),
+        "size" => Term::Size(cmp, parse_size(value)?),
+        "modified" => return modified(cmp, value),
+        "depth" => Term::Depth(
+            cmp,
+            value.parse().map_err(|_| anyhow!("Invalid search query: bad depth '{}'", value))?,
+        ),
+        "owner" => Term::Owner(principal(value, lookup_uid)),
+        "group" => Term::Group(principal(value, lookup_gid)),
+        "perm" => parse_perm(value)?,
+        _ => unreachable!("key checked by split_predicate"),
+    };
+    Ok(Expr::Term(term))
+}
+
+fn name_term(value: &str) -> Result<Term> {
+    Ok(match (is_glob(value), value.contains('/')) {
+        (true, true) => Term::PathGlob(glob(value)?),
+        (true, false) => Term::NameGlob(glob(value)?),
+        (false, _) => contains(value),
+    })
+}
+
+fn contains(text: &str) -> Term {
+    Term::Contains {
+        text: text.to_string(),
+        lower: text.to_lowercase(),
+    }
+}
+
+fn is_glob(value: &str) -> bool {
+    value.contains(['*', '?', '[', '{'])
+}
+
+fn glob(pattern: &str) -> Result<GlobMatcher> {
+    Ok(GlobBuilder::new(pattern)
+        .case_insensitive(true)
+        .literal_separator(true)
+        .build()
+        .map_err(|e| anyhow!("Invalid search query: bad glob '{}': {}", pattern, e))?
+        .compile_matcher())
+}
+
+fn regex(pattern: &str) -> Result<Regex> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| anyhow!("Invalid search query: bad regex '{}': {}", pattern, e))
+}
+
+/// `10M`, `1.5GB`, `512k`, `100` (bytes); binary multiples.
+fn parse_size(value: &str) -> Result<u64> {
+    let bad = || anyhow!("Invalid search query: bad size '{}'", value);
+    let lower = value.to_ascii_lowercase();
+    let digits = lower.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let unit = &lower[digits.len()..];
+    let multiplier: u64 = match unit.trim_end_matches("ib").trim_end_matches('b') {
+        "" => 1,
+        "k" => 1 << 10,
+        "m" => 1 << 20,
+        "g" => 1 << 30,
+        "t" => 1 << 40,
+        _ => return Err(bad()),
+    };
+    let number: f64 = digits.parse().map_err(|_| bad())?;
+    if number < 0.0 {
+        return Err(bad());
+    }
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// `modified<7d` (age) or `modified>2024-01-31` (date, local time). An age
+/// compares backwards: less than 7 days old is later than 7 days ago.
+fn modified(cmp: Cmp, value: &str) -> Result<Expr> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        let start = date
+            .and_hms_opt(0, 0, 0)
+            .and_then(|t| t.and_local_timezone(Local).earliest())
+            .map(|t| t.timestamp())
+            .ok_or_else(|| anyhow!("Invalid search query: bad date '{}'", value))?;
+        let end = start + 86_400;
+        let term = |cmp, bound| Expr::Term(Term::Modified(cmp, bound));
+        return Ok(match cmp {
+            Cmp::Lt => term(Cmp::Lt, start),
+            Cmp::Le => term(Cmp::Lt, end),
+            Cmp::Gt => term(Cmp::Ge, end),
+            Cmp::Ge => term(Cmp::Ge, start),
+            Cmp::Eq => Expr::And(vec![term(Cmp::Ge, start), term(Cmp::Lt, end)]),
+        });
+    }
+
+    let bad = || anyhow!("Invalid search query: bad age '{}' (e.g. 30m, 12h, 7d, 2w, 1y or 2024-01-31)", value);
+    let digits = value.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let seconds: i64 = match &value[digits.len()..] {
+        "s" => 1,
+        "m" | "min" => 60,
+        "h" => 3_600,
+        "d" | "" => 86_400,
+        "w" => 7 * 86_400,
+        "mo" => 30 * 86_400,
+        "y" => 365 * 86_400,
+        _ => return Err(bad()),
+    };
+    let amount: f64 = digits.parse().map_err(|_| bad())?;
+    let age = amount * seconds as f64;
+    if !age.is_finite() || age < 0.0 {
+        return Err(bad());
+    }
+    // The cast saturates; ages beyond the epoch just match everything.
+    let since = Utc::now().timestamp().saturating_sub(age as i64);
+    // `modified:7d` reads as "within 7 days".
+    let cmp = if cmp == Cmp::Eq { Cmp::Le } else { cmp };
+    Ok(Expr::Term(Term::Modified(cmp.flip(), since)))
+}
+
+/// `644` (exactly), `+111` (all these bits) or `rwxr-xr-x` (exactly).
+fn parse_perm(value: &str) -> Result<Term> {
+    let bad = || anyhow!("Invalid search query: bad permission '{}' (e.g. 644, +111, rwxr-xr-x)", value);
+    let octal = |digits: &str| {
+        if digits.is_empty() || digits.len() > 4 {
+            return Err(bad());
+        }
+        u32::from_str_radix(digits, 8).map_err(|_| bad())
+    };
+    if let Some(bits) = value.strip_prefix('+') {
+        return Ok(Term::PermAll(octal(bits)?));
+    }
+    if value.len() == 9 && value.chars().all(|c| "rwx-".contains(c)) {
+        let mut bits = 0;
+        for (i, c) in value.chars().enumerate() {
+            if c != '-' {
+                if c != ['r', 'w', 'x'][i % 3] {
+                    return Err(bad());
+                }
+                bits |= 1 << (8 - i);
+            }
+        }
+        return Ok(Term::PermExact(bits));
+    }
+    Ok(Term::PermExact(octal(value)?))
+}
+
+fn principal(value: &str, lookup: fn(&str) -> Option<u32>) -> Principal {
+    Principal {
+        name: value.to_string(),
+        id: value.parse().ok().or_else(|| lookup(value)),
+    }
+}
+
+#[cfg(unix)]
+fn lookup_uid(name: &str) -> Option<u32> {
+    let name = std::ffi::CString::new(name).ok()?;
+    let mut entry = std::mem::MaybeUninit::<libc::passwd>::uninit();
+    let mut buf = vec![0 as libc::c_char; 16 * 1024];
+    let mut result = std::ptr::null_mut();
+    // SAFETY: every pointer is valid for the call; `result` is non-null only
+    // when `entry` was filled in.
+    let rc = unsafe {
+        libc::getpwnam_r(name.as_ptr(), entry.as_mut_ptr(), buf.as_mut_ptr(), buf.len(), &mut result)
+    };
+    (rc == 0 && !result.is_null()).then(|| unsafe { entry.assume_init() }.pw_uid)
+}
+
+#[cfg(unix)]
+fn lookup_gid(name: &str) -> Option<u32> {
+    let name = std::ffi::CString::new(name).ok()?;
+    let mut entry = std::mem::MaybeUninit::<libc::group>::uninit();
+    let mut buf = vec![0 as libc::c_char; 16 * 1024];
+    let mut result = std::ptr::null_mut();
+    // SAFETY: as in `lookup_uid`.
+    let rc = unsafe {
+        libc::getgrnam_r(name.as_ptr(), entry.as_mut_ptr(), buf.as_mut_ptr(), buf.len(), &mut result)
+    };
+    (rc == 0 && !result.is_null()).then(|| unsafe { entry.assume_init() }.gr_gid)
+}
+
+#[cfg(not(unix))]
+fn lookup_uid(_name: &str) -> Option<u32> {
+    None
+}
+
+#[cfg(not(unix))]
+fn lookup_gid(_name: &str) -> Option<u32> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str) -> Candidate<'_> {
+        Candidate {
+            name,
+            relative: name,
+            depth: 0,
+            kind: Kind::File,
+            size: 0,
+            modified: Utc::now().timestamp(),
+            mode: Some(0o100644),
+            uid: None,
+            gid: None,
+            owner: None,
+            group: None,
+        }
+    }
+
+    fn matches(query: &str, candidate: &Candidate) -> bool {
+        SearchQuery::parse(query).unwrap().matches(candidate)
+    }
+
+    #[test]
+    fn tokenizes_quotes_regexes_and_parentheses() {
+        assert_eq!(
+            tokenize(r#"(a "b c") /x\/y\d/ name:"two words""#).unwrap(),
+            vec![
+                Token::Open,
+                Token::Word("a".into()),
+                Token::Quoted("b c".into()),
+                Token::Close,
+                Token::Regex(r"x/y\d".into()),
+                Token::Word("name:two words".into()),
+            ]
+        );
+        assert!(tokenize("\"open").is_err());
+        assert!(tokenize("/open").is_err());
+        assert!(tokenize("name:\"open").is_err());
+    }
+
+    #[test]
+    fn combines_terms() {
+        let report = file("Annual Report.pdf");
+        assert!(matches("annual report", &report));
+        assert!(matches("\"annual report\"", &report));
+        assert!(!matches("\"report annual\"", &report));
+        assert!(matches("missing OR report", &report));
+        assert!(!matches("missing AND report", &report));
+        assert!(!matches("NOT report", &report));
+        assert!(!matches("-report", &report));
+        assert!(matches("-missing", &report));
+        assert!(matches("(missing OR annual) pdf", &report));
+        assert!(matches("", &report));
+    }
+
+    #[test]
+    fn rejects_malformed_queries() {
+        for query in ["(a", "a)", "OR a", "a OR", "NOT", "size:", "type:socket", "ext>rs", "/(/"] {
+            assert!(SearchQuery::parse(query).is_err(), "{query}");
+        }
+    }
+
+    #[test]
+    fn falls_back_to_a_literal_match() {
+        let odd = file("(draft");
+        assert!(SearchQuery::parse_or_literal("(draft").matches(&odd));
+        assert!(!SearchQuery::parse_or_literal("(draft").matches(&file("draft")));
+        assert!(SearchQuery::parse_or_literal("draft OR x").matches(&file("draft")));
+    }
+
+    #[test]
+    fn matches_globs_and_regexes() {
+        let mut candidate = file("mod.RS");
+        candidate.relative = "src/search/mod.RS";
+        assert!(matches("*.rs", &candidate));
+        assert!(matches("src/**/mod.rs", &candidate));
+        assert!(!matches("src/*/mod.rs", &file("mod.rs")));
+        assert!(matches("path:search", &candidate));
+        assert!(matches("/^mod\\.rs$/", &candidate));
+        assert!(matches("re:^MOD", &candidate));
+        assert!(SearchQuery::parse("path:src").unwrap().needs_path());
+        assert!(!SearchQuery::parse("*.rs").unwrap().needs_path());
+    }
+
+    #[test]
+    fn matches_type_ext_size_and_depth() {
+        let mut candidate = file("archive.tar.GZ");
+        candidate.size = 3 << 20;
+        candidate.depth = 2;
+        assert!(matches("type:file", &candidate));
+        assert!(!matches("type:dir|symlink", &candidate));
+        assert!(matches("ext:.gz", &candidate));
+        assert!(matches("ext:zip,gz", &candidate));
+        assert!(!matches("ext:tar", &candidate));
+        assert!(!matches("ext:gz", &file("gz")));
+        assert!(matches("size>2M size<=3MiB", &candidate));
+        assert!(!matches("size>3M", &candidate));
+        assert!(matches("size>=2.5mb", &candidate));
+        assert!(matches("depth:2 depth<3", &candidate));
+        assert!(!matches("depth>2", &candidate));
+    }
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("100").unwrap(), 100);
+        assert_eq!(parse_size("512k").unwrap(), 512 << 10);
+        assert_eq!(parse_size("1.5GB").unwrap(), 3 << 29);
+        assert_eq!(parse_size("2TiB").unwrap(), 2 << 40);
+        assert!(parse_size("10X").is_err());
+        assert!(parse_size("-1k").is_err());
+        assert!(parse_size("k").is_err());
+    }
+
+    #[test]
+    fn bounds_depth() {
+        let depth = |query: &str| SearchQuery::parse(query).unwrap().max_depth();
+        assert_eq!(depth("depth<3"), Some(2));
+        assert_eq!(depth("depth<=3 depth:1"), Some(1));
+        assert_eq!(depth("depth:1 OR depth<=4"), Some(4));
+        assert_eq!(depth("depth:1 OR report"), None);
+        assert_eq!(depth("depth>1"), None);
+    }
+
+    #[test]
+    fn matches_ages_and_dates() {
+        let now = Utc::now().timestamp();
+        let mut candidate = file("log");
+        candidate.modified = now - 3 * 86_400;
+        assert!(matches("modified<7d", &candidate));
+        assert!(matches("modified:1w", &candidate));
+        assert!(!matches("modified<2d", &candidate));
+        assert!(matches("modified>48h", &candidate));
+        assert!(!matches("modified<1y", &file_unmodified()));
+        assert!(matches("modified>2000-01-01", &candidate));
+        assert!(!matches("modified<2000-01-01", &candidate));
+        assert!(SearchQuery::parse("modified:2024-02-30").is_err());
+        assert!(SearchQuery::parse("modified<7x").is_err());
+    }
+
+    fn file_unmodified() -> Candidate<'static> {
+        Candidate { modified: 0, ..file("unknown") }
+    }
+
+    #[test]
+    fn rejects_ages_that_overflow() {
+        assert!(SearchQuery::parse("modified<-1e300d").is_err());
+        assert!(SearchQuery::parse("modified<-1d").is_err());
+        assert!(SearchQuery::parse("modified<1e400d").is_err());
+        // Ages beyond the epoch saturate and match every dated file.
+        assert!(matches("modified<1e300d", &file("old")));
+        assert!(!matches("modified>1e300d", &file("old")));
+    }
+
+    #[test]
+    fn matches_permissions() {
+        let candidate = file("run.sh");
+        assert!(matches("perm:644", &candidate));
+        assert!(matches("perm:rw-r--r--", &candidate));
+        assert!(!matches("perm:+111", &candidate));
+        assert!(matches("perm:+044", &candidate));
+        assert!(SearchQuery::parse("perm:none").is_err());
+        assert!(parse_perm("rwxrwxrwz").is_err());
+        assert!(parse_perm("wrxr-xr-x").is_err());
+        assert!(parse_perm("12345").is_err());
+        assert!(parse_perm("8").is_err());
+    }
+
+    #[test]
+    fn matches_numeric_principals() {
+        let mut candidate = file("x");
+        candidate.uid = Some(1000);
+        candidate.group = Some("staff");
+        assert!(matches("owner:1000", &candidate));
+        assert!(!matches("owner:1001", &candidate));
+        assert!(matches("group:staff", &candidate));
+    }
+
+    #[test]
+    fn splits_plain_text_from_filters() {
+        let (text, rest) = SearchQuery::parse("main \"read me\" ext:rs").unwrap().split_text();
+        assert_eq!(text, "main read me");
+        let mut candidate = file("anything.rs");
+        assert!(rest.matches(&candidate));
+        candidate.name = "anything.ts";
+        assert!(!rest.matches(&candidate));
+        let (text, _) = SearchQuery::parse("a OR b").unwrap().split_text();
+        assert_eq!(text, "");
+    }
+}
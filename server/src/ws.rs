@@ -1,48 +1,70 @@
 use axum::{
-    extract::ws::{WebSocket, WebSocketUpgrade},
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
     response::IntoResponse,
 };
-use futures_util::{StreamExt};
-use tracing::info;
+use futures_util::{SinkExt, StreamExt};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::commands::CommandExecutor;
+use crate::handlers;
+use crate::protocol::{Command, WebSocketMessage};
+
+/// Outgoing messages buffered per connection; a slow client holds back
+/// streaming commands instead of growing memory.
+const OUTGOING_BUFFER: usize = 64;
 
 pub async fn websocket_handler(ws: WebSocketUpgrade) -> impl IntoResponse {
     ws.on_upgrade(handle_socket)
 }
 
-async fn handle_socket(mut socket: WebSocket) {
+async fn handle_socket(socket: WebSocket) {
     info!("WebSocket connection established");
+    let (mut sender, mut receiver) = socket.split();
 
-    while let Some(Ok(msg)) = socket.next().await {
-        match msg {
-            axum::extract::ws::Message::Text(text) => {
-                info!("Received text message: {}", text);
-                // Echo back
-                if socket
-                    .send(axum::extract::ws::Message::Text(text))
-                    .await
-                    .is_err()
-                {
-                    break;
+    // Commands run concurrently on blocking threads; a single writer task
+    // serialises everything they send.
+    let (tx, mut rx) = mpsc::channel::<WebSocketMessage>(OUTGOING_BUFFER);
+    tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            let text = match serde_json::to_string(&message) {
+                Ok(text) => text,
+                Err(e) => {
+                    warn!("Failed to serialize WebSocket message: {}", e);
+                    continue;
                 }
+            };
+            if sender.send(Message::Text(text)).await.is_err() {
+                break;
             }
-            axum::extract::ws::Message::Binary(data) => {
-                info!("Received binary message of {} bytes", data.len());
-                // Echo back
-                if socket
-                    .send(axum::extract::ws::Message::Binary(data))
-                    .await
-                    .is_err()
-                {
-                    break;
+        }
+    });
+
+    while let Some(Ok(msg)) = receiver.next().await {
+        match msg {
+            Message::Text(text) => match serde_json::from_str::<WebSocketMessage>(&text) {
+                Ok(WebSocketMessage::Command { payload }) => {
+                    let tx = tx.clone();
+                    tokio::task::spawn_blocking(move || run_command(payload, &tx));
+                }
+                Ok(WebSocketMessage::Ping) => {
+                    if tx.send(WebSocketMessage::Pong).await.is_err() {
+                        break;
+                    }
                 }
+                Ok(_) => warn!("Ignoring unexpected WebSocket message"),
+                Err(e) => warn!("Invalid WebSocket message: {}", e),
+            },
+            Message::Binary(data) => {
+                warn!("Ignoring binary message of {} bytes", data.len());
             }
-            axum::extract::ws::Message::Ping(_) => {
+            Message::Ping(_) => {
                 // Auto-respond with Pong
             }
-            axum::extract::ws::Message::Pong(_) => {
+            Message::Pong(_) => {
                 // Ignore
             }
-            axum::extract::ws::Message::Close(_) => {
+            Message::Close(_) => {
                 info!("Client closed connection");
                 break;
             }
@@ -50,4 +72,19 @@ async fn handle_socket(mut socket: WebSocket) {
     }
 
     info!("WebSocket connection closed");
-}
\ No newline at end of file
+}
+
+/// Execute one command, sending any streamed results and then its response.
+/// Once the client is gone the sends fail and a running search stops.
+fn run_command(command: Command, tx: &mpsc::Sender<WebSocketMessage>) {
+    let command_id = command.id().to_string();
+    handlers::log_command(&command);
+    let response = CommandExecutor::execute_streamed(command, &mut |matches| {
+        tx.blocking_send(WebSocketMessage::ContentMatches {
+            command_id: command_id.clone(),
+            payload: matches,
+        })
+        .is_ok()
+    });
+    let _ = tx.blocking_send(WebSocketMessage::Response { payload: response });
+}
@@ -68,6 +68,11 @@ export enum CommandType {
   COPY_FILE = 'COPY_FILE',
   GET_FILE_INFO = 'GET_FILE_INFO',
   SEARCH_FILES = 'SEARCH_FILES',
+  SEARCH_CONTENT = 'SEARCH_CONTENT',
+  INDEX_STATUS = 'INDEX_STATUS',
+  REINDEX = 'REINDEX',
+  QUERY_INDEX = 'QUERY_INDEX',
+  COMPLETE_PATH = 'COMPLETE_PATH',
   GET_OS_INFO = 'GET_OS_INFO',
 }
 
@@ -86,16 +91,23 @@ export interface ListDrivesCommand extends BaseCommand {
   type: CommandType.LIST_DRIVES;
 }
 
+/**
+ * LIST_DIRECTORY, READ_FILE, GET_FILE_INFO, SEARCH_FILES and the source of
+ * COPY_FILE also accept paths that cross into an archive, written with `!`:
+ * `/home/me/x.zip!/docs/readme.md`. `password` unlocks encrypted archives.
+ */
 export interface ListDirectoryCommand extends BaseCommand {
   type: CommandType.LIST_DIRECTORY;
   path: string;
   showHidden?: boolean;
+  password?: string;
 }
 
 export interface ReadFileCommand extends BaseCommand {
   type: CommandType.READ_FILE;
   path: string;
   encoding?: 'utf8' | 'base64';
+  password?: string;
 }
 
 export interface WriteFileCommand extends BaseCommand {
@@ -131,18 +143,115 @@ export interface CopyFileCommand extends BaseCommand {
   destination: string;
   /** Required when copying a directory */
   recursive?: boolean;
+  password?: string;
 }
 
 export interface GetFileInfoCommand extends BaseCommand {
   type: CommandType.GET_FILE_INFO;
   path: string;
+  password?: string;
 }
 
 export interface SearchFilesCommand extends BaseCommand {
   type: CommandType.SEARCH_FILES;
   path: string;
+  /**
+   * Query: words match names containing them, `*.rs` / `docs/*.md` are
+   * globs, `/re/` a regex. Predicates: `type:file|dir|symlink`, `ext:rs,ts`,
+   * `size>10M`, `modified<7d`, `modified>2024-01-31`, `depth<=2`,
+   * `owner:root`, `group:staff`, `perm:644`, `perm:+111`. Terms are ANDed;
+   * use `OR`, `NOT` / `-term` and parentheses to combine them.
+   *
+   * Protocol change: these operators used to be plain text. A pattern that
+   * does not parse as a query still matches names containing it literally.
+   */
   pattern: string;
   recursive?: boolean;
+  password?: string;
+  /** Also match entry names inside archives found while walking */
+  include_archives?: boolean;
+  /** Levels of nested archives to open (default 1) */
+  archive_depth?: number;
+  /** Skip archives larger than this many bytes (default 256 MiB) */
+  max_archive_size?: number;
+  /**
+   * Rank matches by how well their names fit the plain words of the query
+   * (typos, camelCase and separators allowed); predicates still filter.
+   */
+  fuzzy?: boolean;
+  /** Recently used paths, most recent first; they rank higher in fuzzy mode */
+  recent_paths?: string[];
+  /** Most matches returned in fuzzy mode (default 100) */
+  limit?: number;
+}
+
+export interface ContentSearchOptions {
+  /** Treat the query as a regular expression instead of a literal string */
+  regex?: boolean;
+  case_sensitive?: boolean;
+  whole_word?: boolean;
+  /** Skip files larger than this many bytes (default 10 MiB) */
+  max_file_size?: number;
+  /** Also search files that look binary (a NUL byte near the start) */
+  include_binary?: boolean;
+  /** Globs; when given, only matching files are searched */
+  include?: string[];
+  /** Globs of files and directories to skip, e.g. "node_modules" */
+  exclude?: string[];
+  /** Lines of context before and after each match */
+  context_lines?: number;
+  /** Stop after this many matching lines (default 10000) */
+  max_results?: number;
+}
+
+/**
+ * Search file contents below `path`. Over the WebSocket each file's matches
+ * arrive as a CONTENT_MATCHES message before the RESPONSE, whose `files` is
+ * then empty.
+ */
+export interface SearchContentCommand extends BaseCommand {
+  type: CommandType.SEARCH_CONTENT;
+  path: string;
+  query: string;
+  options?: ContentSearchOptions;
+}
+
+export interface IndexStatusCommand extends BaseCommand {
+  type: CommandType.INDEX_STATUS;
+}
+
+/**
+ * Rescan the server's search index in a background job. `roots` and
+ * `exclude` (globs), when given, replace the configured ones.
+ */
+export interface ReindexCommand extends BaseCommand {
+  type: CommandType.REINDEX;
+  roots?: string[];
+  exclude?: string[];
+}
+
+/** Answer a SEARCH_FILES query from the search index. */
+export interface QueryIndexCommand extends BaseCommand {
+  type: CommandType.QUERY_INDEX;
+  query: string;
+  /** Only entries below this path */
+  path?: string;
+  /** Most matches to return (default 1000) */
+  limit?: number;
+}
+
+export interface CompletePathCommand extends BaseCommand {
+  type: CommandType.COMPLETE_PATH;
+  /**
+   * Partially typed path. `~` and `$VAR` / `${VAR}` are expanded; each
+   * segment matches by prefix or fuzzily (`/us/lo/bi`), and `archive.zip!/`
+   * completes inside archives.
+   */
+  path: string;
+  /** Directory that relative paths are taken below */
+  cwd?: string;
+  /** Most completions to return (default 20) */
+  limit?: number;
 }
 
 export type Command =
@@ -156,7 +265,12 @@ export type Command =
   | MoveFileCommand
   | CopyFileCommand
   | GetFileInfoCommand
-  | SearchFilesCommand;
+  | SearchFilesCommand
+  | SearchContentCommand
+  | IndexStatusCommand
+  | ReindexCommand
+  | QueryIndexCommand
+  | CompletePathCommand;
 
 // ============================================================================
 // Responses (Server -> Client)
@@ -216,6 +330,11 @@ export interface FileInfo {
   /** e.g. "rwxr-xr-x" on Unix, "rw-" / "r--" on Windows */
   permissions: string;
   isHidden: boolean;
+  /**
+   * Entry details (mode, owner, compression, ...) when `path` is inside an
+   * archive, e.g. `/home/me/logs.zip!/app/server.log`.
+   */
+  archiveEntry?: Record<string, unknown>;
 }
 
 export interface DirectoryListing {
@@ -237,10 +356,94 @@ export interface OperationResult {
   affectedPaths?: string[];
 }
 
+export interface MatchRank {
+  /** Higher is better */
+  score: number;
+  /** [start, end) ranges of the name that matched, in UTF-16 code units */
+  ranges: [number, number][];
+}
+
 export interface SearchResult {
   path: string;
   matches: FileInfo[];
   totalMatches: number;
+  /** Fuzzy mode only: one per match, in the same order */
+  ranks?: MatchRank[];
+}
+
+export interface LineMatch {
+  /** 1-based */
+  lineNumber: number;
+  line: string;
+  /** [start, end) offsets into `line` of each match */
+  ranges: [number, number][];
+  before?: string[];
+  after?: string[];
+}
+
+export interface ContentMatches {
+  path: string;
+  matches: LineMatch[];
+}
+
+export interface ContentSearchResult {
+  path: string;
+  /** Every match over HTTP; empty when the matches were streamed */
+  files: ContentMatches[];
+  filesSearched: number;
+  filesMatched: number;
+  /** Matching lines */
+  totalMatches: number;
+  skippedBinary: number;
+  skippedLarge: number;
+  /** The search stopped at `max_results` */
+  truncated: boolean;
+}
+
+export type IndexState = 'PENDING' | 'INDEXING' | 'READY' | 'FAILED';
+
+export interface IndexRootStatus {
+  path: string;
+  state: IndexState;
+  files: number;
+  directories: number;
+  /** Unix timestamp of the last completed scan */
+  lastIndexed: number | null;
+  /** Changes are picked up from filesystem events */
+  watching: boolean;
+  error?: string;
+}
+
+export interface IndexStatus {
+  roots: IndexRootStatus[];
+  exclude: string[];
+  totalFiles: number;
+  totalDirectories: number;
+  indexFile: string | null;
+  lastSaved: number | null;
+}
+
+export interface PathCompletion {
+  /** Completed path; a virtual path inside archives */
+  path: string;
+  name: string;
+  type: FileType;
+  /** Append `!/` to complete inside it */
+  isArchive: boolean;
+  /** Higher is better */
+  score: number;
+  /** [start, end) ranges of `path` that matched, in UTF-16 code units */
+  ranges: [number, number][];
+}
+
+export interface PathCompletions {
+  input: string;
+  /** `input` with `~` and environment variables expanded */
+  expanded: string;
+  /** Best first */
+  completions: PathCompletion[];
+  /** The server's time budget ran out before every candidate was checked */
+  truncated: boolean;
 }
 
 // ============================================================================
@@ -253,11 +456,15 @@ export enum MessageType {
   PING = 'PING',
   PONG = 'PONG',
   AUTH = 'AUTH',
+  /** One file's matches for a running SEARCH_CONTENT */
+  CONTENT_MATCHES = 'CONTENT_MATCHES',
 }
 
 export interface WebSocketMessage {
   type: MessageType;
   payload: unknown;
+  /** The command a CONTENT_MATCHES message belongs to */
+  command_id?: string;
 }
 
 export interface AuthPayload {